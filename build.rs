@@ -0,0 +1,34 @@
+// With the embedded-assets feature, bakes every file in assets/ into the game
+// so it can ship as a single executable. Otherwise (or without an assets/
+// folder) the list is empty and the game synthesizes everything as usual.
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=assets");
+    println!("cargo:rerun-if-env-changed=CARGO_FEATURE_EMBEDDED_ASSETS");
+
+    let mut entries = String::new();
+    if env::var_os("CARGO_FEATURE_EMBEDDED_ASSETS").is_some() {
+        let mut paths: Vec<_> = fs::read_dir("assets")
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file())
+            .collect();
+        paths.sort();
+        for path in paths {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            let full = fs::canonicalize(&path).unwrap();
+            entries.push_str(&format!("    ({:?}, include_bytes!({:?})),\n", name, full));
+        }
+    }
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("embedded_assets.rs");
+    let code = format!(
+        "const EMBEDDED_ASSETS: &[(&str, &[u8])] = &[\n{}];\n",
+        entries
+    );
+    fs::write(out, code).unwrap();
+}
@@ -0,0 +1,2889 @@
+// Core game logic: physics, pipes, scoring, difficulty settings and save data.
+// Nothing in here uses macroquad, so tests, bots and benchmarks can run the
+// simulation without a window; the binary draws it and feeds it input.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+// Default physics; players can soften them from the settings screen
+pub const GRAVITY: f32 = 0.5;
+pub const JUMP_STRENGTH: f32 = -8.0;
+pub const BIRD_SIZE: f32 = 30.0;
+pub const PIPE_WIDTH: f32 = 60.0;
+// The caps at the gap end of each pipe are this tall and stick out this far on
+// each side; they're solid too
+pub const PIPE_CAP_HEIGHT: f32 = 20.0;
+pub const PIPE_CAP_OVERHANG: f32 = 5.0;
+pub const PIPE_GAP: f32 = 180.0;
+pub const PIPE_SPEED: f32 = 2.5;
+pub const GROUND_HEIGHT: f32 = 80.0;
+pub const BIRD_START_X: f32 = 150.0;
+pub const PIPE_SPAWN_INTERVAL: f32 = 90.0;
+pub const PIPE_ENTRANCE_FRAMES: f32 = 12.0;
+pub const GAP_SHRINK_PER_POINT: f32 = 1.0;
+pub const GAP_SHRINK_MAX: f32 = 40.0;
+// The narrowest gap any difficulty can reach. One flap lifts the bird
+// JUMP_STRENGTH² / (2 × GRAVITY) = 64px, which leaves room to spare around its
+// 20px hitbox inside a 100px gap.
+pub const MIN_PIPE_GAP: f32 = 100.0;
+// Warm-up: the first WARM_UP_PIPES pipes of a run get extra gap, starting at
+// WARM_UP_GAP_BONUS and shrinking evenly to nothing. Never on Extreme.
+pub const WARM_UP_PIPES: u32 = 3;
+pub const WARM_UP_GAP_BONUS: f32 = 60.0;
+// Closest pipes may ever spawn to each other when pipe density packs them tighter
+pub const MIN_PIPE_SPACING: f32 = 160.0;
+// Chance a new pipe carries a bonus gate, the gate's half-height as a fraction
+// of the gap, and the extra points for flying through it
+pub const BONUS_GATE_CHANCE: f32 = 0.12;
+pub const BONUS_GATE_BAND: f32 = 0.15;
+pub const BONUS_GATE_POINTS: i32 = 2;
+// Chance a pipe without a bonus gate carries an extra-flap pickup in its gap,
+// how close the bird's center has to come to collect it, how many flaps one
+// banks (never more than EXTRA_JUMP_MAX at once) and how much stronger than a
+// normal flap each of them is
+pub const EXTRA_JUMP_CHANCE: f32 = 0.06;
+pub const EXTRA_JUMP_PICKUP_RADIUS: f32 = 12.0;
+pub const EXTRA_JUMPS_PER_PICKUP: u32 = 2;
+pub const EXTRA_JUMP_MAX: u32 = 3;
+pub const EXTRA_JUMP_SCALE: f32 = 1.3;
+// Random gaps keep this far clear of the ceiling and the ground when there's room
+const GAP_TOP_MARGIN: f32 = 150.0;
+const GAP_BOTTOM_MARGIN: f32 = 100.0;
+// Moving pipes: how far the gap drifts either way and how fast, in radians a frame
+pub const MOVING_PIPE_DRIFT: f32 = 35.0;
+pub const MOVING_PIPE_RATE: f32 = 0.05;
+// Double pipes: the open space between the pair, and how far the second gap
+// may sit above or below the first
+pub const DOUBLE_PIPE_SPACING: f32 = 100.0;
+pub const DOUBLE_PIPE_SHIFT: f32 = 40.0;
+// Enemies: hitbox size, and how far and fast they bob around their height
+pub const ENEMY_SIZE: f32 = 24.0;
+pub const ENEMY_BOB: f32 = 50.0;
+pub const ENEMY_BOB_RATE: f32 = 0.06;
+// Gravity curve: gravity starts at GRAVITY_CURVE_START times its usual pull
+// when the bird begins to fall and ramps up to GRAVITY_CURVE_END times it as
+// the fall speed approaches TERMINAL_VELOCITY, which it never exceeds. Rising
+// uses the usual pull, so a flap still lifts the bird as high.
+pub const GRAVITY_CURVE_START: f32 = 0.6;
+pub const GRAVITY_CURVE_END: f32 = 1.4;
+pub const TERMINAL_VELOCITY: f32 = 12.0;
+// Fall assist weakens gravity by the chosen fraction while the bird is falling
+// faster than this many pixels per frame, buying time to react
+pub const FALL_ASSIST_THRESHOLD: f32 = 4.0;
+// Flap boost: a flap FLAP_BOOST_MIN_FRAMES to FLAP_BOOST_MAX_FRAMES after the
+// previous one adds FLAP_BOOST_STEP to the boost, a quicker one throws it all
+// away and a slower one keeps what's left. The boost drains by
+// FLAP_BOOST_DECAY a frame, and a full 1.0 makes flaps FLAP_BOOST_MAX_BONUS
+// stronger, so a steady rhythm climbs a little faster than mashing.
+pub const FLAP_BOOST_MIN_FRAMES: f32 = 12.0;
+pub const FLAP_BOOST_MAX_FRAMES: f32 = 30.0;
+pub const FLAP_BOOST_STEP: f32 = 0.25;
+pub const FLAP_BOOST_DECAY: f32 = 0.005;
+pub const FLAP_BOOST_MAX_BONUS: f32 = 0.15;
+// Fraction of the way the bird's tilt moves towards its velocity-based target
+// each frame, so it eases into a dive instead of snapping
+pub const ROTATION_SMOOTHING: f32 = 0.25;
+// Fraction a fully tilted (90 degree) bird's hitbox narrows by, and grows
+// taller by (see Bird::get_bounds)
+pub const BIRD_BOUNDS_TILT_SQUEEZE: f32 = 0.2;
+// Assist mode shrinks the bird's hitbox to this fraction of its size, so
+// grazing a pipe edge is forgiven; the bird is drawn at full size either way
+pub const ASSIST_HITBOX_SCALE: f32 = 0.75;
+// Fuel mode: a full tank is 1.0, each flap burns FUEL_PER_FLAP and it refills a
+// little every frame. Refilling slightly faster than a steady flap every ~30
+// frames burns keeps rhythm play going while mashing runs dry within a few flaps.
+pub const FUEL_PER_FLAP: f32 = 0.25;
+pub const FUEL_REFILL_PER_FRAME: f32 = 0.009;
+// A pass is "clean" if the bird stays within this fraction of the gap height from its center
+pub const CLEAN_PASS_TOLERANCE: f32 = 0.25;
+// A pass is a near miss if the bird's hitbox came within this many pixels of
+// the pipe without touching it
+pub const NEAR_MISS_MARGIN: f32 = 6.0;
+
+// Axis-aligned rectangle used for hitboxes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl Bounds {
+    pub fn new(x: f32, y: f32, w: f32, h: f32) -> Self {
+        Self { x, y, w, h }
+    }
+
+    // Touching edges count as overlapping
+    pub fn overlaps(&self, other: &Bounds) -> bool {
+        self.x <= other.x + other.w
+            && self.x + self.w >= other.x
+            && self.y <= other.y + other.h
+            && self.y + self.h >= other.y
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+    Extreme,
+    Custom(DifficultyParams),
+}
+
+// Everything that sets a difficulty apart. The built-in difficulties read
+// theirs from the DifficultyTable; Custom carries its own.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DifficultyParams {
+    pub pipe_gap: f32,
+    pub pipe_speed: f32,
+    pub spawn_interval: f32,
+    // Minimum seconds between jumps; zero keeps the classic mash-friendly feel
+    pub jump_cooldown: f32,
+    // The floor the effective gap never shrinks past, however high the score
+    pub min_gap: f32,
+}
+
+impl Default for DifficultyParams {
+    fn default() -> Self {
+        Self {
+            pipe_gap: PIPE_GAP,
+            pipe_speed: PIPE_SPEED,
+            spawn_interval: PIPE_SPAWN_INTERVAL,
+            jump_cooldown: 0.0,
+            min_gap: MIN_PIPE_GAP,
+        }
+    }
+}
+
+impl DifficultyParams {
+    fn builtin(pipe_gap: f32, pipe_speed: f32, min_gap: f32) -> Self {
+        Self {
+            pipe_gap,
+            pipe_speed,
+            min_gap,
+            ..Self::default()
+        }
+    }
+
+    // min_gap isn't on the Custom setup screen; it's only set by editing the
+    // file the params come from
+    pub const FIELDS: [&'static str; 4] =
+        ["Gap Size", "Pipe Speed", "Spawn Interval", "Jump Cooldown"];
+
+    // (min, max, step) for each adjustable field
+    pub fn field_range(field: usize) -> (f32, f32, f32) {
+        match field {
+            0 => (100.0, 260.0, 10.0),
+            1 => (1.5, 5.0, 0.1),
+            2 => (60.0, 150.0, 5.0),
+            _ => (0.0, 0.5, 0.05),
+        }
+    }
+
+    pub fn field_value(&self, field: usize) -> f32 {
+        match field {
+            0 => self.pipe_gap,
+            1 => self.pipe_speed,
+            2 => self.spawn_interval,
+            _ => self.jump_cooldown,
+        }
+    }
+
+    pub fn field_text(&self, field: usize) -> String {
+        let value = self.field_value(field);
+        match field {
+            1 => format!("{:.1}", value),
+            3 => format!("{:.2}s", value),
+            _ => format!("{:.0}", value),
+        }
+    }
+
+    pub fn adjust(&mut self, field: usize, steps: f32) {
+        let (min, max, step) = Self::field_range(field);
+        let value = (self.field_value(field) + steps * step).clamp(min, max);
+        match field {
+            0 => self.pipe_gap = value,
+            1 => self.pipe_speed = value,
+            2 => self.spawn_interval = value,
+            _ => self.jump_cooldown = value,
+        }
+    }
+
+    // Position of the field within its range, used for the setup screen bars
+    pub fn field_fraction(&self, field: usize) -> f32 {
+        let (min, max, _) = Self::field_range(field);
+        (self.field_value(field) - min) / (max - min)
+    }
+}
+
+// Parameters for the four built-in difficulties, which modders can retune
+// through difficulties.json
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DifficultyTable {
+    pub easy: DifficultyParams,
+    pub medium: DifficultyParams,
+    pub hard: DifficultyParams,
+    pub extreme: DifficultyParams,
+}
+
+impl Default for DifficultyTable {
+    fn default() -> Self {
+        Self {
+            easy: DifficultyParams::builtin(220.0, 2.0, 180.0),
+            medium: DifficultyParams::builtin(180.0, 2.5, 140.0),
+            hard: DifficultyParams::builtin(140.0, 3.0, 110.0),
+            extreme: DifficultyParams::builtin(120.0, 3.8, MIN_PIPE_GAP),
+        }
+    }
+}
+
+static DIFFICULTY_TABLE: OnceLock<DifficultyTable> = OnceLock::new();
+
+impl DifficultyTable {
+    // Reads difficulties.json, if there is one. Difficulties and fields the
+    // file leaves out keep their built-in values, so a mod can be as small as
+    // {"extreme": {"pipe_speed": 4.5}}.
+    pub fn load() -> io::Result<Self> {
+        let Some(data) = read_save_file("difficulties.json")? else {
+            return Ok(Self::default());
+        };
+        let invalid = |err| io::Error::new(io::ErrorKind::InvalidData, err);
+        let mut table = serde_json::to_value(Self::default()).map_err(invalid)?;
+        merge_json(&mut table, serde_json::from_str(&data).map_err(invalid)?);
+        serde_json::from_value(table).map_err(invalid)
+    }
+
+    // Makes this the table every Difficulty reads from. Only the first call
+    // counts, so install it at startup before any run is set up.
+    pub fn install(self) {
+        let _ = DIFFICULTY_TABLE.set(self);
+    }
+
+    // The installed table, or the built-in one if none was installed
+    pub fn current() -> &'static DifficultyTable {
+        DIFFICULTY_TABLE.get_or_init(DifficultyTable::default)
+    }
+}
+
+// Overlays one JSON value on another, object keys recursively
+fn merge_json(base: &mut serde_json::Value, overrides: serde_json::Value) {
+    match (base, overrides) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                merge_json(base.entry(key).or_insert(serde_json::Value::Null), value);
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+// The kinds of obstacle the random layout can spawn in place of a pipe
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Obstacle {
+    Plain,
+    // The gap sits against the top or bottom margin
+    OffCenter,
+    // The gap drifts up and down (see MOVING_PIPE_DRIFT)
+    Moving,
+    // A second pipe follows close behind the first
+    Double,
+    // An enemy bird bobs in the open space after the pipe
+    Enemy,
+}
+
+// How often each difficulty spawns each obstacle. Weights only count relative
+// to the others in the same set.
+pub const EASY_OBSTACLES: &[(Obstacle, f32)] = &[(Obstacle::Plain, 1.0)];
+pub const MEDIUM_OBSTACLES: &[(Obstacle, f32)] =
+    &[(Obstacle::Plain, 4.0), (Obstacle::OffCenter, 1.0)];
+pub const HARD_OBSTACLES: &[(Obstacle, f32)] = &[
+    (Obstacle::Plain, 6.0),
+    (Obstacle::OffCenter, 2.0),
+    (Obstacle::Moving, 2.0),
+];
+pub const EXTREME_OBSTACLES: &[(Obstacle, f32)] = &[
+    (Obstacle::Plain, 5.0),
+    (Obstacle::OffCenter, 2.0),
+    (Obstacle::Moving, 2.0),
+    (Obstacle::Double, 2.0),
+    (Obstacle::Enemy, 1.0),
+];
+
+// Picks from weighted choices; the first one if every weight is zero
+pub fn pick_obstacle(set: &[(Obstacle, f32)], rng: &mut impl Rng) -> Obstacle {
+    let total: f32 = set.iter().map(|(_, weight)| weight.max(0.0)).sum();
+    let mut roll = rng.gen::<f32>() * total;
+    for &(obstacle, weight) in set {
+        if roll < weight.max(0.0) {
+            return obstacle;
+        }
+        roll -= weight.max(0.0);
+    }
+    set.first()
+        .map_or(Obstacle::Plain, |(obstacle, _)| *obstacle)
+}
+
+impl Difficulty {
+    pub fn params(&self) -> DifficultyParams {
+        let table = DifficultyTable::current();
+        match self {
+            Difficulty::Easy => table.easy,
+            Difficulty::Medium => table.medium,
+            Difficulty::Hard => table.hard,
+            Difficulty::Extreme => table.extreme,
+            Difficulty::Custom(params) => *params,
+        }
+    }
+
+    // Whether difficulties.json changed this built-in difficulty's numbers
+    pub fn is_retuned(&self) -> bool {
+        let builtin = DifficultyTable::default();
+        let stock = match self {
+            Difficulty::Easy => builtin.easy,
+            Difficulty::Medium => builtin.medium,
+            Difficulty::Hard => builtin.hard,
+            Difficulty::Extreme => builtin.extreme,
+            Difficulty::Custom(_) => return false,
+        };
+        self.params() != stock
+    }
+
+    pub fn pipe_gap(&self) -> f32 {
+        self.params().pipe_gap
+    }
+
+    pub fn min_gap(&self) -> f32 {
+        self.params().min_gap
+    }
+
+    pub fn pipe_speed(&self) -> f32 {
+        self.params().pipe_speed
+    }
+
+    pub fn spawn_interval(&self) -> f32 {
+        self.params().spawn_interval
+    }
+
+    pub fn jump_cooldown(&self) -> f32 {
+        self.params().jump_cooldown
+    }
+
+    // Each difficulty adds its own obstacle to the ones before it. Custom
+    // keeps to plain pipes, like Easy.
+    pub fn obstacle_set(&self) -> &'static [(Obstacle, f32)] {
+        match self {
+            Difficulty::Easy | Difficulty::Custom(_) => EASY_OBSTACLES,
+            Difficulty::Medium => MEDIUM_OBSTACLES,
+            Difficulty::Hard => HARD_OBSTACLES,
+            Difficulty::Extreme => EXTREME_OBSTACLES,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+            Difficulty::Extreme => "Extreme",
+            Difficulty::Custom(_) => "Custom",
+        }
+    }
+}
+
+// A shareable run setup: the pipe seed plus everything that changes the layout
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Challenge {
+    pub seed: u32,
+    pub difficulty: Difficulty,
+    pub upside_down: bool,
+    pub random_start: bool,
+    pub fuel: bool,
+    // Pipes to clear to finish the challenge, one of CHALLENGE_GOALS; None
+    // for an endless run
+    pub goal: Option<u32>,
+}
+
+// Goals a challenge code can carry
+pub const CHALLENGE_GOALS: [u32; 4] = [10, 25, 50, 100];
+
+const CHALLENGE_DIFFICULTIES: [Difficulty; 4] = [
+    Difficulty::Easy,
+    Difficulty::Medium,
+    Difficulty::Hard,
+    Difficulty::Extreme,
+];
+const CHALLENGE_FLAG_UPSIDE_DOWN: u64 = 1;
+const CHALLENGE_FLAG_RANDOM_START: u64 = 2;
+const CHALLENGE_FLAG_FUEL: u64 = 4;
+
+// Packs the challenge as [goal:3][checksum:8][seed:32][flags:4][difficulty:3]
+// and writes it in base 36, still at most 10 characters. The goal is 0 for
+// endless or a 1-based index into CHALLENGE_GOALS, and sits above the checksum
+// (which still covers it) so codes from before goals existed keep working.
+// Custom difficulties can't be shared since their parameters don't fit in the
+// code.
+pub fn encode_challenge(challenge: &Challenge) -> Option<String> {
+    let difficulty = CHALLENGE_DIFFICULTIES
+        .iter()
+        .position(|d| *d == challenge.difficulty)? as u64;
+    let mut flags = 0;
+    if challenge.upside_down {
+        flags |= CHALLENGE_FLAG_UPSIDE_DOWN;
+    }
+    if challenge.random_start {
+        flags |= CHALLENGE_FLAG_RANDOM_START;
+    }
+    if challenge.fuel {
+        flags |= CHALLENGE_FLAG_FUEL;
+    }
+    let goal = match challenge.goal {
+        Some(goal) => CHALLENGE_GOALS.iter().position(|&g| g == goal)? as u64 + 1,
+        None => 0,
+    };
+    let payload = (challenge.seed as u64) << 7 | flags << 3 | difficulty;
+    let checksum = challenge_checksum(goal << 39 | payload) as u64;
+    let packed = goal << 47 | checksum << 39 | payload;
+
+    let digits = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    let mut n = packed;
+    let mut code = Vec::new();
+    // At least one digit, so a challenge that packs to 0 is "0" rather than empty
+    loop {
+        code.push(digits[(n % 36) as usize]);
+        n /= 36;
+        if n == 0 {
+            break;
+        }
+    }
+    code.reverse();
+    String::from_utf8(code).ok()
+}
+
+pub fn decode_challenge(code: &str) -> Option<Challenge> {
+    let code = code.trim();
+    if code.is_empty() || code.len() > 10 {
+        return None;
+    }
+
+    let mut packed: u64 = 0;
+    for c in code.chars() {
+        packed = packed * 36 + c.to_digit(36)? as u64;
+    }
+    if packed >> 50 != 0 {
+        return None;
+    }
+
+    let payload = packed & ((1 << 39) - 1);
+    let goal = packed >> 47;
+    if (packed >> 39) as u8 != challenge_checksum(goal << 39 | payload) {
+        return None;
+    }
+    let goal = match goal {
+        0 => None,
+        i => Some(*CHALLENGE_GOALS.get(i as usize - 1)?),
+    };
+    let flags = payload >> 3 & 0xF;
+    if flags & !(CHALLENGE_FLAG_UPSIDE_DOWN | CHALLENGE_FLAG_RANDOM_START | CHALLENGE_FLAG_FUEL)
+        != 0
+    {
+        return None;
+    }
+
+    Some(Challenge {
+        seed: (payload >> 7) as u32,
+        difficulty: *CHALLENGE_DIFFICULTIES.get((payload & 0x7) as usize)?,
+        upside_down: flags & CHALLENGE_FLAG_UPSIDE_DOWN != 0,
+        random_start: flags & CHALLENGE_FLAG_RANDOM_START != 0,
+        fuel: flags & CHALLENGE_FLAG_FUEL != 0,
+        goal,
+    })
+}
+
+fn challenge_checksum(payload: u64) -> u8 {
+    (payload.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 56) as u8
+}
+
+// Leaderboard names are three characters, padded with this one when shorter
+pub const INITIALS_LEN: usize = 3;
+pub const INITIALS_PAD: char = '_';
+// Names that get replaced on shared leaderboards; compared after sanitizing,
+// with padding stripped, so add entries in uppercase
+const BLOCKED_INITIALS: &[&str] = &[
+    "ASS", "COK", "CUM", "DIK", "FAG", "FCK", "FUC", "FUK", "KKK", "NAZ", "SEX", "SHT", "TIT",
+    "WTF",
+];
+const BLOCKED_INITIALS_REPLACEMENT: &str = "AAA";
+
+// Turns whatever was typed into exactly INITIALS_LEN characters: letters are
+// uppercased, spaces become padding and anything else is dropped. A blocked
+// name comes back as "AAA".
+pub fn sanitize_initials(raw: &str) -> String {
+    let mut initials: String = raw
+        .trim()
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_ascii_alphabetic() => Some(c.to_ascii_uppercase()),
+            ' ' | INITIALS_PAD => Some(INITIALS_PAD),
+            _ => None,
+        })
+        .take(INITIALS_LEN)
+        .collect();
+    while initials.len() < INITIALS_LEN {
+        initials.push(INITIALS_PAD);
+    }
+
+    let letters: String = initials.chars().filter(|c| *c != INITIALS_PAD).collect();
+    if BLOCKED_INITIALS.contains(&letters.as_str()) {
+        return BLOCKED_INITIALS_REPLACEMENT.to_string();
+    }
+    initials
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DeathCause {
+    Pipe,
+    Ceiling,
+    Ground,
+    Enemy,
+}
+
+impl DeathCause {
+    pub fn description(&self) -> &str {
+        match self {
+            DeathCause::Pipe => "Crashed into a pipe",
+            DeathCause::Ceiling => "Flew into the sky",
+            DeathCause::Ground => "Hit the ground",
+            DeathCause::Enemy => "Flew into another bird",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Bird {
+    pub x: f32,
+    pub y: f32,
+    pub velocity: f32,
+    pub rotation: f32,
+    // Time of the last accepted jump, for the jump cooldown
+    pub last_jump: f64,
+    // Multiplies the hitbox; 1.0 unless assist mode is on
+    pub hitbox_scale: f32,
+    // 0.0 - 1.0, how much pull fall assist takes off a fast fall
+    pub fall_assist: f32,
+    // Stronger flaps banked from pickups; the next flaps spend them one each
+    pub extra_jumps: u32,
+}
+
+impl Bird {
+    pub fn new(x: f32, y: f32) -> Self {
+        Self {
+            x,
+            y,
+            velocity: 0.0,
+            rotation: 0.0,
+            last_jump: f64::NEG_INFINITY,
+            hitbox_scale: 1.0,
+            fall_assist: 0.0,
+            extra_jumps: 0,
+        }
+    }
+
+    // gravity_sign is 1.0 normally and -1.0 when the world is upside down.
+    // When falling faster than max_fall the speed is capped there.
+    pub fn update(&mut self, gravity: f32, gravity_sign: f32, max_fall: f32) {
+        let gravity = if self.velocity * gravity_sign > FALL_ASSIST_THRESHOLD {
+            gravity * (1.0 - self.fall_assist.clamp(0.0, 1.0))
+        } else {
+            gravity
+        };
+        self.velocity += gravity * gravity_sign;
+        if self.velocity * gravity_sign > max_fall {
+            self.velocity = max_fall * gravity_sign;
+        }
+        self.y += self.velocity;
+
+        // Ease rotation towards the tilt the velocity calls for
+        let target = (self.velocity * gravity_sign * 3.0).clamp(-30.0, 90.0) * gravity_sign;
+        self.rotation += (target - self.rotation) * ROTATION_SMOOTHING;
+    }
+
+    // Returns false if the jump was ignored because it came within the cooldown
+    // window. `now` is in seconds on any monotonic clock.
+    pub fn jump(&mut self, strength: f32, gravity_sign: f32, cooldown: f32, now: f64) -> bool {
+        if now - self.last_jump < cooldown as f64 {
+            return false;
+        }
+        self.last_jump = now;
+        self.velocity = strength * gravity_sign;
+        true
+    }
+
+    // Axis-aligned hitbox, a little inside the body. The more the bird tilts,
+    // the narrower and taller it gets, so a nose-diving bird's box follows its
+    // vertical silhouette instead of staying square.
+    pub fn get_bounds(&self) -> Bounds {
+        let size = (BIRD_SIZE - 10.0) * self.hitbox_scale;
+        let tilt = self.rotation.to_radians().sin().abs() * BIRD_BOUNDS_TILT_SQUEEZE;
+        let w = size * (1.0 - tilt);
+        let h = size * (1.0 + tilt);
+        Bounds::new(self.x - w / 2.0, self.y - h / 2.0, w, h)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Pipe {
+    pub x: f32,
+    pub gap_y: f32,
+    pub gap_height: f32,
+    // Horizontal size of both halves; the caps overhang it by PIPE_CAP_OVERHANG
+    // on each side
+    pub width: f32,
+    // Points for getting past the pipe, awarded once when it becomes `scored`.
+    // A pipe is one obstacle however many gaps it has.
+    pub score_value: i32,
+    pub scored: bool,
+    pub max_offset: f32,
+    // Closest the bird's hitbox came to either side of the gap while inside
+    pub min_clearance: f32,
+    pub age: f32,
+    // World y of the ground the bottom pipe stands on
+    pub floor: f32,
+    // A ring in the middle of the gap worth bonus points, on some pipes
+    pub bonus_gate: Option<GateState>,
+    // An extra-flap pickup waiting in the middle of the gap, on some others
+    pub extra_jump: bool,
+    // Moving pipes swing their gap up to drift pixels either side of base_y;
+    // 0.0 keeps it still
+    pub base_y: f32,
+    pub drift: f32,
+    // Whether the pipe spawned with its trailing edge ahead of the bird. One
+    // that didn't (a window shrunk under it, say) is never scored, since the
+    // bird never had to get through it.
+    pub was_ahead: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GateState {
+    Pending,
+    Hit,
+    Missed,
+}
+
+impl Pipe {
+    // floor is the top of the ground; the gap always ends above it
+    pub fn new(x: f32, gap_height: f32, floor: f32, rng: &mut impl Rng) -> Self {
+        let min_y = GAP_TOP_MARGIN;
+        let max_y = floor - gap_height - GAP_BOTTOM_MARGIN;
+        let gap_y = if max_y > min_y {
+            rng.gen_range(min_y..max_y)
+        } else {
+            // No room for the usual margins (short window or tall ground), so
+            // center the gap in whatever space there is
+            ((floor - gap_height) / 2.0).max(0.0).min(min_y)
+        };
+        Self::with_gap(x, gap_y, gap_height, floor)
+    }
+
+    // Like new, but the gap covers y with a bird's size to spare on both sides.
+    // The usual margins give way when y is too close to the ceiling or ground
+    // for them.
+    pub fn covering(x: f32, gap_height: f32, floor: f32, y: f32, rng: &mut impl Rng) -> Self {
+        let range = |top: f32, bottom: f32| {
+            let low = (y + BIRD_SIZE - gap_height).max(top);
+            let high = (y - BIRD_SIZE).min(floor - gap_height - bottom);
+            (high > low).then_some(low..high)
+        };
+        match range(GAP_TOP_MARGIN, GAP_BOTTOM_MARGIN).or_else(|| range(0.0, 0.0)) {
+            Some(range) => Self::with_gap(x, rng.gen_range(range), gap_height, floor),
+            None => Self::new(x, gap_height, floor, rng),
+        }
+    }
+
+    // A pipe whose gap starts at gap_y, as laid out by a level
+    pub fn with_gap(x: f32, gap_y: f32, gap_height: f32, floor: f32) -> Self {
+        Self {
+            x,
+            gap_y,
+            gap_height,
+            width: PIPE_WIDTH,
+            score_value: 1,
+            scored: false,
+            max_offset: 0.0,
+            min_clearance: f32::INFINITY,
+            age: 0.0,
+            floor,
+            bonus_gate: None,
+            extra_jump: false,
+            base_y: gap_y,
+            drift: 0.0,
+            was_ahead: false,
+        }
+    }
+
+    // Moves the gap against the top or bottom margin, if there's room for them
+    pub fn push_to_edge(&mut self, top: bool) {
+        let max_y = self.floor - self.gap_height - GAP_BOTTOM_MARGIN;
+        if max_y > GAP_TOP_MARGIN {
+            self.gap_y = if top { GAP_TOP_MARGIN } else { max_y };
+        }
+    }
+
+    // Sets the gap drifting around where it is now, by less than
+    // MOVING_PIPE_DRIFT if that would take it into the ceiling or the ground
+    pub fn start_moving(&mut self) {
+        self.base_y = self.gap_y;
+        let room = self.gap_y.min(self.floor - self.gap_height - self.gap_y);
+        self.drift = MOVING_PIPE_DRIFT.min(room).max(0.0);
+    }
+
+    // Center of the bonus gate or extra-flap pickup (and of the gap)
+    pub fn gate_center(&self) -> (f32, f32) {
+        (
+            self.x + self.width / 2.0,
+            self.gap_y + self.gap_height / 2.0,
+        )
+    }
+
+    // Half-height of the band the bird has to be in to hit the gate
+    pub fn gate_radius(&self) -> f32 {
+        self.gap_height * BONUS_GATE_BAND
+    }
+
+    pub fn update(&mut self, speed: f32, time_scale: f32) {
+        self.x -= speed;
+        self.age += time_scale;
+        if self.drift > 0.0 {
+            self.gap_y = self.base_y + self.drift * (self.age * MOVING_PIPE_RATE).sin();
+        }
+    }
+
+    // 0..1 progress of the entrance animation, eased out
+    pub fn entrance_progress(&self) -> f32 {
+        let t = (self.age / PIPE_ENTRANCE_FRAMES).min(1.0);
+        1.0 - (1.0 - t).powi(3)
+    }
+
+    // Pipes only become solid once the entrance animation has finished
+    pub fn is_materialized(&self) -> bool {
+        self.age >= PIPE_ENTRANCE_FRAMES
+    }
+
+    // 1 while the pipe is fully on screen, fading to 0 as it slides off the left
+    // edge so nothing pops when it's removed
+    pub fn exit_fade(&self) -> f32 {
+        let visible_width = self.width + PIPE_CAP_OVERHANG;
+        ((self.x + visible_width) / visible_width).clamp(0.0, 1.0)
+    }
+
+    // Bodies of the top and bottom pipes, then their caps
+    pub fn hitboxes(&self) -> [Bounds; 4] {
+        let bottom_y = self.gap_y + self.gap_height;
+        let cap_x = self.x - PIPE_CAP_OVERHANG;
+        let cap_width = self.width + PIPE_CAP_OVERHANG * 2.0;
+        // Caps never reach past the ceiling or the ground, however small the pipe
+        let top_cap = PIPE_CAP_HEIGHT.min(self.gap_y);
+        let bottom_cap = PIPE_CAP_HEIGHT.min(self.floor - bottom_y);
+        [
+            Bounds::new(self.x, 0.0, self.width, self.gap_y),
+            Bounds::new(self.x, bottom_y, self.width, self.floor - bottom_y),
+            Bounds::new(cap_x, self.gap_y - top_cap, cap_width, top_cap),
+            Bounds::new(cap_x, bottom_y, cap_width, bottom_cap),
+        ]
+    }
+
+    pub fn collides_with(&self, bird: &Bird) -> bool {
+        let bird_bounds = bird.get_bounds();
+        self.hitboxes()
+            .iter()
+            .any(|hitbox| bird_bounds.overlaps(hitbox))
+    }
+
+    // The pipe's trailing edge is behind the bird at bird_x but it hasn't been
+    // scored yet, and it started out ahead; the next step awards it
+    pub fn is_passed(&self, bird_x: f32) -> bool {
+        self.was_ahead && !self.scored && self.x + self.width < bird_x
+    }
+
+    // Includes the cap overhang. By now the pipe is long past the bird, so it
+    // has already been scored and `scored` keeps it from counting twice.
+    pub fn is_offscreen(&self) -> bool {
+        self.x + self.width + PIPE_CAP_OVERHANG < 0.0
+    }
+}
+
+// Another bird hovering in the open between two pipes. It scrolls with them
+// and bobs ENEMY_BOB pixels either side of base_y; touching it is a crash.
+#[derive(Clone, Debug)]
+pub struct Enemy {
+    pub x: f32,
+    pub y: f32,
+    pub base_y: f32,
+    pub age: f32,
+}
+
+impl Enemy {
+    pub fn new(x: f32, y: f32) -> Self {
+        Self {
+            x,
+            y,
+            base_y: y,
+            age: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, speed: f32, time_scale: f32) {
+        self.x -= speed;
+        self.age += time_scale;
+        self.y = self.base_y + ENEMY_BOB * (self.age * ENEMY_BOB_RATE).sin();
+    }
+
+    pub fn hitbox(&self) -> Bounds {
+        let half = ENEMY_SIZE / 2.0;
+        Bounds::new(self.x - half, self.y - half, ENEMY_SIZE, ENEMY_SIZE)
+    }
+
+    pub fn collides_with(&self, bird: &Bird) -> bool {
+        bird.get_bounds().overlaps(&self.hitbox())
+    }
+
+    pub fn is_offscreen(&self) -> bool {
+        self.x + ENEMY_SIZE / 2.0 < 0.0
+    }
+}
+
+// One pipe of a hand-made level. gap_y is the top of the gap in world pixels.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LevelPipe {
+    pub gap_y: f32,
+    pub gap_height: f32,
+    #[serde(default)]
+    pub bonus_gate: bool,
+}
+
+// A designed pipe sequence read from level.json, spawned in order instead of
+// the random layout. Pipes still arrive at the difficulty's speed and spacing.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Level {
+    #[serde(default)]
+    pub name: String,
+    pub pipes: Vec<LevelPipe>,
+    // Start over from the first pipe after the last one instead of ending the level
+    #[serde(default)]
+    pub looped: bool,
+}
+
+impl Level {
+    // Reads level.json, if there is one
+    pub fn load() -> io::Result<Option<Self>> {
+        let Some(data) = read_save_file("level.json")? else {
+            return Ok(None);
+        };
+        serde_json::from_str(&data)
+            .map(Some)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    // Checks every gap fits between the ceiling and the ground (at floor) and
+    // is wide enough for the bird. The error names the first bad pipe, 1-based.
+    pub fn validate(&self, floor: f32) -> Result<(), String> {
+        if self.pipes.is_empty() {
+            return Err("the level has no pipes".to_string());
+        }
+        for (i, pipe) in self.pipes.iter().enumerate() {
+            if pipe.gap_height <= BIRD_SIZE {
+                return Err(format!("pipe {}'s gap is too small for the bird", i + 1));
+            }
+            if pipe.gap_y < 0.0 || pipe.gap_y + pipe.gap_height > floor {
+                return Err(format!("pipe {}'s gap doesn't fit above the ground", i + 1));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Everything that shapes a run, fixed when it starts
+#[derive(Clone, Copy, Debug)]
+pub struct SimConfig {
+    pub difficulty: Difficulty,
+    pub seed: u32,
+    pub gravity: f32,
+    pub jump_strength: f32,
+    // 1.0 normally and -1.0 when the world is upside down
+    pub gravity_sign: f32,
+    // Extra tuning layered over the difficulty, e.g. by adaptive mode
+    pub gap_offset: f32,
+    pub speed_offset: f32,
+    // Multiplies how often pipes spawn
+    pub pipe_density: f32,
+    pub bird_x: f32,
+    pub world_width: f32,
+    pub world_height: f32,
+    // Thickness of the ground strip at the bottom of the world
+    pub ground_height: f32,
+    // Start the bird at a seeded height instead of the middle of the world
+    pub random_start: bool,
+    // Flaps burn fuel that refills over time; no flapping on an empty tank
+    pub fuel: bool,
+    // Width of every pipe spawned this run
+    pub pipe_width: f32,
+    // Nothing is deadly: the bird flies through pipes and rests against the
+    // ceiling and ground instead of crashing
+    pub zen: bool,
+    // Shrinks the bird's hitbox by ASSIST_HITBOX_SCALE
+    pub assist: bool,
+    // Gravity eases in as the bird falls (see curved_gravity) instead of
+    // pulling the same every frame
+    pub gravity_curve: bool,
+    // Wider gaps on the first few pipes (see warm_up_bonus)
+    pub warm_up: bool,
+    // Takes this fraction of gravity off once the bird falls faster than
+    // FALL_ASSIST_THRESHOLD; 0.0 is off
+    pub fall_assist: f32,
+    // Well-timed consecutive flaps build a boost (see FLAP_BOOST_STEP)
+    pub flap_boost: bool,
+}
+
+impl SimConfig {
+    // Default physics and tuning for a world of the given size
+    pub fn new(difficulty: Difficulty, seed: u32, world_width: f32, world_height: f32) -> Self {
+        Self {
+            difficulty,
+            seed,
+            gravity: GRAVITY,
+            jump_strength: JUMP_STRENGTH,
+            gravity_sign: 1.0,
+            gap_offset: 0.0,
+            speed_offset: 0.0,
+            pipe_density: 1.0,
+            bird_x: BIRD_START_X,
+            world_width,
+            world_height,
+            ground_height: GROUND_HEIGHT,
+            random_start: false,
+            fuel: false,
+            pipe_width: PIPE_WIDTH,
+            zen: false,
+            assist: false,
+            gravity_curve: false,
+            warm_up: false,
+            fall_assist: 0.0,
+            flap_boost: false,
+        }
+    }
+}
+
+// What happened during a step, for the front end to react to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SimEvent {
+    // The bird cleared a pipe whose center is at x, possibly by a hair
+    Scored {
+        x: f32,
+        clean: bool,
+        near_miss: bool,
+    },
+    // The bird flew through the bonus gate centered at (x, y)
+    BonusGate {
+        x: f32,
+        y: f32,
+    },
+    // The bird collected the extra-flap pickup at (x, y)
+    ExtraJump {
+        x: f32,
+        y: f32,
+    },
+    Died(DeathCause),
+    // The bird got past the last pipe of a level that doesn't loop
+    LevelComplete,
+}
+
+// One run of the game, advanced a frame at a time by `step`
+pub struct Simulation {
+    pub config: SimConfig,
+    pub bird: Bird,
+    pub pipes: Vec<Pipe>,
+    pub enemies: Vec<Enemy>,
+    pub score: i32,
+    pub clean_passes: u32,
+    pub total_passes: u32,
+    // Consecutive clean passes, and the longest streak this run
+    pub combo: u32,
+    pub max_combo: u32,
+    // Pipes, ceiling and ground are harmless while either is set: the debug
+    // cheat toggle, or a timed power-up the game switches off again
+    pub cheat_invincible: bool,
+    pub powerup_invincible: bool,
+    // Set once the bird dies or finishes the level; later steps do nothing
+    pub death: Option<DeathCause>,
+    pub completed: bool,
+    // Pipes come from this sequence instead of the random generator when set
+    pub level: Option<Level>,
+    pub bonus_gates: u32,
+    // 0.0 - 1.0, only drained in fuel mode
+    pub fuel: f32,
+    // Simulated frames so far (slow motion counts as a fraction of a frame), and
+    // the frame each point was scored on. Whole and half frames add up exactly
+    // in an f32 for 2^24 frames, over three days of play.
+    pub elapsed: f32,
+    pub score_frames: Vec<f32>,
+    // Pipes spawned so far this run
+    pub pipes_spawned: u32,
+    // Where the bird started, which the first pipe's gap is placed around
+    pub start_y: f32,
+    // 0.0 - 1.0, only built up with flap boost on; last_flap is the elapsed
+    // frame of the most recent flap
+    pub boost: f32,
+    last_flap: Option<f32>,
+    pipe_spawn_timer: f32,
+    rng: StdRng,
+    // Bonus gates, pickups and obstacle kinds roll on their own generators so
+    // seeds keep their pipe layouts
+    gate_rng: StdRng,
+    pickup_rng: StdRng,
+    obstacle_rng: StdRng,
+}
+
+impl Simulation {
+    pub fn new(config: SimConfig) -> Self {
+        let mut rng = StdRng::seed_from_u64(config.seed as u64);
+        let ground_y = config.world_height - config.ground_height;
+        let start_y = if config.random_start {
+            // Somewhere in the middle half of the sky, and always a couple of
+            // bird heights clear of the ceiling and ground even on tiny windows
+            let low = (ground_y * 0.25).max(BIRD_SIZE * 2.0);
+            let high = (ground_y * 0.75).min(ground_y - BIRD_SIZE * 2.0);
+            if high > low {
+                rng.gen_range(low..high)
+            } else {
+                ground_y / 2.0
+            }
+        } else {
+            config.world_height / 2.0
+        };
+
+        let mut bird = Bird::new(config.bird_x, start_y);
+        if config.assist {
+            bird.hitbox_scale = ASSIST_HITBOX_SCALE;
+        }
+        bird.fall_assist = config.fall_assist;
+
+        Self {
+            bird,
+            pipes: Vec::new(),
+            enemies: Vec::new(),
+            score: 0,
+            clean_passes: 0,
+            total_passes: 0,
+            combo: 0,
+            max_combo: 0,
+            cheat_invincible: false,
+            powerup_invincible: false,
+            death: None,
+            completed: false,
+            level: None,
+            bonus_gates: 0,
+            fuel: 1.0,
+            elapsed: 0.0,
+            score_frames: Vec::new(),
+            pipes_spawned: 0,
+            start_y,
+            boost: 0.0,
+            last_flap: None,
+            pipe_spawn_timer: 0.0,
+            rng,
+            gate_rng: StdRng::seed_from_u64(config.seed as u64 ^ 0x6A7E_0B0E),
+            pickup_rng: StdRng::seed_from_u64(config.seed as u64 ^ 0x0E87_A1F5),
+            obstacle_rng: StdRng::seed_from_u64(config.seed as u64 ^ 0x0B57_AC1E),
+            config,
+        }
+    }
+
+    // Either source of invincibility; only the game decides which ones count
+    // against the run's ranking
+    pub fn invincible(&self) -> bool {
+        self.cheat_invincible || self.powerup_invincible
+    }
+
+    pub fn ground_y(&self) -> f32 {
+        self.config.world_height - self.config.ground_height
+    }
+
+    // The gap tightens as the score climbs, down to the difficulty's floor
+    pub fn effective_gap(&self) -> f32 {
+        let difficulty = self.config.difficulty;
+        let shrink = (self.score as f32 * GAP_SHRINK_PER_POINT).min(GAP_SHRINK_MAX);
+        (difficulty.pipe_gap() - shrink - self.config.gap_offset).max(difficulty.min_gap())
+    }
+
+    pub fn effective_speed(&self) -> f32 {
+        self.config.difficulty.pipe_speed() + self.config.speed_offset
+    }
+
+    // Pipe density packs pipes tighter or looser than the difficulty's default,
+    // but never closer than MIN_PIPE_SPACING (or the default, if that's closer)
+    pub fn effective_spawn_interval(&self) -> f32 {
+        let base = self.config.difficulty.spawn_interval();
+        let floor = base.min(MIN_PIPE_SPACING / self.effective_speed());
+        (base / self.config.pipe_density).max(floor)
+    }
+
+    // Returns false if the jump was ignored (cooldown, empty tank, or the run is over)
+    // scale multiplies the configured jump strength for this one flap, and a
+    // banked extra flap multiplies it again by EXTRA_JUMP_SCALE. With flap
+    // boost on, the boost this flap builds adds up to FLAP_BOOST_MAX_BONUS.
+    pub fn jump(&mut self, now: f64, scale: f32) -> bool {
+        if self.death.is_some() || self.out_of_fuel() {
+            return false;
+        }
+        let extra = self.bird.extra_jumps > 0;
+        let scale = if extra {
+            scale * EXTRA_JUMP_SCALE
+        } else {
+            scale
+        };
+        let boost = self.next_boost();
+        let scale = scale * (1.0 + FLAP_BOOST_MAX_BONUS * boost);
+        let cooldown = self.config.difficulty.jump_cooldown();
+        let strength = self.config.jump_strength * scale;
+        let jumped = self
+            .bird
+            .jump(strength, self.config.gravity_sign, cooldown, now);
+        if jumped && self.config.flap_boost {
+            self.boost = boost;
+            self.last_flap = Some(self.elapsed);
+        }
+        if jumped && extra {
+            self.bird.extra_jumps -= 1;
+        }
+        if jumped && self.config.fuel {
+            self.fuel -= FUEL_PER_FLAP;
+        }
+        jumped
+    }
+
+    // What the boost would be after a flap right now
+    fn next_boost(&self) -> f32 {
+        if !self.config.flap_boost {
+            return 0.0;
+        }
+        let Some(last_flap) = self.last_flap else {
+            return self.boost;
+        };
+        let since = self.elapsed - last_flap;
+        if since < FLAP_BOOST_MIN_FRAMES {
+            0.0
+        } else if since <= FLAP_BOOST_MAX_FRAMES {
+            (self.boost + FLAP_BOOST_STEP).min(1.0)
+        } else {
+            self.boost
+        }
+    }
+
+    // Not enough left in the tank for another flap
+    pub fn out_of_fuel(&self) -> bool {
+        self.config.fuel && self.fuel < FUEL_PER_FLAP
+    }
+
+    // Advances one frame; time_scale below 1.0 is slow motion
+    pub fn step(&mut self, time_scale: f32) -> Vec<SimEvent> {
+        let mut events = Vec::new();
+        if self.death.is_some() || self.completed {
+            return events;
+        }
+
+        self.elapsed += time_scale;
+        fall(&self.config, &mut self.bird);
+        if self.config.fuel {
+            self.fuel = (self.fuel + FUEL_REFILL_PER_FRAME * time_scale).min(1.0);
+        }
+        self.boost = (self.boost - FLAP_BOOST_DECAY * time_scale).max(0.0);
+        if self.config.zen {
+            let top = BIRD_SIZE / 2.0;
+            let bottom = self.ground_y() - BIRD_SIZE / 2.0;
+            if self.bird.y <= top || self.bird.y >= bottom {
+                self.bird.y = self.bird.y.clamp(top, bottom.max(top));
+                self.bird.velocity = 0.0;
+            }
+        }
+
+        // Spawn pipes. The timer restarts first, since a double pipe pushes
+        // the next spawn back.
+        self.pipe_spawn_timer += time_scale;
+        if self.pipe_spawn_timer > self.effective_spawn_interval() {
+            self.pipe_spawn_timer = 0.0;
+            self.spawn_pipe();
+        }
+
+        // Update pipes
+        let speed = self.effective_speed() * time_scale;
+        let mut points = 0;
+        for pipe in &mut self.pipes {
+            let gate_x_before = pipe.gate_center().0;
+            pipe.update(speed, time_scale);
+
+            // The gate counts when its center sweeps past the bird while the
+            // bird is inside its band
+            let (gate_x, gate_y) = pipe.gate_center();
+            if pipe.bonus_gate == Some(GateState::Pending)
+                && gate_x_before > self.bird.x
+                && gate_x <= self.bird.x
+            {
+                if (self.bird.y - gate_y).abs() <= pipe.gate_radius() {
+                    pipe.bonus_gate = Some(GateState::Hit);
+                    self.bonus_gates += 1;
+                    points += BONUS_GATE_POINTS;
+                    events.push(SimEvent::BonusGate {
+                        x: gate_x,
+                        y: gate_y,
+                    });
+                } else {
+                    pipe.bonus_gate = Some(GateState::Missed);
+                }
+            }
+
+            // The pickup is collected by touching it, from any direction
+            if pipe.extra_jump {
+                let (x, y) = pipe.gate_center();
+                let reach = EXTRA_JUMP_PICKUP_RADIUS + BIRD_SIZE / 2.0;
+                if (self.bird.x - x).hypot(self.bird.y - y) <= reach {
+                    pipe.extra_jump = false;
+                    self.bird.extra_jumps =
+                        (self.bird.extra_jumps + EXTRA_JUMPS_PER_PICKUP).min(EXTRA_JUMP_MAX);
+                    events.push(SimEvent::ExtraJump { x, y });
+                }
+            }
+
+            // Track how far from the gap center the bird strays while inside the pipe
+            if self.bird.x >= pipe.x && self.bird.x <= pipe.x + pipe.width {
+                let offset = (self.bird.y - (pipe.gap_y + pipe.gap_height / 2.0)).abs();
+                pipe.max_offset = pipe.max_offset.max(offset);
+                let bounds = self.bird.get_bounds();
+                let clearance = (bounds.y - pipe.gap_y)
+                    .min(pipe.gap_y + pipe.gap_height - (bounds.y + bounds.h));
+                pipe.min_clearance = pipe.min_clearance.min(clearance);
+            }
+
+            // Check if bird passed pipe
+            if pipe.is_passed(self.bird.x) {
+                pipe.scored = true;
+                points += pipe.score_value;
+                self.total_passes += 1;
+                let clean = pipe.max_offset <= pipe.gap_height * CLEAN_PASS_TOLERANCE;
+                if clean {
+                    self.clean_passes += 1;
+                    self.combo = self.combo.saturating_add(1);
+                    self.max_combo = self.max_combo.max(self.combo);
+                } else {
+                    self.combo = 0;
+                }
+                let near_miss = (0.0..NEAR_MISS_MARGIN).contains(&pipe.min_clearance);
+                events.push(SimEvent::Scored {
+                    x: pipe.x + pipe.width / 2.0,
+                    clean,
+                    near_miss,
+                });
+            }
+        }
+
+        self.award(points);
+
+        for enemy in &mut self.enemies {
+            enemy.update(speed, time_scale);
+        }
+
+        // Remove offscreen pipes and enemies
+        self.pipes.retain(|pipe| !pipe.is_offscreen());
+        self.enemies.retain(|enemy| !enemy.is_offscreen());
+
+        if let Some(cause) = self.check_collision() {
+            self.death = Some(cause);
+            events.push(SimEvent::Died(cause));
+        } else if self.level_finished() {
+            self.completed = true;
+            events.push(SimEvent::LevelComplete);
+        }
+
+        events
+    }
+
+    // The only place the score goes up. Every point earned this frame (pipes
+    // turning `scored`, bonus gates) is summed first, so a multiplier added
+    // here applies to all of them alike. Each point is stamped with the
+    // current frame for replays and ghosts.
+    fn award(&mut self, points: i32) {
+        for _ in 0..points.max(0) {
+            self.score = self.score.saturating_add(1);
+            self.score_frames.push(self.elapsed);
+        }
+    }
+
+    // What would kill the bird where it is right now: a solid pipe first, then
+    // an enemy, then the ceiling or ground. Always None while invincible or in zen mode.
+    pub fn check_collision(&self) -> Option<DeathCause> {
+        if self.invincible() || self.config.zen {
+            None
+        } else if self
+            .pipes
+            .iter()
+            .any(|pipe| pipe.is_materialized() && pipe.collides_with(&self.bird))
+        {
+            Some(DeathCause::Pipe)
+        } else if self
+            .enemies
+            .iter()
+            .any(|enemy| enemy.collides_with(&self.bird))
+        {
+            Some(DeathCause::Enemy)
+        } else if self.bird.y - BIRD_SIZE / 2.0 <= 0.0 {
+            Some(DeathCause::Ceiling)
+        } else if self.bird.y + BIRD_SIZE / 2.0 >= self.ground_y() {
+            Some(DeathCause::Ground)
+        } else {
+            None
+        }
+    }
+
+    // Frames until the nearest pipe still ahead reaches the front of the bird's
+    // hitbox at the current speed; 0.0 while the bird is already between its
+    // halves, None with no pipe coming
+    pub fn frames_to_next_pipe(&self) -> Option<f32> {
+        let front = self.bird.x + BIRD_SIZE / 2.0;
+        let speed = self.effective_speed();
+        let pipe = self
+            .pipes
+            .iter()
+            .filter(|pipe| !pipe.scored && pipe.x + pipe.width > self.bird.x)
+            .min_by(|a, b| a.x.total_cmp(&b.x))?;
+        if speed <= 0.0 {
+            return None;
+        }
+        Some(((pipe.x - front) / speed).max(0.0))
+    }
+
+    // Where the bird would be over the next `frames` frames without flapping,
+    // moved exactly as `step` moves it. x runs ahead at the pipe speed, so the
+    // path crosses each pipe's current x at the height the bird would meet it.
+    pub fn predicted_path(&self, frames: usize) -> Vec<(f32, f32)> {
+        let mut bird = self.bird.clone();
+        let speed = self.effective_speed();
+        (1..=frames)
+            .map(|frame| {
+                fall(&self.config, &mut bird);
+                (bird.x + speed * frame as f32, bird.y)
+            })
+            .collect()
+    }
+
+    // Pipes the bird is past that haven't been scored yet
+    pub fn pipes_to_score(&self) -> usize {
+        self.pipes
+            .iter()
+            .filter(|pipe| pipe.is_passed(self.bird.x))
+            .count()
+    }
+
+    // Every pipe of a level that doesn't loop has spawned and been scored
+    pub fn level_finished(&self) -> bool {
+        match &self.level {
+            Some(level) if !level.looped => {
+                self.pipes_spawned as usize >= level.pipes.len()
+                    && self.pipes.iter().all(|pipe| pipe.scored || !pipe.was_ahead)
+            }
+            _ => false,
+        }
+    }
+
+    fn spawn_pipe(&mut self) {
+        let x = self.config.world_width + 50.0;
+        if let Some(level) = &self.level {
+            let index = self.pipes_spawned as usize;
+            if level.pipes.is_empty() || (index >= level.pipes.len() && !level.looped) {
+                return;
+            }
+            let entry = &level.pipes[index % level.pipes.len()];
+            let mut pipe = Pipe::with_gap(x, entry.gap_y, entry.gap_height, self.ground_y());
+            pipe.width = self.config.pipe_width.max(1.0);
+            if entry.bonus_gate {
+                pipe.bonus_gate = Some(GateState::Pending);
+            }
+            self.pipes_spawned += 1;
+            self.push_pipe(pipe);
+            return;
+        }
+        let warm_up = self.config.warm_up && !matches!(self.config.difficulty, Difficulty::Extreme);
+        let bonus = if warm_up {
+            warm_up_bonus(self.pipes_spawned)
+        } else {
+            0.0
+        };
+        // The opening pipe and warm-up pipes are always plain
+        let set = self.config.difficulty.obstacle_set();
+        let obstacle = match pick_obstacle(set, &mut self.obstacle_rng) {
+            _ if self.pipes_spawned == 0 || bonus > 0.0 => Obstacle::Plain,
+            obstacle => obstacle,
+        };
+        let gap = self.effective_gap() + bonus;
+        self.pipes_spawned += 1;
+        let floor = self.ground_y();
+        // The opening pipe lines up with where the bird started rather than
+        // wherever it is now, so the layout still depends on nothing but the seed
+        let mut pipe = if self.pipes_spawned == 1 {
+            Pipe::covering(x, gap, floor, self.start_y, &mut self.rng)
+        } else {
+            Pipe::new(x, gap, floor, &mut self.rng)
+        };
+        pipe.width = self.config.pipe_width.max(1.0);
+        if obstacle == Obstacle::OffCenter {
+            pipe.push_to_edge(self.obstacle_rng.gen());
+        }
+        if let Some(previous) = self.pipes.last() {
+            self.keep_reachable(&mut pipe, previous);
+        }
+        if obstacle == Obstacle::Moving {
+            pipe.start_moving();
+        }
+        if self.gate_rng.gen::<f32>() < BONUS_GATE_CHANCE {
+            pipe.bonus_gate = Some(GateState::Pending);
+        }
+        let pickup_roll = self.pickup_rng.gen::<f32>();
+        if pipe.bonus_gate.is_none() && pickup_roll < EXTRA_JUMP_CHANCE {
+            pipe.extra_jump = true;
+        }
+        if obstacle == Obstacle::Enemy {
+            let spacing = self.effective_spawn_interval() * self.effective_speed();
+            let x = pipe.x + pipe.width + (spacing - pipe.width) / 2.0;
+            let (_, y) = pipe.gate_center();
+            self.enemies.push(Enemy::new(x, y));
+        }
+        // The front pipe goes in first, keeping `pipes` in order of x
+        let front = pipe.clone();
+        self.push_pipe(pipe);
+        if obstacle == Obstacle::Double {
+            self.spawn_second_pipe(&front);
+        }
+    }
+
+    // Every spawned pipe goes through here to have was_ahead set. Pipes are
+    // pushed in order of x, front to back, and the rest of the simulation
+    // relies on it.
+    fn push_pipe(&mut self, mut pipe: Pipe) {
+        pipe.was_ahead = pipe.x + pipe.width >= self.bird.x;
+        self.pipes.push(pipe);
+    }
+
+    // The back half of a double pipe, DOUBLE_PIPE_SPACING behind the front
+    // one. The next spawn waits as long again, so the pipe after the pair is
+    // as far from it as usual.
+    fn spawn_second_pipe(&mut self, front: &Pipe) {
+        let offset = front.width + DOUBLE_PIPE_SPACING;
+        let shift = self
+            .obstacle_rng
+            .gen_range(-DOUBLE_PIPE_SHIFT..=DOUBLE_PIPE_SHIFT);
+        let lowest = (front.floor - front.gap_height).max(0.0);
+        let gap_y = (front.gap_y + shift).clamp(0.0, lowest);
+        let mut back = Pipe::with_gap(front.x + offset, gap_y, front.gap_height, front.floor);
+        back.width = front.width;
+        self.pipes_spawned += 1;
+        self.push_pipe(back);
+        self.pipe_spawn_timer = -offset / self.effective_speed();
+    }
+
+    // Pulls a new pipe's gap towards the previous one until the bird can get
+    // from anywhere in that gap to somewhere in this one in the time between
+    // them (see max_reachable_delta), wherever a moving previous gap happens to be
+    fn keep_reachable(&self, pipe: &mut Pipe, previous: &Pipe) {
+        let previous_y = if previous.drift > 0.0 {
+            previous.base_y
+        } else {
+            previous.gap_y
+        };
+        let previous_height = previous.gap_height;
+        let speed = self.effective_speed();
+        let spacing = self.effective_spawn_interval() * speed;
+        let horizontal_gap = spacing - pipe.width - BIRD_SIZE;
+        // The bird can use the whole of both gaps, less its own size
+        let slack = (previous_height - BIRD_SIZE).max(0.0) / 2.0
+            + (pipe.gap_height - BIRD_SIZE).max(0.0) / 2.0;
+        let reach = max_reachable_delta(horizontal_gap, speed) + slack - previous.drift;
+        let reach = reach.max(0.0);
+        let previous_center = previous_y + previous_height / 2.0;
+        let low = previous_center - reach - pipe.gap_height / 2.0;
+        let high = previous_center + reach - pipe.gap_height / 2.0;
+        pipe.gap_y = pipe.gap_y.clamp(low, high.max(low));
+    }
+
+    // Fast-forwards the pipe layout as if the bird had flown through every
+    // pipe before the given one (1-based), then parks the bird level in the
+    // next gap. Pipes spawn, move and shrink exactly as they would in a real
+    // run on the same seed, so the stretch being practiced looks the same.
+    // Bonus gates on skipped pipes count as missed and their pickups vanish.
+    pub fn advance_to_pipe(&mut self, pipe: u32) {
+        let passed = pipe.saturating_sub(1) as i32;
+        let speed = self.effective_speed();
+        let bird_x = self.bird.x;
+        // Keep going until the last skipped pipe is fully clear of the bird
+        let in_the_way = |pipe: &Pipe| {
+            pipe.scored && pipe.x + pipe.width + PIPE_CAP_OVERHANG >= bird_x - BIRD_SIZE
+        };
+        while self.score < passed || self.pipes.iter().any(in_the_way) {
+            self.elapsed += 1.0;
+            self.pipe_spawn_timer += 1.0;
+            if self.pipe_spawn_timer > self.effective_spawn_interval() {
+                self.pipe_spawn_timer = 0.0;
+                self.spawn_pipe();
+            }
+            let mut points = 0;
+            for pipe in &mut self.pipes {
+                pipe.update(speed, 1.0);
+                if pipe.is_passed(bird_x) {
+                    pipe.scored = true;
+                    if pipe.bonus_gate == Some(GateState::Pending) {
+                        pipe.bonus_gate = Some(GateState::Missed);
+                    }
+                    pipe.extra_jump = false;
+                    points += pipe.score_value;
+                }
+            }
+            self.award(points);
+            for enemy in &mut self.enemies {
+                enemy.update(speed, 1.0);
+            }
+            self.pipes.retain(|pipe| !pipe.is_offscreen());
+            self.enemies.retain(|enemy| !enemy.is_offscreen());
+        }
+
+        if let Some(next) = self
+            .pipes
+            .iter()
+            .find(|pipe| !pipe.scored && pipe.was_ahead)
+        {
+            self.bird.y = next.gap_y + next.gap_height / 2.0;
+        }
+        self.bird.velocity = 0.0;
+    }
+
+    // 0-3 stars based on the share of passes made close to the gap center
+    pub fn star_rating(&self) -> u32 {
+        if self.total_passes == 0 {
+            return 0;
+        }
+        let ratio = self.clean_passes as f32 / self.total_passes as f32;
+        if ratio >= 0.9 {
+            3
+        } else if ratio >= 0.6 {
+            2
+        } else if ratio >= 0.3 {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+// Extra gap for the run's pipe_index-th pipe (0-based) during the warm-up
+pub fn warm_up_bonus(pipe_index: u32) -> f32 {
+    let left = WARM_UP_PIPES.saturating_sub(pipe_index);
+    WARM_UP_GAP_BONUS * left as f32 / WARM_UP_PIPES as f32
+}
+
+// How far the bird can surely move up or down while flying horizontal_gap
+// pixels at speed. Climbing is one flap's JUMP_STRENGTH² / (2 × GRAVITY) per
+// flap, an average of half the jump speed, and falling starts from rest; the
+// smaller of the two holds either way. The default physics are used rather
+// than the run's, so a seed's layout doesn't depend on anyone's settings.
+pub fn max_reachable_delta(horizontal_gap: f32, speed: f32) -> f32 {
+    if speed <= 0.0 {
+        return f32::INFINITY;
+    }
+    let frames = horizontal_gap.max(0.0) / speed;
+    let climb = JUMP_STRENGTH.abs() / 2.0 * frames;
+    let fall = 0.5 * GRAVITY * frames * frames;
+    climb.min(fall)
+}
+
+// One frame of gravity on the bird under the run's physics
+fn fall(config: &SimConfig, bird: &mut Bird) {
+    let sign = config.gravity_sign;
+    if config.gravity_curve {
+        let gravity = curved_gravity(config.gravity, bird.velocity * sign);
+        bird.update(gravity, sign, TERMINAL_VELOCITY);
+    } else {
+        bird.update(config.gravity, sign, f32::INFINITY);
+    }
+}
+
+// Pull this frame for a bird falling at fall_speed (negative while rising)
+pub fn curved_gravity(gravity: f32, fall_speed: f32) -> f32 {
+    if fall_speed < 0.0 {
+        return gravity;
+    }
+    let t = (fall_speed / TERMINAL_VELOCITY).min(1.0);
+    gravity * (GRAVITY_CURVE_START + (GRAVITY_CURVE_END - GRAVITY_CURVE_START) * t)
+}
+
+// Score a run had reached by the given frame, from its score_frames
+pub fn score_at(score_frames: &[f32], frame: f32) -> i32 {
+    score_frames.partition_point(|&scored| scored <= frame) as i32
+}
+
+// Save files live next to the executable so they don't depend on the working
+// directory; falls back to the relative path if that location can't be resolved
+pub fn save_path(file_name: &str) -> PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.to_path_buf()))
+        .filter(|dir| fs::create_dir_all(dir).is_ok())
+        .map(|dir| dir.join(file_name))
+        .unwrap_or_else(|| PathBuf::from(file_name))
+}
+
+// Reads a save file, treating a missing one as empty rather than an error
+pub fn read_save_file(file_name: &str) -> io::Result<Option<String>> {
+    let path = save_path(file_name);
+    if path.is_dir() {
+        return Err(io::Error::other(format!("{} is a directory", file_name)));
+    }
+    match fs::read_to_string(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+pub fn write_save_file(file_name: &str, data: &str) -> io::Result<()> {
+    let path = save_path(file_name);
+    if path.is_dir() {
+        return Err(io::Error::other(format!("{} is a directory", file_name)));
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, data)
+}
+
+// Backups of one save file kept before back_up_save_file gives up
+const MAX_BACKUPS: u32 = 100;
+
+// Moves an unreadable save file aside to <name>.bak, so saving the defaults
+// the game falls back to can't overwrite it. An older backup is never replaced:
+// later ones go to <name>.1.bak, <name>.2.bak and so on. A missing file needs
+// nothing, and anything else in its place (a directory, say) is an error.
+pub fn back_up_save_file(file_name: &str) -> io::Result<()> {
+    let path = save_path(file_name);
+    match fs::metadata(&path) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+        Ok(metadata) if !metadata.is_file() => {
+            return Err(io::Error::other(format!("{} isn't a file", file_name)));
+        }
+        Ok(_) => {}
+    }
+    let backup = (0..MAX_BACKUPS)
+        .map(|n| match n {
+            0 => save_path(&format!("{}.bak", file_name)),
+            n => save_path(&format!("{}.{}.bak", file_name, n)),
+        })
+        .find(|backup| !backup.exists())
+        .ok_or_else(|| io::Error::other(format!("{} has too many backups", file_name)))?;
+    fs::rename(path, backup)
+}
+
+// Each player profile keeps its own high scores in profiles/<name>.json
+pub const DEFAULT_PROFILE: &str = "Player 1";
+pub const PROFILE_NAME_MAX: usize = 12;
+const PROFILES_DIR: &str = "profiles";
+
+fn profile_file(name: &str) -> String {
+    format!("{}/{}.json", PROFILES_DIR, name)
+}
+
+// Trims a typed profile name and checks it's usable as a file name: 1 to
+// PROFILE_NAME_MAX letters, digits, spaces, '-' or '_'
+pub fn sanitize_profile_name(raw: &str) -> Option<String> {
+    let name = raw.trim();
+    let valid = !name.is_empty()
+        && name.chars().count() <= PROFILE_NAME_MAX
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_'));
+    valid.then(|| name.to_string())
+}
+
+// Names of every saved profile, sorted; empty if there's no profiles folder yet
+pub fn list_profiles() -> io::Result<Vec<String>> {
+    let entries = match fs::read_dir(save_path(PROFILES_DIR)) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+// A profile that was never saved has no file, which is fine to "delete"
+pub fn delete_profile(name: &str) -> io::Result<()> {
+    match fs::remove_file(save_path(&profile_file(name))) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+// Refuses to overwrite an existing profile. Names are compared ignoring case
+// since some file systems do.
+pub fn rename_profile(old: &str, new: &str) -> io::Result<()> {
+    let taken = list_profiles()?
+        .iter()
+        .any(|name| name != old && name.eq_ignore_ascii_case(new));
+    if taken {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("a profile named {} already exists", new),
+        ));
+    }
+    let from = save_path(&profile_file(old));
+    if !from.exists() {
+        return Ok(());
+    }
+    fs::rename(from, save_path(&profile_file(new)))
+}
+
+// One finished run, appended as a JSON line to runs.jsonl for overlays and
+// personal stats when run logging is on
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub score: i32,
+    pub difficulty: String,
+    pub duration_secs: f32,
+    pub seed: u32,
+    pub max_combo: u32,
+    pub bonus_gates: u32,
+    // None when the run was cut short by quitting
+    pub death_cause: Option<DeathCause>,
+    pub ranked: bool,
+    pub profile: String,
+    // Why a run wasn't ranked: a cheat key, an assist option, or physics or
+    // layout changed by settings or difficulties.json. False in older logs.
+    #[serde(default)]
+    pub cheated: bool,
+    #[serde(default)]
+    pub assisted: bool,
+    #[serde(default)]
+    pub modified_physics: bool,
+}
+
+pub fn append_run_record(record: &RunRecord) -> io::Result<()> {
+    let line = serde_json::to_string(record).map_err(io::Error::other)?;
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(save_path("runs.jsonl"))?;
+    writeln!(file, "{}", line)
+}
+
+// Parses a save file's contents, surfacing corruption as an error so it isn't
+// quietly replaced with defaults
+pub fn parse_save<T: for<'de> Deserialize<'de> + Default>(data: Option<String>) -> io::Result<T> {
+    match data {
+        Some(data) => serde_json::from_str(&data)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+        None => Ok(T::default()),
+    }
+}
+
+// One value per difficulty slot, for records that aren't plain scores
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PerDifficulty<T> {
+    pub easy: T,
+    pub medium: T,
+    pub hard: T,
+    pub extreme: T,
+    pub custom: T,
+}
+
+impl<T: Copy> PerDifficulty<T> {
+    pub fn get(&self, difficulty: Difficulty) -> T {
+        *self.get_ref(difficulty)
+    }
+}
+
+impl<T> PerDifficulty<T> {
+    pub fn get_ref(&self, difficulty: Difficulty) -> &T {
+        match difficulty {
+            Difficulty::Easy => &self.easy,
+            Difficulty::Medium => &self.medium,
+            Difficulty::Hard => &self.hard,
+            Difficulty::Extreme => &self.extreme,
+            Difficulty::Custom(_) => &self.custom,
+        }
+    }
+
+    pub fn get_mut(&mut self, difficulty: Difficulty) -> &mut T {
+        match difficulty {
+            Difficulty::Easy => &mut self.easy,
+            Difficulty::Medium => &mut self.medium,
+            Difficulty::Hard => &mut self.hard,
+            Difficulty::Extreme => &mut self.extreme,
+            Difficulty::Custom(_) => &mut self.custom,
+        }
+    }
+}
+
+impl<T: Copy + PartialOrd> PerDifficulty<T> {
+    // Stores value if it beats the current record, returning whether it did
+    pub fn raise(&mut self, difficulty: Difficulty, value: T) -> bool {
+        if value > self.get(difficulty) {
+            *self.get_mut(difficulty) = value;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+// Which score table a run counts towards; modified runs are kept apart from
+// normal play
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Leaderboard {
+    Normal,
+    UpsideDown,
+    Adaptive,
+    Fuel,
+}
+
+// Why a run wasn't ranked: a cheat key, an assist option, or physics or
+// layout changed by settings or difficulties.json
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunFlags {
+    pub cheated: bool,
+    pub assisted: bool,
+    pub modified_physics: bool,
+}
+
+impl RunFlags {
+    pub fn any(&self) -> bool {
+        self.cheated || self.assisted || self.modified_physics
+    }
+
+    // Short mark for the leaderboard, e.g. "cheat, assist"
+    pub fn mark(&self) -> String {
+        let names = [
+            (self.cheated, "cheat"),
+            (self.assisted, "assist"),
+            (self.modified_physics, "physics"),
+        ];
+        let set: Vec<&str> = names
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, name)| *name)
+            .collect();
+        set.join(", ")
+    }
+}
+
+// The best a flagged run scored on a difficulty, with what it was flagged for.
+// It never counts as the high score; the menu shows it marked beside that.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FlaggedScore {
+    pub score: i32,
+    pub flags: RunFlags,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HighScores {
+    pub easy: i32,
+    pub medium: i32,
+    pub hard: i32,
+    pub extreme: i32,
+    pub custom: i32,
+    pub upside_down: PerDifficulty<i32>,
+    pub adaptive: PerDifficulty<i32>,
+    pub fuel: PerDifficulty<i32>,
+    pub best_stars: PerDifficulty<u32>,
+    // Longest run in seconds, however many points it scored
+    pub best_time: PerDifficulty<f32>,
+    // score_frames of the run behind each normal high score, for racing it
+    pub best_splits: PerDifficulty<Vec<f32>>,
+    // Most hardcore ladder stages cleared on a single life
+    pub best_ladder_stage: u32,
+    // Best normal-table scores from runs that were cheated, assisted or
+    // played with modified physics, kept apart from the real ones
+    pub flagged: PerDifficulty<FlaggedScore>,
+    // Guided progression, set from the profiles screen: Medium, Hard and
+    // Extreme each unlock once the difficulty before them has a high score of
+    // at least this. None (the default) has everything unlocked.
+    pub unlock_score: Option<i32>,
+}
+
+impl HighScores {
+    pub fn load(profile: &str) -> io::Result<Self> {
+        let data = match read_save_file(&profile_file(profile))? {
+            // Scores from before profiles existed carry over to the default one
+            None if profile == DEFAULT_PROFILE => read_save_file("highscores.json")?,
+            data => data,
+        };
+        parse_save(data)
+    }
+
+    pub fn save(&self, profile: &str) -> io::Result<()> {
+        let data = serde_json::to_string_pretty(self)?;
+        write_save_file(&profile_file(profile), &data)
+    }
+
+    // For a profile whose file failed to load (see back_up_save_file)
+    pub fn back_up(profile: &str) -> io::Result<()> {
+        back_up_save_file(&profile_file(profile))
+    }
+
+    pub fn get(&self, difficulty: Difficulty) -> i32 {
+        match difficulty {
+            Difficulty::Easy => self.easy,
+            Difficulty::Medium => self.medium,
+            Difficulty::Hard => self.hard,
+            Difficulty::Extreme => self.extreme,
+            Difficulty::Custom(_) => self.custom,
+        }
+    }
+
+    pub fn update(&mut self, difficulty: Difficulty, score: i32) -> bool {
+        let current_high = self.get(difficulty);
+        if score > current_high {
+            match difficulty {
+                Difficulty::Easy => self.easy = score,
+                Difficulty::Medium => self.medium = score,
+                Difficulty::Hard => self.hard = score,
+                Difficulty::Extreme => self.extreme = score,
+                Difficulty::Custom(_) => self.custom = score,
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn get_on(&self, board: Leaderboard, difficulty: Difficulty) -> i32 {
+        match board {
+            Leaderboard::Normal => self.get(difficulty),
+            Leaderboard::UpsideDown => self.upside_down.get(difficulty),
+            Leaderboard::Adaptive => self.adaptive.get(difficulty),
+            Leaderboard::Fuel => self.fuel.get(difficulty),
+        }
+    }
+
+    pub fn update_on(&mut self, board: Leaderboard, difficulty: Difficulty, score: i32) -> bool {
+        match board {
+            Leaderboard::Normal => self.update(difficulty, score),
+            Leaderboard::UpsideDown => self.upside_down.raise(difficulty, score),
+            Leaderboard::Adaptive => self.adaptive.raise(difficulty, score),
+            Leaderboard::Fuel => self.fuel.raise(difficulty, score),
+        }
+    }
+
+    pub fn update_stars(&mut self, difficulty: Difficulty, stars: u32) -> bool {
+        self.best_stars.raise(difficulty, stars)
+    }
+
+    pub fn update_time(&mut self, difficulty: Difficulty, seconds: f32) -> bool {
+        self.best_time.raise(difficulty, seconds)
+    }
+
+    // Keeps score if it beats the best flagged run so far; a run without any
+    // flags doesn't belong here and is ignored
+    pub fn update_flagged(&mut self, difficulty: Difficulty, score: i32, flags: RunFlags) -> bool {
+        let best = self.flagged.get_mut(difficulty);
+        let improved = flags.any() && score > best.score;
+        if improved {
+            *best = FlaggedScore { score, flags };
+        }
+        improved
+    }
+
+    pub fn update_ladder(&mut self, stages: u32) -> bool {
+        let improved = stages > self.best_ladder_stage;
+        if improved {
+            self.best_ladder_stage = stages;
+        }
+        improved
+    }
+
+    // The difficulty and high score still needed before `difficulty` can be
+    // played (the earliest one, if several are missing), or None once it's
+    // unlocked. Easy and Custom are always open.
+    pub fn unlock_requirement(&self, difficulty: Difficulty) -> Option<(Difficulty, i32)> {
+        let needed = self.unlock_score?;
+        let previous = match difficulty {
+            Difficulty::Medium => Difficulty::Easy,
+            Difficulty::Hard => Difficulty::Medium,
+            Difficulty::Extreme => Difficulty::Hard,
+            Difficulty::Easy | Difficulty::Custom(_) => return None,
+        };
+        self.unlock_requirement(previous)
+            .or_else(|| (self.get(previous) < needed).then_some((previous, needed)))
+    }
+
+    pub fn is_unlocked(&self, difficulty: Difficulty) -> bool {
+        self.unlock_requirement(difficulty).is_none()
+    }
+
+    // Splits for the normal high score, or None if they're missing or belong
+    // to an older record (saves from before splits were kept)
+    pub fn best_splits(&self, difficulty: Difficulty) -> Option<&[f32]> {
+        let splits = self.best_splits.get_ref(difficulty);
+        (!splits.is_empty() && splits.len() as i32 == self.get(difficulty)).then_some(splits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRESETS: [Difficulty; 4] = [
+        Difficulty::Easy,
+        Difficulty::Medium,
+        Difficulty::Hard,
+        Difficulty::Extreme,
+    ];
+
+    // A run in a default 800x600 world that nothing can kill, so it keeps
+    // spawning pipes for as long as a test steps it
+    fn endless(difficulty: Difficulty, seed: u32) -> Simulation {
+        let mut sim = Simulation::new(SimConfig::new(difficulty, seed, 800.0, 600.0));
+        sim.cheat_invincible = true;
+        sim
+    }
+
+    #[test]
+    fn easy_only_spawns_plain_pipes() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..1000 {
+            assert_eq!(
+                pick_obstacle(Difficulty::Easy.obstacle_set(), &mut rng),
+                Obstacle::Plain
+            );
+        }
+        for seed in 0..20 {
+            let mut sim = endless(Difficulty::Easy, seed);
+            for _ in 0..3000 {
+                sim.step(1.0);
+                assert!(sim.enemies.is_empty());
+                assert!(sim.pipes.iter().all(|pipe| pipe.drift == 0.0));
+            }
+        }
+    }
+
+    #[test]
+    fn extreme_can_spawn_every_obstacle() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut seen = Vec::new();
+        for _ in 0..1000 {
+            let obstacle = pick_obstacle(Difficulty::Extreme.obstacle_set(), &mut rng);
+            if !seen.contains(&obstacle) {
+                seen.push(obstacle);
+            }
+        }
+        for obstacle in [
+            Obstacle::Plain,
+            Obstacle::OffCenter,
+            Obstacle::Moving,
+            Obstacle::Double,
+            Obstacle::Enemy,
+        ] {
+            assert!(seen.contains(&obstacle), "{:?} never spawned", obstacle);
+        }
+    }
+
+    #[test]
+    fn pipes_stay_in_order_across_double_pipes() {
+        let mut doubles = 0;
+        for seed in 0..50 {
+            let mut sim = endless(Difficulty::Extreme, seed);
+            for _ in 0..3000 {
+                sim.step(1.0);
+                for pair in sim.pipes.windows(2) {
+                    assert!(pair[0].x < pair[1].x, "seed {} out of order", seed);
+                    let spacing = pair[1].x - pair[0].x;
+                    if approx_eq(spacing, pair[0].width + DOUBLE_PIPE_SPACING) {
+                        doubles += 1;
+                    }
+                }
+            }
+        }
+        assert!(doubles > 0, "no double pipes spawned");
+    }
+
+    #[test]
+    fn practice_starts_in_the_next_gap() {
+        for seed in 0..50 {
+            for pipe in [2, 5, 12] {
+                let mut sim = endless(Difficulty::Extreme, seed);
+                sim.advance_to_pipe(pipe);
+                let next = sim
+                    .pipes
+                    .iter()
+                    .filter(|pipe| !pipe.scored)
+                    .min_by(|a, b| a.x.total_cmp(&b.x))
+                    .unwrap();
+                let y = sim.bird.y;
+                assert!(
+                    y > next.gap_y && y < next.gap_y + next.gap_height,
+                    "seed {}",
+                    seed
+                );
+            }
+        }
+    }
+
+    fn challenge(seed: u32, difficulty: Difficulty, goal: Option<u32>) -> Challenge {
+        Challenge {
+            seed,
+            difficulty,
+            upside_down: false,
+            random_start: false,
+            fuel: false,
+            goal,
+        }
+    }
+
+    #[test]
+    fn challenge_codes_round_trip() {
+        let mut challenges = Vec::new();
+        for difficulty in CHALLENGE_DIFFICULTIES {
+            for seed in [0, 1, 12345, u32::MAX] {
+                for goal in [None, Some(10), Some(100)] {
+                    for flags in 0..8 {
+                        let mut c = challenge(seed, difficulty, goal);
+                        c.upside_down = flags & 1 != 0;
+                        c.random_start = flags & 2 != 0;
+                        c.fuel = flags & 4 != 0;
+                        challenges.push(c);
+                    }
+                }
+            }
+        }
+        for c in challenges {
+            let code = encode_challenge(&c).unwrap();
+            assert!(
+                !code.is_empty() && code.len() <= 10,
+                "{:?} gave {:?}",
+                c,
+                code
+            );
+            assert_eq!(decode_challenge(&code), Some(c));
+        }
+    }
+
+    #[test]
+    fn zero_challenge_still_has_a_code() {
+        let zero = challenge(0, Difficulty::Easy, None);
+        let code = encode_challenge(&zero).unwrap();
+        assert!(!code.is_empty());
+        assert_eq!(decode_challenge(&code), Some(zero));
+    }
+
+    #[test]
+    fn custom_and_unknown_goals_cant_be_shared() {
+        let custom = challenge(7, Difficulty::Custom(DifficultyParams::default()), None);
+        assert_eq!(encode_challenge(&custom), None);
+        assert_eq!(
+            encode_challenge(&challenge(7, Difficulty::Hard, Some(11))),
+            None
+        );
+        assert_eq!(decode_challenge(""), None);
+        assert_eq!(decode_challenge("not a code!"), None);
+    }
+
+    #[test]
+    fn a_save_file_that_is_a_directory_is_an_error() {
+        let name = "test-617-directory";
+        let path = save_path(&profile_file(name));
+        fs::create_dir_all(&path).unwrap();
+        let read = read_save_file(&profile_file(name));
+        let write = write_save_file(&profile_file(name), "{}");
+        let load = HighScores::load(name);
+        let save = HighScores::default().save(name);
+        fs::remove_dir(&path).unwrap();
+        assert!(read.is_err() && write.is_err());
+        assert!(load.is_err() && save.is_err());
+    }
+
+    #[test]
+    fn backups_never_replace_each_other() {
+        let name = "test-621-backups.json";
+        let backups = [format!("{}.bak", name), format!("{}.1.bak", name)];
+        for (i, contents) in ["first", "second"].into_iter().enumerate() {
+            write_save_file(name, contents).unwrap();
+            back_up_save_file(name).unwrap();
+            assert!(!save_path(name).exists());
+            assert_eq!(
+                fs::read_to_string(save_path(&backups[i])).unwrap(),
+                contents
+            );
+        }
+        let first = fs::read_to_string(save_path(&backups[0]));
+        for backup in &backups {
+            fs::remove_file(save_path(backup)).unwrap();
+        }
+        assert_eq!(first.unwrap(), "first");
+
+        // Nothing to back up is fine; a directory in the file's place isn't
+        assert!(back_up_save_file(name).is_ok());
+        let dir = "test-621-directory";
+        fs::create_dir_all(save_path(dir)).unwrap();
+        let result = back_up_save_file(dir);
+        let still_there = save_path(dir).is_dir();
+        fs::remove_dir(save_path(dir)).unwrap();
+        assert!(result.is_err() && still_there);
+        assert!(!save_path(&format!("{}.bak", dir)).exists());
+    }
+
+    #[test]
+    fn a_save_under_a_file_is_an_error() {
+        // The save's directory is taken by a file, so it can't be written; a
+        // read-only target fails the same way but root ignores permissions
+        let parent = "test-617-parent";
+        fs::write(save_path(parent), "keep me").unwrap();
+        let write = write_save_file(&format!("{}/scores.json", parent), "{}");
+        let kept = fs::read_to_string(save_path(parent));
+        fs::remove_file(save_path(parent)).unwrap();
+        assert!(write.is_err());
+        assert_eq!(kept.unwrap(), "keep me");
+    }
+
+    #[test]
+    fn flagged_runs_are_kept_apart_with_their_flags() {
+        let assisted = RunFlags {
+            assisted: true,
+            ..RunFlags::default()
+        };
+        let mut scores = HighScores::default();
+        assert!(!scores.update_flagged(Difficulty::Hard, 30, RunFlags::default()));
+        assert!(scores.update_flagged(Difficulty::Hard, 30, assisted));
+        assert!(!scores.update_flagged(Difficulty::Hard, 20, assisted));
+        assert_eq!(scores.get(Difficulty::Hard), 0);
+        assert_eq!(
+            scores.flagged.get(Difficulty::Hard),
+            FlaggedScore {
+                score: 30,
+                flags: assisted
+            }
+        );
+        assert_eq!(assisted.mark(), "assist");
+        let all = RunFlags {
+            cheated: true,
+            assisted: true,
+            modified_physics: true,
+        };
+        assert_eq!(all.mark(), "cheat, assist, physics");
+
+        let json = serde_json::to_string(&scores).unwrap();
+        let loaded: HighScores = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            loaded.flagged.get(Difficulty::Hard),
+            scores.flagged.get(Difficulty::Hard)
+        );
+        // Saves from before flags existed load with nothing flagged
+        let old: HighScores = serde_json::from_str(r#"{"easy": 5, "hard": 12}"#).unwrap();
+        assert_eq!(
+            (old.get(Difficulty::Hard), old.flagged.hard),
+            (12, FlaggedScore::default())
+        );
+    }
+
+    #[test]
+    fn a_missing_save_file_loads_as_defaults() {
+        let name = "test-617-missing";
+        assert!(!save_path(&profile_file(name)).exists());
+        assert_eq!(read_save_file(&profile_file(name)).unwrap(), None);
+        assert_eq!(HighScores::load(name).unwrap().get(Difficulty::Hard), 0);
+    }
+
+    // A run with no gravity and nothing spawning, holding just these pipes,
+    // already solid and pushed the way spawned pipes are. The bird hovers at
+    // mid-height, 300 in the default world.
+    fn hovering(pipes: Vec<Pipe>) -> Simulation {
+        let mut config = SimConfig::new(Difficulty::Medium, 1, 800.0, 600.0);
+        config.gravity = 0.0;
+        let mut sim = Simulation::new(config);
+        sim.pipe_spawn_timer = f32::NEG_INFINITY;
+        for mut pipe in pipes {
+            pipe.age = PIPE_ENTRANCE_FRAMES;
+            sim.push_pipe(pipe);
+        }
+        sim
+    }
+
+    // A pipe at x whose gap of the given height is centered on the bird
+    fn open_pipe(x: f32, gap_height: f32) -> Pipe {
+        Pipe::with_gap(x, 300.0 - gap_height / 2.0, gap_height, 520.0)
+    }
+
+    #[test]
+    fn pipes_score_once_and_leave_after_fading() {
+        let mut sim = hovering(vec![open_pipe(400.0, 180.0)]);
+        let mut scored = 0;
+        while let Some(pipe) = sim.pipes.first().cloned() {
+            // Still drawn, cap and all, until the cap has cleared the edge
+            assert!(pipe.x + pipe.width + PIPE_CAP_OVERHANG >= 0.0);
+            let events = sim.step(1.0);
+            scored += events
+                .iter()
+                .filter(|e| matches!(e, SimEvent::Scored { .. }))
+                .count();
+            // and it had all but faded out by the step that removed it
+            if sim.pipes.is_empty() {
+                let step = sim.effective_speed() / (pipe.width + PIPE_CAP_OVERHANG);
+                assert!(pipe.exit_fade() <= step);
+            }
+        }
+        assert_eq!(scored, 1);
+        assert_eq!(sim.score, 1);
+        assert!(sim.death.is_none());
+    }
+
+    #[test]
+    fn pipes_spawned_behind_the_bird_never_score() {
+        // A window narrower than where the bird flies spawns every pipe behind it
+        let mut config = SimConfig::new(Difficulty::Medium, 3, 800.0, 600.0);
+        config.bird_x = 1000.0;
+        let mut sim = Simulation::new(config);
+        sim.cheat_invincible = true;
+        for _ in 0..3000 {
+            let events = sim.step(1.0);
+            assert!(!events.iter().any(|e| matches!(e, SimEvent::Scored { .. })));
+        }
+        assert!(sim.pipes_spawned > 0);
+        assert_eq!(sim.score, 0);
+
+        let mut sim = hovering(vec![open_pipe(40.0, 180.0), open_pipe(400.0, 180.0)]);
+        assert!(!sim.pipes[0].was_ahead && sim.pipes[1].was_ahead);
+        while !sim.pipes.is_empty() {
+            sim.step(1.0);
+        }
+        assert_eq!(sim.score, 1);
+    }
+
+    #[test]
+    fn each_obstacle_scores_its_own_value_once() {
+        let clear = |pipes: Vec<Pipe>| {
+            let mut sim = hovering(pipes);
+            while !sim.pipes.is_empty() {
+                sim.step(1.0);
+            }
+            assert_eq!(sim.score_frames.len(), sim.score as usize);
+            sim
+        };
+        assert_eq!(clear(vec![open_pipe(400.0, 180.0)]).score, 1);
+
+        // Both halves of a double pipe are a pipe each
+        let back = 400.0 + PIPE_WIDTH + DOUBLE_PIPE_SPACING;
+        let double = clear(vec![open_pipe(400.0, 180.0), open_pipe(back, 180.0)]);
+        assert_eq!((double.score, double.total_passes), (2, 2));
+
+        let mut heavy = open_pipe(400.0, 180.0);
+        heavy.score_value = 3;
+        assert_eq!(clear(vec![heavy]).score, 3);
+
+        let mut gated = open_pipe(400.0, 180.0);
+        gated.bonus_gate = Some(GateState::Pending);
+        let sim = clear(vec![gated]);
+        assert_eq!(sim.bonus_gates, 1);
+        assert_eq!(sim.score, 1 + BONUS_GATE_POINTS);
+    }
+
+    // Gap height of each pipe in spawn order over the first `frames` frames
+    fn spawned_gaps(mut sim: Simulation, frames: usize) -> Vec<f32> {
+        let mut gaps = Vec::new();
+        for _ in 0..frames {
+            let before = sim.pipes_spawned as usize;
+            sim.step(1.0);
+            let new = sim.pipes_spawned as usize - before;
+            let start = sim.pipes.len() - new;
+            gaps.extend(sim.pipes[start..].iter().map(|pipe| pipe.gap_height));
+        }
+        gaps
+    }
+
+    #[test]
+    fn warm_up_only_widens_the_first_pipes() {
+        assert_eq!(warm_up_bonus(0), WARM_UP_GAP_BONUS);
+        assert_eq!(warm_up_bonus(WARM_UP_PIPES), 0.0);
+        for difficulty in [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard] {
+            let plain = spawned_gaps(endless(difficulty, 9), 2000);
+            let mut warm_up = endless(difficulty, 9);
+            warm_up.config.warm_up = true;
+            let warm_up = spawned_gaps(warm_up, 2000);
+            assert!(plain.len() > WARM_UP_PIPES as usize);
+            for (index, (plain, warm_up)) in plain.iter().zip(&warm_up).enumerate() {
+                let bonus = warm_up_bonus(index as u32);
+                assert!(
+                    approx_eq(*warm_up, plain + bonus),
+                    "{:?} pipe {}",
+                    difficulty,
+                    index
+                );
+                assert_eq!(bonus > 0.0, index < WARM_UP_PIPES as usize);
+            }
+        }
+
+        // Extreme keeps its gaps
+        let mut extreme = endless(Difficulty::Extreme, 9);
+        extreme.config.warm_up = true;
+        let plain = spawned_gaps(endless(Difficulty::Extreme, 9), 2000);
+        assert_eq!(spawned_gaps(extreme, 2000), plain);
+    }
+
+    #[test]
+    fn the_first_gap_covers_the_starting_height() {
+        for difficulty in PRESETS {
+            for random_start in [false, true] {
+                for seed in 0..100 {
+                    let mut config = SimConfig::new(difficulty, seed, 800.0, 600.0);
+                    config.random_start = random_start;
+                    let mut sim = Simulation::new(config);
+                    sim.cheat_invincible = true;
+                    while sim.pipes_spawned == 0 {
+                        sim.step(1.0);
+                    }
+                    let first = &sim.pipes[0];
+                    let (top, bottom) = (first.gap_y, first.gap_y + first.gap_height);
+                    let y = sim.start_y;
+                    assert!(top < y && y < bottom, "{:?} seed {}", difficulty, seed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_gap_never_shrinks_past_the_floor() {
+        for difficulty in PRESETS {
+            let mut sim = endless(difficulty, 1);
+            for score in [0, 10, 40, 1000, i32::MAX] {
+                sim.score = score;
+                let gap = sim.effective_gap();
+                assert!(gap >= difficulty.min_gap(), "{:?} at {}", difficulty, score);
+            }
+            assert_eq!(sim.effective_gap(), difficulty.min_gap());
+            // Adaptive tuning can't take it under the floor either
+            sim.score = 0;
+            sim.config.gap_offset = 1000.0;
+            assert_eq!(sim.effective_gap(), difficulty.min_gap());
+        }
+    }
+
+    #[test]
+    fn builtin_difficulties_keep_their_old_numbers() {
+        let table = DifficultyTable::default();
+        let numbers = |p: DifficultyParams| (p.pipe_gap, p.pipe_speed, p.min_gap);
+        assert_eq!(numbers(table.easy), (220.0, 2.0, 180.0));
+        assert_eq!(numbers(table.medium), (180.0, 2.5, 140.0));
+        assert_eq!(numbers(table.hard), (140.0, 3.0, 110.0));
+        assert_eq!(numbers(table.extreme), (120.0, 3.8, 100.0));
+        for params in [table.easy, table.medium, table.hard, table.extreme] {
+            assert_eq!(params.spawn_interval, 90.0);
+            assert_eq!(params.jump_cooldown, 0.0);
+        }
+        assert!(PRESETS.iter().all(|difficulty| !difficulty.is_retuned()));
+    }
+
+    #[test]
+    fn difficulty_overrides_only_touch_what_they_name() {
+        let mut table = serde_json::to_value(DifficultyTable::default()).unwrap();
+        merge_json(
+            &mut table,
+            serde_json::json!({"extreme": {"pipe_speed": 4.5}}),
+        );
+        let table: DifficultyTable = serde_json::from_value(table).unwrap();
+        let mut expected = DifficultyTable::default();
+        expected.extreme.pipe_speed = 4.5;
+        assert_eq!(table, expected);
+    }
+
+    #[test]
+    fn fast_steps_cant_carry_the_bird_through_anything() {
+        // Eight times speed is the fastest the debug controls go; a bird
+        // outside the gap still meets the pipe instead of skipping it
+        for difficulty in PRESETS {
+            let mut sim = hovering(vec![Pipe::with_gap(200.0, 400.0, 100.0, 520.0)]);
+            sim.config.difficulty = difficulty;
+            let mut events = Vec::new();
+            while sim.death.is_none() && !sim.pipes.is_empty() {
+                events.extend(sim.step(8.0));
+            }
+            assert_eq!(sim.death, Some(DeathCause::Pipe), "{:?}", difficulty);
+            assert!(!events.iter().any(|e| matches!(e, SimEvent::Scored { .. })));
+        }
+
+        // and a bird falling far faster than gravity allows still lands on the ground
+        let mut sim = hovering(Vec::new());
+        sim.bird.velocity = 10_000.0;
+        sim.step(1.0);
+        assert_eq!(sim.death, Some(DeathCause::Ground));
+    }
+
+    #[test]
+    fn marathon_runs_stay_stable() {
+        let mut sim = endless(Difficulty::Extreme, 4);
+        let mut last_spawn = 0;
+        let mut early_gaps = Vec::new();
+        for frame in 1..=900_000 {
+            let before = sim.pipes_spawned;
+            sim.step(1.0);
+            assert!(sim.pipes.len() < 20);
+            if sim.pipes_spawned == before {
+                continue;
+            }
+            // Pipes keep coming at the same few intervals hours in as they did
+            // in the first minutes
+            let gap = frame - last_spawn;
+            last_spawn = frame;
+            if frame < 20_000 {
+                if !early_gaps.contains(&gap) {
+                    early_gaps.push(gap);
+                }
+            } else {
+                assert!(
+                    early_gaps.contains(&gap),
+                    "{} frames between pipes at {}",
+                    gap,
+                    frame
+                );
+            }
+        }
+        assert_eq!(sim.elapsed, 900_000.0);
+        assert!(sim.pipes_spawned > 9_000 && sim.score > 9_000);
+        assert_eq!(sim.score_frames.len(), sim.score as usize);
+        assert!(sim.score_frames.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        sim.score = i32::MAX - 1;
+        sim.award(BONUS_GATE_POINTS);
+        assert_eq!(sim.score, i32::MAX);
+    }
+
+    #[test]
+    fn consecutive_gaps_stay_reachable() {
+        for difficulty in PRESETS {
+            for seed in 0..40 {
+                let mut sim = endless(difficulty, seed);
+                let speed = sim.effective_speed();
+                for _ in 0..5000 {
+                    let before = sim.pipes_spawned as usize;
+                    sim.step(1.0);
+                    let new = sim.pipes_spawned as usize - before;
+                    let start = sim.pipes.len().saturating_sub(new + 1);
+                    for pair in sim.pipes[start..].windows(2) {
+                        let (previous, pipe) = (&pair[0], &pair[1]);
+                        let horizontal = pipe.x - previous.x - pipe.width - BIRD_SIZE;
+                        let slack = (previous.gap_height - BIRD_SIZE) / 2.0
+                            + (pipe.gap_height - BIRD_SIZE) / 2.0;
+                        // Moving gaps can be anywhere within drift of base_y
+                        let middle = |p: &Pipe| if p.drift > 0.0 { p.base_y } else { p.gap_y };
+                        let center = |p: &Pipe| middle(p) + p.gap_height / 2.0;
+                        let apart =
+                            (center(pipe) - center(previous)).abs() + previous.drift + pipe.drift;
+                        let reach = max_reachable_delta(horizontal, speed) + slack;
+                        assert!(apart <= reach + 0.01, "{:?} seed {}", difficulty, seed);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn initials_are_cleaned_up_to_three_letters() {
+        assert_eq!(sanitize_initials("  abc  "), "ABC");
+        assert_eq!(sanitize_initials("abcdef"), "ABC");
+        assert_eq!(sanitize_initials("a"), "A__");
+        assert_eq!(sanitize_initials(""), "___");
+        assert_eq!(sanitize_initials("a b"), "A_B");
+        assert_eq!(sanitize_initials("j-4k!"), "JK_");
+        assert_eq!(sanitize_initials("éz"), "Z__");
+        assert_eq!(sanitize_initials("zoë"), "ZO_");
+    }
+
+    #[test]
+    fn blocked_initials_are_replaced() {
+        for blocked in BLOCKED_INITIALS {
+            assert_eq!(sanitize_initials(blocked), BLOCKED_INITIALS_REPLACEMENT);
+            assert_eq!(
+                sanitize_initials(&blocked.to_lowercase()),
+                BLOCKED_INITIALS_REPLACEMENT
+            );
+        }
+        // Junk and padding can't sneak one through
+        assert_eq!(sanitize_initials("a.s.s"), BLOCKED_INITIALS_REPLACEMENT);
+        assert_eq!(sanitize_initials("wtf!!"), BLOCKED_INITIALS_REPLACEMENT);
+        assert_eq!(sanitize_initials("ASH"), "ASH");
+    }
+
+    #[test]
+    fn run_records_round_trip() {
+        let record = RunRecord {
+            score: 42,
+            difficulty: "Hard".to_string(),
+            duration_secs: 61.5,
+            seed: 12345,
+            max_combo: 7,
+            bonus_gates: 2,
+            death_cause: Some(DeathCause::Ground),
+            ranked: false,
+            profile: DEFAULT_PROFILE.to_string(),
+            cheated: false,
+            assisted: true,
+            modified_physics: false,
+        };
+        let line = serde_json::to_string(&record).unwrap();
+        assert!(!line.contains('\n'));
+        assert_eq!(serde_json::from_str::<RunRecord>(&line).unwrap(), record);
+
+        let quit = RunRecord {
+            death_cause: None,
+            ..record
+        };
+        let line = serde_json::to_string(&quit).unwrap();
+        assert_eq!(serde_json::from_str::<RunRecord>(&line).unwrap(), quit);
+    }
+
+    #[test]
+    fn older_run_records_still_load() {
+        // A line logged before the ranking flags were recorded
+        let line = r#"{"score":3,"difficulty":"Easy","duration_secs":4.0,"seed":1,"max_combo":1,
+            "bonus_gates":0,"death_cause":"Pipe","ranked":true,"profile":"Player 1"}"#;
+        let record: RunRecord = serde_json::from_str(line).unwrap();
+        assert_eq!(record.death_cause, Some(DeathCause::Pipe));
+        assert!(!record.cheated && !record.assisted && !record.modified_physics);
+    }
+
+    fn level(looped: bool) -> Level {
+        let pipe = |gap_y, bonus_gate| LevelPipe {
+            gap_y,
+            gap_height: 160.0,
+            bonus_gate,
+        };
+        Level {
+            name: "Steps".to_string(),
+            pipes: vec![pipe(120.0, false), pipe(200.0, true), pipe(280.0, false)],
+            looped,
+        }
+    }
+
+    #[test]
+    fn levels_round_trip() {
+        let level = level(true);
+        let json = serde_json::to_string_pretty(&level).unwrap();
+        assert_eq!(serde_json::from_str::<Level>(&json).unwrap(), level);
+
+        // Only the gaps are required
+        let minimal: Level =
+            serde_json::from_str(r#"{"pipes": [{"gap_y": 100, "gap_height": 150}]}"#).unwrap();
+        assert_eq!((minimal.name.as_str(), minimal.looped), ("", false));
+        assert!(!minimal.pipes[0].bonus_gate);
+    }
+
+    #[test]
+    fn levels_are_checked_against_the_world() {
+        assert_eq!(level(false).validate(520.0), Ok(()));
+        assert!(level(false).validate(400.0).is_err());
+        assert!(Level {
+            pipes: Vec::new(),
+            ..level(false)
+        }
+        .validate(520.0)
+        .is_err());
+        let mut narrow = level(false);
+        narrow.pipes[1].gap_height = BIRD_SIZE;
+        assert_eq!(
+            narrow.validate(520.0),
+            Err("pipe 2's gap is too small for the bird".into())
+        );
+    }
+
+    #[test]
+    fn levels_spawn_their_pipes_in_order() {
+        for looped in [false, true] {
+            let mut sim = endless(Difficulty::Medium, 1);
+            sim.level = Some(level(looped));
+            let mut gaps = Vec::new();
+            for _ in 0..2000 {
+                let before = sim.pipes_spawned;
+                sim.step(1.0);
+                if sim.pipes_spawned > before {
+                    let pipe = sim.pipes.last().unwrap();
+                    gaps.push((pipe.gap_y, pipe.bonus_gate.is_some()));
+                }
+            }
+            let expected = [(120.0, false), (200.0, true), (280.0, false)];
+            assert_eq!(gaps[..3], expected);
+            if looped {
+                assert_eq!(gaps[3..6], expected);
+                assert!(!sim.completed);
+            } else {
+                assert_eq!(gaps.len(), 3);
+                assert!(sim.completed);
+            }
+        }
+    }
+
+    #[test]
+    fn tilting_narrows_the_bird_and_makes_it_taller() {
+        let mut bird = Bird::new(100.0, 200.0);
+        let size = BIRD_SIZE - 10.0;
+        let sin_45 = std::f32::consts::FRAC_1_SQRT_2;
+        for (rotation, squeeze) in [(0.0, 0.0), (45.0, sin_45), (90.0, 1.0), (-90.0, 1.0)] {
+            bird.rotation = rotation;
+            let bounds = bird.get_bounds();
+            let tilt = squeeze * BIRD_BOUNDS_TILT_SQUEEZE;
+            assert!(approx_eq(bounds.w, size * (1.0 - tilt)), "{}°", rotation);
+            assert!(approx_eq(bounds.h, size * (1.0 + tilt)), "{}°", rotation);
+            // The box stays centered on the bird
+            assert!(approx_eq(bounds.x + bounds.w / 2.0, bird.x));
+            assert!(approx_eq(bounds.y + bounds.h / 2.0, bird.y));
+        }
+    }
+
+    #[test]
+    fn assist_shrinks_the_hitbox_inside_the_normal_one() {
+        let mut config = SimConfig::new(Difficulty::Medium, 1, 800.0, 600.0);
+        let normal = Simulation::new(config).bird;
+        config.assist = true;
+        let assisted = Simulation::new(config).bird;
+        assert_eq!(assisted.hitbox_scale, ASSIST_HITBOX_SCALE);
+        for rotation in [-30.0, 0.0, 45.0, 90.0] {
+            let (mut normal, mut assisted) = (normal.clone(), assisted.clone());
+            normal.rotation = rotation;
+            assisted.rotation = rotation;
+            let (outer, inner) = (normal.get_bounds(), assisted.get_bounds());
+            assert!(
+                inner.x > outer.x && inner.x + inner.w < outer.x + outer.w,
+                "{}°",
+                rotation
+            );
+            assert!(
+                inner.y > outer.y && inner.y + inner.h < outer.y + outer.h,
+                "{}°",
+                rotation
+            );
+        }
+    }
+
+    #[test]
+    fn touching_only_a_cap_lip_is_a_hit() {
+        // Gap from 250 to 400, so the caps span y 230-250 and 400-420 and
+        // x 195-265 around the 200-260 body
+        let pipe = Pipe::with_gap(200.0, 250.0, 150.0, 520.0);
+        let half = (BIRD_SIZE - 10.0) / 2.0;
+        let bird = |right_edge: f32, y: f32| Bird::new(right_edge - half, y);
+        let lip = 200.0 - PIPE_CAP_OVERHANG;
+        for y in [240.0, 410.0] {
+            assert!(pipe.collides_with(&bird(lip + 2.0, y)), "y {}", y);
+            assert!(pipe.collides_with(&bird(lip, y)));
+            assert!(!pipe.collides_with(&bird(lip - 1.0, y)));
+        }
+        // Beside the body, away from the caps, the overhang isn't there
+        assert!(!pipe.collides_with(&bird(lip + 2.0, 100.0)));
+        assert!(!pipe.collides_with(&bird(lip + 2.0, 480.0)));
+        // and the middle of the gap is clear all the way through
+        assert!(!pipe.collides_with(&bird(230.0, 325.0)));
+    }
+
+    #[test]
+    fn pipes_collide_and_score_by_their_own_width() {
+        for width in [20.0, PIPE_WIDTH, 150.0] {
+            let mut blocking = Pipe::with_gap(0.0, 400.0, 100.0, 520.0);
+            blocking.width = width;
+            // A bird just inside the far edge of a pipe is hitting it, and one
+            // just past it isn't
+            blocking.x = 150.0 - width - (BIRD_SIZE - 10.0) / 2.0 + 1.0;
+            assert!(
+                blocking.collides_with(&Bird::new(150.0, 300.0)),
+                "width {}",
+                width
+            );
+            blocking.x -= 2.0 + PIPE_CAP_OVERHANG;
+            assert!(
+                !blocking.collides_with(&Bird::new(150.0, 300.0)),
+                "width {}",
+                width
+            );
+
+            let mut pipe = open_pipe(400.0, 180.0);
+            pipe.width = width;
+            let mut sim = hovering(vec![pipe]);
+            loop {
+                let events = sim.step(1.0);
+                let pipe = &sim.pipes[0];
+                let scored = events.iter().any(|e| matches!(e, SimEvent::Scored { .. }));
+                assert_eq!(scored, pipe.scored);
+                if scored {
+                    // On the first step the trailing edge is behind the bird
+                    let speed = sim.effective_speed();
+                    assert!(pipe.x + width < 150.0 && pipe.x + width + speed >= 150.0);
+                    break;
+                }
+            }
+        }
+
+        let mut config = SimConfig::new(Difficulty::Hard, 2, 800.0, 600.0);
+        config.pipe_width = 90.0;
+        let mut sim = Simulation::new(config);
+        sim.cheat_invincible = true;
+        for _ in 0..1000 {
+            sim.step(1.0);
+            assert!(sim.pipes.iter().all(|pipe| pipe.width == 90.0));
+        }
+        assert!(sim.score > 0);
+    }
+
+    #[test]
+    fn a_taller_ground_moves_the_floor_and_the_gaps() {
+        for ground_height in [40.0, GROUND_HEIGHT, 200.0] {
+            for difficulty in PRESETS {
+                let mut config = SimConfig::new(difficulty, 5, 800.0, 600.0);
+                config.ground_height = ground_height;
+                let mut sim = Simulation::new(config);
+                let floor = 600.0 - ground_height;
+                assert_eq!(sim.ground_y(), floor);
+
+                // The bird crashes where this ground starts, not the default one
+                sim.bird.y = floor - BIRD_SIZE / 2.0 - 1.0;
+                assert_eq!(sim.check_collision(), None);
+                sim.bird.y += 1.0;
+                assert_eq!(sim.check_collision(), Some(DeathCause::Ground));
+
+                sim.cheat_invincible = true;
+                sim.bird.y = 300.0;
+                for _ in 0..3000 {
+                    sim.step(1.0);
+                    for pipe in &sim.pipes {
+                        assert_eq!(pipe.floor, floor);
+                        assert!(pipe.gap_y >= 0.0 && pipe.gap_y + pipe.gap_height <= floor);
+                        let bottom = pipe.hitboxes()[1];
+                        assert!(approx_eq(bottom.y + bottom.h, floor));
+                    }
+                }
+            }
+        }
+    }
+
+    fn approx_eq(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.01
+    }
+}
@@ -1,723 +1,6038 @@
+use ::rand::rngs::StdRng;
+use ::rand::{Rng, SeedableRng};
+use flappy_bird::{
+    append_run_record, back_up_save_file, decode_challenge, delete_profile, encode_challenge,
+    list_profiles, parse_save, read_save_file, rename_profile, sanitize_profile_name, save_path,
+    score_at, write_save_file, Bird, Challenge, DeathCause, Difficulty, DifficultyParams,
+    DifficultyTable, Enemy, GateState, HighScores, Leaderboard, Level, PerDifficulty, Pipe,
+    RunFlags, RunRecord, SimConfig, SimEvent, Simulation, BIRD_SIZE, BIRD_START_X, CHALLENGE_GOALS,
+    DEFAULT_PROFILE, ENEMY_SIZE, EXTRA_JUMP_PICKUP_RADIUS, FUEL_PER_FLAP, GRAVITY, GROUND_HEIGHT,
+    JUMP_STRENGTH, PIPE_CAP_HEIGHT, PIPE_CAP_OVERHANG, PIPE_SPEED, PROFILE_NAME_MAX,
+};
+use macroquad::audio::{
+    load_sound_from_bytes, play_sound, set_sound_volume, stop_sound, PlaySoundParams, Sound,
+};
+use macroquad::miniquad::{BlendFactor, BlendState, BlendValue, Equation};
 use macroquad::prelude::*;
-use rand::Rng;
+// macroquad's clock, window and keyboard queries panic without a window, so
+// tests swap these in for them (see tests::window)
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::fs;
+use std::io::{self, Write};
+#[cfg(test)]
+use tests::window::{
+    get_time, is_key_down, is_key_pressed, is_quit_requested, screen_height, screen_width,
+};
 
-const GRAVITY: f32 = 0.5;
-const JUMP_STRENGTH: f32 = -8.0;
-const BIRD_SIZE: f32 = 30.0;
-const PIPE_WIDTH: f32 = 60.0;
-const PIPE_GAP: f32 = 180.0;
-const PIPE_SPEED: f32 = 2.5;
-const GROUND_HEIGHT: f32 = 80.0;
+const POWERUP_DURATION: f32 = 300.0;
+// Adaptive mode looks at the last few runs and shifts gap/speed by at most
+// these amounts, one step per run
+const ADAPTIVE_WINDOW: usize = 5;
+const ADAPTIVE_STRUGGLING_SCORE: f32 = 3.0;
+const ADAPTIVE_CRUISING_SCORE: f32 = 15.0;
+const ADAPTIVE_STEP: f32 = 0.25;
+const ADAPTIVE_MAX_GAP_OFFSET: f32 = 30.0;
+const ADAPTIVE_MAX_SPEED_OFFSET: f32 = 0.4;
+// How long the new high score fountain and pulsing text last, in frames
+const CELEBRATION_FRAMES: f32 = 180.0;
+// After a crash the camera closes in on the bird by up to DEATH_ZOOM while
+// the particles play at DEATH_TIME_SCALE, for this many seconds before the
+// game over screen
+const DEATH_ZOOM_SECONDS: f32 = 0.5;
+const DEATH_ZOOM: f32 = 1.6;
+const DEATH_TIME_SCALE: f32 = 0.3;
+// Screen shake strength in pixels for a near miss, and how much of it wears
+// off each frame
+const NEAR_MISS_SHAKE: f32 = 3.0;
+const SHAKE_DECAY: f32 = 0.3;
+// Upper bound on live particles so overlapping bursts can't tank the frame rate
+const MAX_PARTICLES: usize = 300;
+// Defaults for new particles, per 60fps frame
+const PARTICLE_GRAVITY: f32 = 0.2;
+const PARTICLE_FADE: f32 = 0.02;
+// Each consecutive clean pass raises the point chime a semitone, up to this many
+const COMBO_PITCH_STEPS: u32 = 8;
+const POINT_BASE_FREQUENCY: f32 = 660.0;
+// Flap (and refused flap) sounds closer together than this are skipped, in seconds
+const FLAP_SOUND_MIN_GAP: f64 = 0.04;
+// Default music volume for every track, and how much a track's volume may
+// change per frame while crossfading
+const MUSIC_VOLUME: f32 = 0.3;
+const MUSIC_FADE_STEP: f32 = 0.02;
+// Debug mode's [ and ] step the game speed through these, on top of slow
+// motion; anything but 1x counts as a cheat
+const DEBUG_TIME_SCALES: [f32; 7] = [0.1, 0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+const DEBUG_TIME_SCALE_NORMAL: usize = 3;
+// How far ahead the debug trajectory looks, in frames
+const TRAJECTORY_FRAMES: usize = 60;
+// Shortest time between frames drawn by the startup loading screen
+const LOADING_FRAME_SECONDS: f64 = 1.0 / 30.0;
+// Share of its volume the music keeps while paused or on the game over screen,
+// and how much of the difference is made up per frame when ducking or restoring
+const MUSIC_DUCK_VOLUME: f32 = 0.3;
+const MUSIC_DUCK_STEP: f32 = 0.05;
+// (min, max, step) for the pipe density multiplier
+const PIPE_DENSITY_RANGE: (f32, f32, f32) = (0.5, 2.0, 0.25);
+// (min, max, step) for the physics sliders; jump strength is the flap's upward speed
+const GRAVITY_RANGE: (f32, f32, f32) = (0.3, 0.7, 0.05);
+const JUMP_STRENGTH_RANGE: (f32, f32, f32) = (6.0, 10.0, 0.5);
+// (min, max, step) for overlay darkness, which scales the pause and game over
+// overlays' full-strength alpha below
+const OVERLAY_DARKNESS_RANGE: (f32, f32, f32) = (0.0, 1.0, 0.1);
+const PAUSE_OVERLAY_ALPHA: f32 = 180.0 / 255.0;
+const GAME_OVER_OVERLAY_ALPHA: f32 = 200.0 / 255.0;
+// Border drawn around pipes by the built-in themes
+const PIPE_OUTLINE_WIDTH: f32 = 3.0;
+// The gap guide is training wheels and switches itself off past this score
+const GAP_GUIDE_MAX_SCORE: i32 = 10;
+// With assist on, a ring closes in on the bird over the last TIMING_RING_FRAMES
+// before the next pipe arrives, until the score reaches TIMING_RING_MAX_SCORE
+const TIMING_RING_FRAMES: f32 = 60.0;
+const TIMING_RING_MAX_SCORE: i32 = 10;
+// Background stripes scroll this many pixels per frame at Medium's pipe speed,
+// faster or slower with the difficulty's; clouds drift at CLOUD_PARALLAX of that
+const BACKGROUND_SCROLL_SPEED: f32 = 1.0;
+const CLOUD_PARALLAX: f32 = 0.5;
+// Width of one cloud, from the left edge of its first puff to the right edge of its last
+const CLOUD_WIDTH: f32 = 150.0;
+// Speed lines fade in once the pipes move faster than SPEED_LINES_START and are
+// at full strength from SPEED_LINES_FULL (px per frame)
+const SPEED_LINES_START: f32 = 2.75;
+const SPEED_LINES_FULL: f32 = 4.2;
+// Squash and stretch: the body's aspect changes this much per unit of vertical
+// speed, up to BIRD_MAX_STRETCH either way
+const BIRD_STRETCH_PER_SPEED: f32 = 0.025;
+const BIRD_MAX_STRETCH: f32 = 0.2;
+// Timeouts the auto-pause setting cycles through, in seconds
+const AUTO_PAUSE_CHOICES: [f32; 4] = [3.0, 5.0, 10.0, 20.0];
+// Jump strength multipliers the keyboard and mouse jump settings cycle through
+const JUMP_SCALE_CHOICES: [f32; 5] = [0.8, 0.9, 1.0, 1.1, 1.2];
+// How much gravity the fall assist setting can take off a fast fall
+const FALL_ASSIST_CHOICES: [f32; 3] = [0.25, 0.5, 0.75];
+// Score intervals the milestone setting cycles through
+const MILESTONE_CHOICES: [i32; 4] = [10, 25, 50, 100];
+// The milestone banner slides in, holds and slides out over this many frames,
+// kept short so it never hides the next pipe for long
+const MILESTONE_BANNER_FRAMES: f32 = 60.0;
+// The first-launch tutorial runs the world at this speed until the player has
+// passed TUTORIAL_PIPES pipes, then its prompts fade out over TUTORIAL_FADE_FRAMES
+const TUTORIAL_PIPES: i32 = 3;
+const TUTORIAL_TIME_SCALE: f32 = 0.6;
+const TUTORIAL_FADE_FRAMES: f32 = 45.0;
+// Outside the tutorial, a flap reminder hangs by the bird for the first
+// HINT_SECONDS of a run, fading out over HINT_FADE_SECONDS (sooner once the
+// bird flaps). It stops appearing after HINT_SESSION_FLAPS flaps this session.
+const HINT_SECONDS: f32 = 5.0;
+const HINT_FADE_SECONDS: f32 = 0.5;
+const HINT_SESSION_FLAPS: u32 = 3;
+// Default seconds after a crash during which the game over screen ignores
+// retry/menu keys, so a reflex jump press doesn't skip past it
+const RETRY_LOCKOUT_SECONDS: f32 = 0.7;
+// The retry prompt fades in over this many seconds once the lockout ends
+const RETRY_PROMPT_FADE_SECONDS: f32 = 0.3;
+// Length of the whole fade out and back in between the menu and a run
+const TRANSITION_SECONDS: f64 = 0.3;
+// Crossing this score once in a run sets off applause, confetti and a
+// "LEGENDARY" banner shown for LEGENDARY_BANNER_FRAMES
+const LEGENDARY_SCORE: i32 = 100;
+const LEGENDARY_BANNER_FRAMES: f32 = 180.0;
+// How long "BEST!" flashes next to the HUD's best score once a run beats it
+const BEST_FLASH_FRAMES: f32 = 90.0;
+// Hardcore ladder: reach LADDER_TARGET on each difficulty in turn without dying
+// once; a crash anywhere starts the climb over from Easy
+const LADDER: [Difficulty; 4] = [
+    Difficulty::Easy,
+    Difficulty::Medium,
+    Difficulty::Hard,
+    Difficulty::Extreme,
+];
+const LADDER_TARGET: i32 = 20;
+// GIF clips keep the last GIF_CLIP_SECONDS of play, one frame every
+// GIF_FRAME_SECONDS (the GIF delay in hundredths matches), downscaled to
+// GIF_WIDTH pixels across. That's about 3MB of frames in an 800x600 window.
+const GIF_CLIP_SECONDS: f32 = 5.0;
+const GIF_FRAME_SECONDS: f32 = 0.07;
+const GIF_FRAME_DELAY: u16 = 7;
+const GIF_WIDTH: usize = 240;
+// How long the game over screen keeps recording, so a clip shows the crash
+const GIF_GAME_OVER_SECONDS: f32 = 1.0;
+const CLIP_NOTICE_SECONDS: f64 = 3.0;
+// The follower bird trails FOLLOWER_OFFSET_X to the left of the player, at the
+// heights the player flew FOLLOWER_DELAY_FRAMES ago. It unlocks once any
+// difficulty's high score reaches FOLLOWER_UNLOCK_SCORE.
+const FOLLOWER_OFFSET_X: f32 = 36.0;
+const FOLLOWER_DELAY_FRAMES: usize = 8;
+const FOLLOWER_UNLOCK_SCORE: i32 = 25;
+const FOLLOWER_COLOR: Color = Color::new(1.0, 0.55, 0.7, 1.0);
+// Pixel art mode draws each frame at this many lines (the width follows the
+// window's aspect) and blows it up with nearest-neighbor filtering
+const PIXEL_ART_HEIGHT: f32 = 240.0;
+// Settings rows shown at once; the list scrolls to keep the selected one in view
+const SETTINGS_VISIBLE_ROWS: usize = 15;
+// Longest changed scores wait in memory before being written out, unless a
+// pause, game over, profile switch or quit flushes them sooner
+const SAVE_INTERVAL_SECONDS: f32 = 5.0;
+// Window size on first launch, and the smallest saved size that's restored;
+// anything smaller in settings.json is treated as corrupt
+const DEFAULT_WINDOW_SIZE: (u32, u32) = (800, 600);
+const MIN_WINDOW_SIZE: (u32, u32) = (320, 240);
+// Keeps the profiles screen to a single page
+const MAX_PROFILES: usize = 8;
+// High scores a guided profile can need before the next difficulty unlocks
+// (see HighScores::unlock_score), cycled with G on the profiles screen
+const UNLOCK_SCORE_CHOICES: [i32; 4] = [5, 10, 25, 50];
+// The slow motion vignette fades in and out over this many frames
+const SLOW_MOTION_FADE_FRAMES: f32 = 12.0;
+// A jump press that can't be applied right away (e.g. the cooldown is still
+// running) is kept around this long, in seconds, and applied as soon as it can be
+const JUMP_BUFFER_SECONDS: f64 = 0.08;
+const NOMINAL_FRAME_DT: f32 = 1.0 / 60.0;
+const MAX_FRAME_DT: f32 = 1.0 / 20.0;
+// A frame that took longer than this (the OS suspended the game, a laptop
+// throttled hard) pauses the run instead of carrying on blind
+const LAG_PAUSE_SECONDS: f32 = 0.25;
 
-#[derive(Clone, Copy, PartialEq)]
+// Credits screen lines; "{version}" is replaced with the crate version
+const CREDITS: &[&str] = &[
+    "FLAPPY BIRD",
+    "Rust Edition",
+    "",
+    "Version {version}",
+    "",
+    "Game Design & Programming",
+    "Flappy Bird Developer",
+    "",
+    "Built With",
+    "Rust",
+    "Macroquad",
+    "rand",
+    "serde & serde_json",
+    "",
+    "Inspired By",
+    "Flappy Bird by Dong Nguyen",
+    "",
+    "Thanks for playing!",
+];
+const CREDITS_SCROLL_SPEED: f32 = 40.0;
+const CREDITS_LINE_HEIGHT: f32 = 40.0;
+
+// Each page of the How to Play screen is a list of (heading, lines) sections
+const HOW_TO_PLAY_PAGES: &[&[(&str, &[&str])]] = &[
+    &[
+        (
+            "Goal",
+            &[
+                "Fly through the gaps between the pipes",
+                "Every pipe you pass is worth 1 point",
+                "Hitting a pipe, the ground, or the ceiling ends the run",
+            ],
+        ),
+        (
+            "Controls",
+            &[
+                "SPACE / LEFT CLICK - Jump",
+                "ESC - Pause / Resume",
+                "Q - Main Menu (while paused or after a run)",
+            ],
+        ),
+    ],
+    &[
+        (
+            "Difficulty",
+            &[
+                "Harder levels shrink the gap and speed up the pipes",
+                "Custom lets you tune gap, speed, and spawn rate",
+                "Every difficulty keeps its own high score",
+            ],
+        ),
+        (
+            "Debug & Cheats",
+            &[
+                "Enable with --debug or CTRL+SHIFT+D",
+                "H - Toggle Hitboxes",
+                "I - Toggle Invincibility",
+                "S - Toggle Slow Motion",
+            ],
+        ),
+    ],
+    &[
+        (
+            "Modifiers",
+            &[
+                "Toggle these on the main menu before starting",
+                "U - Upside-Down: gravity pulls up, jumps push down",
+                "A - Adaptive: gap and speed follow how you're doing",
+                "R - Random Start: begin each run at a random height",
+                "F - Fuel: flaps burn fuel, which slowly refills",
+                "Upside-Down, Adaptive and Fuel runs keep their own high scores",
+                "Changing pipe density in Settings makes runs unranked",
+            ],
+        ),
+        (
+            "Challenges",
+            &[
+                "Every run shows a challenge code on the game over screen",
+                "E (menu) - Enter a code to fly the exact same pipes",
+            ],
+        ),
+    ],
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum GameState {
     Menu,
     Playing,
     Paused,
     GameOver,
+    CustomSetup,
+    HowToPlay,
+    Credits,
+    EnterCode,
+    Settings,
+    Profiles,
 }
 
-#[derive(Clone, Copy, PartialEq)]
-enum Difficulty {
-    Easy,
-    Medium,
-    Hard,
-    Extreme,
+// What the profiles screen is in the middle of: typing a name for a new or
+// renamed profile, or waiting for a delete to be confirmed
+#[derive(Clone, PartialEq)]
+enum ProfileEdit {
+    None,
+    Create(String),
+    Rename(String),
+    Delete,
+}
+
+// A fade to black and back between the menu and a run. The change happens
+// at the darkest point, halfway through.
+#[derive(Clone, Copy)]
+struct Transition {
+    started: f64,
+    change: StateChange,
+    applied: bool,
+}
+
+#[derive(Clone, Copy)]
+enum StateChange {
+    // Whether the run is a tutorial
+    StartRun(bool),
+    StartChallenge(Challenge),
+    StartLevel,
+    StartLadder,
+    ToMenu,
+}
+
+// Everything a run reacts to in one frame: playing, pausing and the game over
+// screen take their input from here instead of asking macroquad, so a bot, a
+// replay or another input backend can drive them through Game::tick
+#[derive(Clone, Copy, Default)]
+struct InputState {
+    // Which frame the presses were read on
+    frame: u64,
+    // The bound jump key and the left mouse button
+    jump: bool,
+    click: bool,
+    pause: bool,
+    // SPACE or ENTER, ESC, and the Q and C shortcuts
+    confirm: bool,
+    back: bool,
+    quit: bool,
+    credits: bool,
+    // G on the game over screen, cycling the shared code's goal
+    cycle_goal: bool,
+    // Debug toggles, ignored outside debug mode
+    toggle_hitboxes: bool,
+    toggle_invincible: bool,
+    toggle_slow_motion: bool,
+    toggle_trajectory: bool,
+    slower: bool,
+    faster: bool,
+    // Any key or click at all, for auto-pause
+    any: bool,
+    // Any mouse button, or any key that isn't the pause key, a modifier or
+    // reserved (see reserved_key_use); a jump with simple controls on
+    tap: bool,
+}
+
+impl InputState {
+    // This frame's presses from the keyboard and mouse
+    fn read(keys: Keybindings, frame: u64) -> Self {
+        let click = is_mouse_button_pressed(MouseButton::Left);
+        let modifiers = [
+            KeyCode::LeftShift,
+            KeyCode::RightShift,
+            KeyCode::LeftControl,
+            KeyCode::RightControl,
+            KeyCode::LeftAlt,
+            KeyCode::RightAlt,
+            KeyCode::LeftSuper,
+            KeyCode::RightSuper,
+        ];
+        let tap_key = get_keys_pressed().into_iter().any(|key| {
+            key != keys.pause && !modifiers.contains(&key) && reserved_key_use(key).is_none()
+        });
+        let tap_button = [MouseButton::Left, MouseButton::Right, MouseButton::Middle]
+            .into_iter()
+            .any(is_mouse_button_pressed);
+        Self {
+            frame,
+            jump: is_key_pressed(keys.jump),
+            click,
+            pause: is_key_pressed(keys.pause),
+            confirm: is_key_pressed(KeyCode::Space) || is_key_pressed(KeyCode::Enter),
+            back: is_key_pressed(KeyCode::Escape),
+            quit: is_key_pressed(KeyCode::Q),
+            credits: is_key_pressed(KeyCode::C),
+            cycle_goal: is_key_pressed(KeyCode::G),
+            toggle_hitboxes: is_key_pressed(KeyCode::H),
+            toggle_invincible: is_key_pressed(KeyCode::I),
+            toggle_slow_motion: is_key_pressed(KeyCode::S),
+            toggle_trajectory: is_key_pressed(KeyCode::T),
+            slower: is_key_pressed(KeyCode::LeftBracket),
+            faster: is_key_pressed(KeyCode::RightBracket),
+            any: get_last_key_pressed().is_some() || click,
+            tap: tap_key || tap_button,
+        }
+    }
+
+    // Drops every press once one of them has changed the state, so nothing
+    // later in the same tick acts on it as well
+    fn consume(&mut self) {
+        *self = Self {
+            frame: self.frame,
+            ..Self::default()
+        };
+    }
+
+    // This input with nothing left in it if it's from the frame whose presses
+    // were already spent. A driver that ticks faster than it reads input hands
+    // the same frame in again, and the state the press led to mustn't see it.
+    fn unspent(self, spent: Option<u64>) -> Self {
+        if spent == Some(self.frame) {
+            Self {
+                frame: self.frame,
+                ..Self::default()
+            }
+        } else {
+            self
+        }
+    }
+}
+
+// How each difficulty looks and feels on screen; the gameplay numbers live in
+// the library
+trait DifficultyStyle {
+    fn color(&self) -> Color;
+    fn bird_color(&self) -> Color;
+    fn background_colors(&self) -> ([Color; 3], Color);
+    fn celebration_particles(&self) -> usize;
+    fn score_burst(&self) -> (usize, f32);
+    // Multiplies the graphics quality's cloud count
+    fn cloud_density(&self) -> f32;
+    // Multiplies the background scroll speed so the backdrop keeps pace with the pipes
+    fn scroll_factor(&self) -> f32;
 }
 
-impl Difficulty {
-    fn pipe_gap(&self) -> f32 {
+impl DifficultyStyle for Difficulty {
+    fn color(&self) -> Color {
         match self {
-            Difficulty::Easy => 220.0,
-            Difficulty::Medium => 180.0,
-            Difficulty::Hard => 140.0,
-            Difficulty::Extreme => 120.0,
+            Difficulty::Easy => GREEN,
+            Difficulty::Medium => YELLOW,
+            Difficulty::Hard => ORANGE,
+            Difficulty::Extreme => RED,
+            Difficulty::Custom(_) => VIOLET,
         }
     }
 
-    fn pipe_speed(&self) -> f32 {
+    // Subtle tint that gets hotter with difficulty while keeping the classic yellow feel
+    fn bird_color(&self) -> Color {
         match self {
-            Difficulty::Easy => 2.0,
-            Difficulty::Medium => 2.5,
-            Difficulty::Hard => 3.0,
-            Difficulty::Extreme => 3.8,
+            Difficulty::Easy | Difficulty::Custom(_) => YELLOW,
+            Difficulty::Medium => Color::from_rgba(255, 224, 40, 255),
+            Difficulty::Hard => Color::from_rgba(255, 200, 45, 255),
+            Difficulty::Extreme => Color::from_rgba(255, 165, 60, 255),
         }
     }
 
-    fn name(&self) -> &str {
+    // Sky stripe shades and cloud color: bright daytime on Easy through to a
+    // dark storm on Extreme, so the tier is obvious at a glance
+    fn background_colors(&self) -> ([Color; 3], Color) {
+        let stripes = |r: u8, g: u8, b: u8| sky_stripes(Color::from_rgba(r, g, b, 255));
         match self {
-            Difficulty::Easy => "Easy",
-            Difficulty::Medium => "Medium",
-            Difficulty::Hard => "Hard",
-            Difficulty::Extreme => "Extreme",
+            Difficulty::Easy => (stripes(150, 220, 250), WHITE),
+            Difficulty::Medium => (stripes(135, 206, 235), WHITE),
+            Difficulty::Hard => (stripes(225, 160, 120), Color::from_rgba(255, 225, 210, 255)),
+            Difficulty::Extreme => (stripes(45, 52, 72), Color::from_rgba(95, 100, 115, 255)),
+            Difficulty::Custom(_) => (stripes(150, 125, 200), Color::from_rgba(235, 225, 255, 255)),
         }
     }
-}
 
-struct Bird {
-    x: f32,
-    y: f32,
-    velocity: f32,
-    rotation: f32,
-    color: Color,
-}
+    // Gold particles per frame while celebrating a new high score; beating
+    // Extreme should feel like it
+    fn celebration_particles(&self) -> usize {
+        match self {
+            Difficulty::Easy => 1,
+            Difficulty::Medium | Difficulty::Custom(_) => 2,
+            Difficulty::Hard => 3,
+            Difficulty::Extreme => 5,
+        }
+    }
 
-impl Bird {
-    fn new(x: f32, y: f32) -> Self {
-        Self {
-            x,
-            y,
-            velocity: 0.0,
-            rotation: 0.0,
-            color: YELLOW,
+    // Gold particles for each point scored and how far they fly (1.0 is a
+    // normal burst), so points earned on Extreme go off harder than on Easy
+    fn score_burst(&self) -> (usize, f32) {
+        match self {
+            Difficulty::Easy => (10, 0.8),
+            Difficulty::Medium | Difficulty::Custom(_) => (15, 1.0),
+            Difficulty::Hard => (20, 1.2),
+            Difficulty::Extreme => (28, 1.5),
         }
     }
 
-    fn update(&mut self) {
-        self.velocity += GRAVITY;
-        self.y += self.velocity;
-        
-        // Update rotation based on velocity
-        self.rotation = (self.velocity * 3.0).clamp(-30.0, 90.0);
+    fn cloud_density(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.8,
+            Difficulty::Medium | Difficulty::Custom(_) => 1.0,
+            Difficulty::Hard => 1.2,
+            Difficulty::Extreme => 1.8,
+        }
     }
 
-    fn jump(&mut self) {
-        self.velocity = JUMP_STRENGTH;
+    fn scroll_factor(&self) -> f32 {
+        self.pipe_speed() / PIPE_SPEED
     }
+}
 
-    fn draw(&self) {
-        let bird_rect = Rect::new(
-            self.x - BIRD_SIZE / 2.0,
-            self.y - BIRD_SIZE / 2.0,
-            BIRD_SIZE,
-            BIRD_SIZE,
-        );
-
-        // Draw bird body
-        draw_circle(self.x, self.y, BIRD_SIZE / 2.0, self.color);
-        
-        // Draw eye
-        draw_circle(self.x + 8.0, self.y - 5.0, 5.0, WHITE);
-        draw_circle(self.x + 10.0, self.y - 5.0, 3.0, BLACK);
-        
-        // Draw beak
-        draw_triangle(
-            Vec2::new(self.x + BIRD_SIZE / 2.0, self.y),
-            Vec2::new(self.x + BIRD_SIZE / 2.0 + 10.0, self.y - 5.0),
-            Vec2::new(self.x + BIRD_SIZE / 2.0 + 10.0, self.y + 5.0),
-            ORANGE,
-        );
+// The sky is drawn as stripes a little redder than each other
+fn sky_stripes(base: Color) -> [Color; 3] {
+    let redder = |amount: f32| Color {
+        r: (base.r + amount / 255.0).min(1.0),
+        ..base
+    };
+    [base, redder(10.0), redder(20.0)]
+}
+
+fn death_particle_color(cause: DeathCause) -> Color {
+    match cause {
+        DeathCause::Pipe => RED,
+        DeathCause::Ceiling => Color::from_rgba(200, 230, 255, 255),
+        DeathCause::Ground => Color::from_rgba(139, 69, 19, 255),
+        DeathCause::Enemy => PURPLE,
     }
+}
 
-    fn get_bounds(&self) -> Rect {
-        Rect::new(
-            self.x - BIRD_SIZE / 2.0 + 5.0,
-            self.y - BIRD_SIZE / 2.0 + 5.0,
-            BIRD_SIZE - 10.0,
-            BIRD_SIZE - 10.0,
-        )
+// How much taller than wide the bird is drawn: stretched while shooting up
+// after a jump, squashed while dropping fast
+fn bird_stretch(velocity: f32) -> f32 {
+    (1.0 - velocity * BIRD_STRETCH_PER_SPEED).clamp(1.0 - BIRD_MAX_STRETCH, 1.0 + BIRD_MAX_STRETCH)
+}
+
+fn draw_round(x: f32, y: f32, radius: f32, sides: u8, color: Color) {
+    draw_poly(x, y, sides, radius, 0.0, color);
+}
+
+// Same fan as macroquad's draw_ellipse, which is fixed at 20 sides
+fn draw_round_ellipse(x: f32, y: f32, radius_x: f32, radius_y: f32, sides: u8, color: Color) {
+    let point = |i: u8| {
+        let angle = i as f32 / sides as f32 * std::f32::consts::TAU;
+        Vec2::new(x + radius_x * angle.cos(), y + radius_y * angle.sin())
+    };
+    let center = Vec2::new(x, y);
+    for i in 0..sides {
+        draw_triangle(center, point(i), point(i + 1), color);
     }
 }
 
-struct Pipe {
-    x: f32,
-    gap_y: f32,
-    gap_height: f32,
-    scored: bool,
-    color_top: Color,
-    color_bottom: Color,
-}
-
-impl Pipe {
-    fn new(x: f32, gap_height: f32) -> Self {
-        let mut rng = rand::thread_rng();
-        let gap_y = rng.gen_range(150.0..(screen_height() - GROUND_HEIGHT - gap_height - 100.0));
-        
+// The menu's bird, centered and bobbing around y
+fn draw_menu_bird(y: f32, sides: u8) {
+    let bird_x = screen_width() / 2.0;
+    let bird_y = y + (get_time() * 2.0).sin() as f32 * 10.0;
+    draw_round(bird_x, bird_y, BIRD_SIZE / 2.0, sides, YELLOW);
+    draw_round(bird_x + 8.0, bird_y - 5.0, 5.0, sides, WHITE);
+    draw_round(bird_x + 10.0, bird_y - 5.0, 3.0, sides, BLACK);
+}
+
+// stretch scales the body vertically (and inversely horizontally, so it keeps
+// its area); the eye and beak follow the deformed outline
+fn draw_bird(bird: &Bird, color: Color, tint: Option<Color>, stretch: f32, sides: u8) {
+    let radius_x = BIRD_SIZE / 2.0 / stretch;
+    let radius_y = BIRD_SIZE / 2.0 * stretch;
+
+    // Draw bird body
+    draw_round_ellipse(
+        bird.x,
+        bird.y,
+        radius_x,
+        radius_y,
+        sides,
+        tint.unwrap_or(color),
+    );
+
+    // Draw eye
+    let eye_x = bird.x + 8.0 / stretch;
+    let eye_y = bird.y - 5.0 * stretch;
+    draw_round(eye_x, eye_y, 5.0, sides, WHITE);
+    draw_round(eye_x + 2.0, eye_y, 3.0, sides, BLACK);
+
+    // Draw beak
+    let beak_x = bird.x + radius_x;
+    draw_triangle(
+        Vec2::new(beak_x, bird.y),
+        Vec2::new(beak_x + 10.0, bird.y - 5.0),
+        Vec2::new(beak_x + 10.0, bird.y + 5.0),
+        ORANGE,
+    );
+}
+
+// A dark purple bird facing the player, its wings beating as it bobs
+fn draw_enemy(enemy: &Enemy, sides: u8) {
+    let radius = ENEMY_SIZE / 2.0;
+    let (x, y) = (enemy.x, enemy.y);
+    let flap = (enemy.age * 0.4).sin() * 6.0;
+    draw_triangle(
+        vec2(x - 2.0, y - 2.0),
+        vec2(x + 6.0, y - 2.0),
+        vec2(x + 2.0, y - 2.0 - radius - flap),
+        Color::from_rgba(70, 30, 90, 255),
+    );
+    draw_round(x, y, radius, sides, Color::from_rgba(100, 40, 130, 255));
+    draw_round(x - 5.0, y - 3.0, 4.0, sides, WHITE);
+    draw_round(x - 6.0, y - 3.0, 2.0, sides, RED);
+    draw_triangle(
+        vec2(x - radius, y),
+        vec2(x - radius - 8.0, y + 4.0),
+        vec2(x - radius + 1.0, y + 5.0),
+        ORANGE,
+    );
+}
+
+// Fill colors for the two halves of a pipe, plus the border shared by the body
+// and the cap so recolored pipes stay consistent. A zero outline width draws
+// flat pipes with no border at all.
+struct PipeColors {
+    top: Color,
+    bottom: Color,
+    outline: Color,
+    outline_width: f32,
+}
+
+impl Default for PipeColors {
+    fn default() -> Self {
         Self {
-            x,
-            gap_y,
-            gap_height,
-            scored: false,
-            color_top: GREEN,
-            color_bottom: GREEN,
+            top: GREEN,
+            bottom: GREEN,
+            outline: DARKGREEN,
+            outline_width: PIPE_OUTLINE_WIDTH,
         }
     }
+}
 
-    fn update(&mut self, speed: f32) {
-        self.x -= speed;
+// Glowing gold ring in the gap; a missed gate stays as a faint outline and a
+// hit one disappears into its particle burst
+fn draw_bonus_gate(pipe: &Pipe) {
+    let alpha = pipe.entrance_progress() * pipe.exit_fade();
+    let (x, y) = pipe.gate_center();
+    let radius = pipe.gate_radius();
+    match pipe.bonus_gate {
+        Some(GateState::Pending) => {
+            let pulse = 0.75 + 0.25 * (get_time() * 6.0).sin() as f32;
+            for i in 0..4 {
+                let glow = Color {
+                    a: 0.15 * alpha * pulse,
+                    ..GOLD
+                };
+                draw_circle_lines(x, y, radius + i as f32 * 3.0, 3.0, glow);
+            }
+            draw_circle_lines(x, y, radius, 3.0, Color { a: alpha, ..GOLD });
+        }
+        Some(GateState::Missed) => {
+            draw_circle_lines(
+                x,
+                y,
+                radius,
+                2.0,
+                Color {
+                    a: 0.3 * alpha,
+                    ..LIGHTGRAY
+                },
+            );
+        }
+        Some(GateState::Hit) | None => {}
     }
+}
 
-    fn draw(&self) {
-        // Top pipe
-        draw_rectangle(
-            self.x,
-            0.0,
-            PIPE_WIDTH,
-            self.gap_y,
-            self.color_top,
-        );
-        draw_rectangle_lines(self.x, 0.0, PIPE_WIDTH, self.gap_y, 3.0, DARKGREEN);
-        
-        // Top pipe cap
-        draw_rectangle(
-            self.x - 5.0,
-            self.gap_y - 20.0,
-            PIPE_WIDTH + 10.0,
-            20.0,
-            self.color_top,
-        );
-        draw_rectangle_lines(
-            self.x - 5.0,
-            self.gap_y - 20.0,
-            PIPE_WIDTH + 10.0,
-            20.0,
-            3.0,
-            DARKGREEN,
+// A sky blue bubble with an upward chevron; it pops when collected
+fn draw_extra_jump_pickup(pipe: &Pipe, sides: u8) {
+    if !pipe.extra_jump {
+        return;
+    }
+    let alpha = pipe.entrance_progress() * pipe.exit_fade();
+    let (x, y) = pipe.gate_center();
+    let bob = (get_time() * 4.0).sin() as f32 * 2.0;
+    let y = y + bob;
+    let radius = EXTRA_JUMP_PICKUP_RADIUS;
+    draw_round(
+        x,
+        y,
+        radius,
+        sides,
+        Color {
+            a: 0.8 * alpha,
+            ..SKYBLUE
+        },
+    );
+    draw_circle_lines(x, y, radius, 2.0, Color { a: alpha, ..WHITE });
+    let white = Color { a: alpha, ..WHITE };
+    for offset in [-3.0, 4.0] {
+        draw_triangle(
+            vec2(x, y - 6.0 + offset),
+            vec2(x - 6.0, y + offset),
+            vec2(x + 6.0, y + offset),
+            white,
         );
+    }
+}
 
-        // Bottom pipe
-        let bottom_y = self.gap_y + self.gap_height;
-        draw_rectangle(
-            self.x,
-            bottom_y + 20.0,
-            PIPE_WIDTH,
-            screen_height() - bottom_y - GROUND_HEIGHT - 20.0,
-            self.color_bottom,
-        );
-        draw_rectangle_lines(
-            self.x,
-            bottom_y + 20.0,
-            PIPE_WIDTH,
-            screen_height() - bottom_y - GROUND_HEIGHT - 20.0,
-            3.0,
-            DARKGREEN,
-        );
-        
-        // Bottom pipe cap
-        draw_rectangle(
-            self.x - 5.0,
-            bottom_y,
-            PIPE_WIDTH + 10.0,
-            20.0,
-            self.color_bottom,
-        );
-        draw_rectangle_lines(
-            self.x - 5.0,
-            bottom_y,
-            PIPE_WIDTH + 10.0,
-            20.0,
-            3.0,
-            DARKGREEN,
-        );
+// Small pips over the bird, one for each banked extra flap
+fn draw_extra_jump_pips(bird: &Bird, sides: u8) {
+    let count = bird.extra_jumps;
+    let spacing = 10.0;
+    let left = bird.x - (count.saturating_sub(1)) as f32 * spacing / 2.0;
+    for i in 0..count {
+        let x = left + i as f32 * spacing;
+        let y = bird.y - BIRD_SIZE / 2.0 - 12.0;
+        draw_round(x, y, 3.5, sides, SKYBLUE);
+        draw_circle_lines(x, y, 3.5, 1.0, WHITE);
     }
+}
 
-    fn collides_with(&self, bird: &Bird) -> bool {
-        let bird_bounds = bird.get_bounds();
-        
-        // Check collision with top pipe
-        let top_pipe = Rect::new(self.x, 0.0, PIPE_WIDTH, self.gap_y);
-        if bird_bounds.overlaps(&top_pipe) {
-            return true;
-        }
+// A thin gold bar under the bird showing the flap boost built up so far
+fn draw_boost_meter(bird: &Bird, boost: f32) {
+    if boost <= 0.0 {
+        return;
+    }
+    let width = BIRD_SIZE;
+    let x = bird.x - width / 2.0;
+    let y = bird.y + BIRD_SIZE / 2.0 + 8.0;
+    draw_rectangle(x, y, width, 3.0, Color::new(0.0, 0.0, 0.0, 0.4));
+    draw_rectangle(x, y, width * boost.min(1.0), 3.0, GOLD);
+}
 
-        // Check collision with bottom pipe
-        let bottom_y = self.gap_y + self.gap_height;
-        let bottom_pipe = Rect::new(
-            self.x,
-            bottom_y,
-            PIPE_WIDTH,
-            screen_height() - bottom_y - GROUND_HEIGHT,
-        );
-        if bird_bounds.overlaps(&bottom_pipe) {
-            return true;
+// Fills a rectangle in vertical bands, lighter in the middle and darker at
+// the edges, so pipes look round. Zero bands draws it flat.
+fn draw_shaded_rect(x: f32, y: f32, w: f32, h: f32, color: Color, bands: usize) {
+    if bands == 0 {
+        draw_rectangle(x, y, w, h, color);
+        return;
+    }
+    let band_width = w / bands as f32;
+    for i in 0..bands {
+        // -1.0 at the left edge, 0.0 in the middle, 1.0 at the right edge
+        let across = (i as f32 + 0.5) / bands as f32 * 2.0 - 1.0;
+        let shade = 1.2 - 0.5 * across * across;
+        let band = Color {
+            r: (color.r * shade).min(1.0),
+            g: (color.g * shade).min(1.0),
+            b: (color.b * shade).min(1.0),
+            a: color.a,
+        };
+        draw_rectangle(x + i as f32 * band_width, y, band_width, h, band);
+    }
+}
+
+fn draw_pipe(pipe: &Pipe, colors: &PipeColors, bands: usize) {
+    // While entering, the pipes grow in from the ceiling and ground and fade in
+    let grow = pipe.entrance_progress();
+    let alpha = grow * pipe.exit_fade();
+    let gap_y = pipe.gap_y * grow;
+    let floor = pipe.floor;
+    let bottom_y = floor - (floor - (pipe.gap_y + pipe.gap_height)) * grow;
+    let color_top = Color {
+        a: colors.top.a * alpha,
+        ..colors.top
+    };
+    let color_bottom = Color {
+        a: colors.bottom.a * alpha,
+        ..colors.bottom
+    };
+    let outline = Color {
+        a: colors.outline.a * alpha,
+        ..colors.outline
+    };
+    let outline_width = colors.outline_width;
+    let draw_outline = |x: f32, y: f32, w: f32, h: f32| {
+        if outline_width > 0.0 {
+            draw_rectangle_lines(x, y, w, h, outline_width, outline);
         }
+    };
+    let cap_width = pipe.width + PIPE_CAP_OVERHANG * 2.0;
+
+    // Top pipe
+    draw_shaded_rect(pipe.x, 0.0, pipe.width, gap_y, color_top, bands);
+    draw_outline(pipe.x, 0.0, pipe.width, gap_y);
+
+    // Top pipe cap
+    draw_shaded_rect(
+        pipe.x - PIPE_CAP_OVERHANG,
+        gap_y - PIPE_CAP_HEIGHT,
+        cap_width,
+        PIPE_CAP_HEIGHT,
+        color_top,
+        bands,
+    );
+    draw_outline(
+        pipe.x - PIPE_CAP_OVERHANG,
+        gap_y - PIPE_CAP_HEIGHT,
+        cap_width,
+        PIPE_CAP_HEIGHT,
+    );
+
+    // Bottom pipe
+    draw_shaded_rect(
+        pipe.x,
+        bottom_y + PIPE_CAP_HEIGHT,
+        pipe.width,
+        floor - bottom_y - PIPE_CAP_HEIGHT,
+        color_bottom,
+        bands,
+    );
+    draw_outline(
+        pipe.x,
+        bottom_y + PIPE_CAP_HEIGHT,
+        pipe.width,
+        floor - bottom_y - PIPE_CAP_HEIGHT,
+    );
+
+    // Bottom pipe cap
+    draw_shaded_rect(
+        pipe.x - PIPE_CAP_OVERHANG,
+        bottom_y,
+        cap_width,
+        PIPE_CAP_HEIGHT,
+        color_bottom,
+        bands,
+    );
+    draw_outline(
+        pipe.x - PIPE_CAP_OVERHANG,
+        bottom_y,
+        cap_width,
+        PIPE_CAP_HEIGHT,
+    );
+}
 
-        false
+// How the window is opened; read before the window exists, so changes apply
+// on the next launch
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+enum WindowMode {
+    #[default]
+    Windowed,
+    // A borderless window covering the whole screen. This is what macroquad's
+    // fullscreen is on desktop (it never switches display modes), which keeps
+    // alt-tabbing and screen capture painless.
+    Borderless,
+}
+
+impl WindowMode {
+    fn toggled(&self) -> Self {
+        match self {
+            WindowMode::Windowed => WindowMode::Borderless,
+            WindowMode::Borderless => WindowMode::Windowed,
+        }
     }
 
-    fn is_offscreen(&self) -> bool {
-        self.x + PIPE_WIDTH < 0.0
+    fn name(&self) -> &str {
+        match self {
+            WindowMode::Windowed => "Windowed",
+            WindowMode::Borderless => "Borderless",
+        }
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct HighScores {
-    easy: i32,
-    medium: i32,
-    hard: i32,
-    extreme: i32,
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+enum GraphicsQuality {
+    Low,
+    #[default]
+    Medium,
+    High,
 }
 
-impl Default for HighScores {
-    fn default() -> Self {
-        Self {
-            easy: 0,
-            medium: 0,
-            hard: 0,
-            extreme: 0,
+impl GraphicsQuality {
+    fn next(&self) -> Self {
+        match self {
+            GraphicsQuality::Low => GraphicsQuality::Medium,
+            GraphicsQuality::Medium => GraphicsQuality::High,
+            GraphicsQuality::High => GraphicsQuality::Low,
         }
     }
-}
 
-impl HighScores {
-    fn load() -> Self {
-        if let Ok(data) = fs::read_to_string("highscores.json") {
-            serde_json::from_str(&data).unwrap_or_default()
-        } else {
-            Self::default()
+    fn prev(&self) -> Self {
+        match self {
+            GraphicsQuality::Low => GraphicsQuality::High,
+            GraphicsQuality::Medium => GraphicsQuality::Low,
+            GraphicsQuality::High => GraphicsQuality::Medium,
+        }
+    }
+
+    // Segments used for round shapes (bird, particles, clouds). Medium matches
+    // macroquad's own draw_circle so the default look is unchanged
+    fn circle_sides(&self) -> u8 {
+        match self {
+            GraphicsQuality::Low => 12,
+            GraphicsQuality::Medium => 20,
+            GraphicsQuality::High => 48,
         }
     }
 
-    fn save(&self) {
-        if let Ok(data) = serde_json::to_string_pretty(self) {
-            let _ = fs::write("highscores.json", data);
+    // Shading bands across each pipe; Low keeps them flat
+    fn pipe_shade_bands(&self) -> usize {
+        match self {
+            GraphicsQuality::Low => 0,
+            GraphicsQuality::Medium => 6,
+            GraphicsQuality::High => 14,
         }
     }
 
-    fn get(&self, difficulty: Difficulty) -> i32 {
-        match difficulty {
-            Difficulty::Easy => self.easy,
-            Difficulty::Medium => self.medium,
-            Difficulty::Hard => self.hard,
-            Difficulty::Extreme => self.extreme,
+    // Multiplies the size of bursts that scale with how well the player is doing
+    fn particle_scale(&self) -> f32 {
+        match self {
+            GraphicsQuality::Low => 0.5,
+            GraphicsQuality::Medium => 1.0,
+            GraphicsQuality::High => 1.5,
         }
     }
 
-    fn update(&mut self, difficulty: Difficulty, score: i32) -> bool {
-        let current_high = self.get(difficulty);
-        if score > current_high {
-            match difficulty {
-                Difficulty::Easy => self.easy = score,
-                Difficulty::Medium => self.medium = score,
-                Difficulty::Hard => self.hard = score,
-                Difficulty::Extreme => self.extreme = score,
-            }
-            true
-        } else {
-            false
+    // Streaks along the top and bottom of the sky at high speed; none on Low
+    fn speed_lines(&self) -> usize {
+        match self {
+            GraphicsQuality::Low => 0,
+            GraphicsQuality::Medium => 6,
+            GraphicsQuality::High => 12,
+        }
+    }
+
+    // Clouds drawn at the default theme density
+    fn cloud_count(&self) -> usize {
+        match self {
+            GraphicsQuality::Low => 2,
+            GraphicsQuality::Medium => 5,
+            GraphicsQuality::High => 8,
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            GraphicsQuality::Low => "Low",
+            GraphicsQuality::Medium => "Medium",
+            GraphicsQuality::High => "High",
         }
     }
 }
 
-struct Particle {
-    x: f32,
-    y: f32,
-    vx: f32,
-    vy: f32,
-    life: f32,
-    color: Color,
-    size: f32,
+// Keys that can be bound to an action; they're saved by their KeyCode names
+const BINDABLE_KEYS: &[KeyCode] = &[
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Space,
+    KeyCode::Enter,
+    KeyCode::Tab,
+    KeyCode::Backspace,
+    KeyCode::Escape,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::LeftShift,
+    KeyCode::RightShift,
+    KeyCode::LeftControl,
+    KeyCode::RightControl,
+    KeyCode::LeftAlt,
+    KeyCode::RightAlt,
+];
+
+// Fixed keys the game already uses for something else while playing
+fn reserved_key_use(key: KeyCode) -> Option<&'static str> {
+    match key {
+        KeyCode::F1 => Some("build info"),
+        KeyCode::F9 => Some("saving a GIF clip"),
+        KeyCode::Q => Some("quitting to the menu"),
+        KeyCode::H => Some("debug hitboxes"),
+        KeyCode::I => Some("debug invincibility"),
+        KeyCode::S => Some("debug slow motion"),
+        KeyCode::T => Some("debug trajectory"),
+        KeyCode::LeftBracket | KeyCode::RightBracket => Some("debug speed"),
+        _ => None,
+    }
 }
 
-impl Particle {
-    fn update(&mut self) {
-        self.x += self.vx;
-        self.y += self.vy;
-        self.vy += 0.2; // gravity
-        self.life -= 0.02;
+// KeyCode has no serde support, so bindings are stored by variant name
+// Colors in theme.json are [r, g, b, a] with each channel 0 - 255
+mod rgba {
+    use macroquad::prelude::Color;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        color_to_bytes(*color).serialize(serializer)
     }
 
-    fn draw(&self) {
-        let alpha = (self.life * 255.0) as u8;
-        let color = Color::new(
-            self.color.r,
-            self.color.g,
-            self.color.b,
-            alpha as f32 / 255.0,
-        );
-        draw_circle(self.x, self.y, self.size, color);
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let [r, g, b, a] = <[u8; 4]>::deserialize(deserializer)?;
+        Ok(Color::from_rgba(r, g, b, a))
     }
 
-    fn is_dead(&self) -> bool {
-        self.life <= 0.0
+    fn color_to_bytes(color: Color) -> [u8; 4] {
+        [color.r, color.g, color.b, color.a].map(|channel| (channel * 255.0).round() as u8)
+    }
+
+    // For colors a theme may leave to the difficulty
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            color: &Option<Color>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            color.map(color_to_bytes).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Color>, D::Error> {
+            let bytes = Option::<[u8; 4]>::deserialize(deserializer)?;
+            Ok(bytes.map(|[r, g, b, a]| Color::from_rgba(r, g, b, a)))
+        }
     }
 }
 
-struct Game {
-    bird: Bird,
-    pipes: Vec<Pipe>,
-    particles: Vec<Particle>,
-    score: i32,
-    high_scores: HighScores,
-    state: GameState,
-    difficulty: Difficulty,
-    pipe_spawn_timer: f32,
-    background_offset: f32,
-    show_hitboxes: bool,
-    powerup_timer: f32,
-    invincible: bool,
-    slow_motion: bool,
-    slow_motion_timer: f32,
+// Colors for the world and HUD. Sky, clouds and bird left unset (null) keep
+// each difficulty's own colors. Missing keys in theme.json fall back to Classic.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+struct Theme {
+    #[serde(with = "rgba::option")]
+    sky: Option<Color>,
+    #[serde(with = "rgba::option")]
+    clouds: Option<Color>,
+    #[serde(with = "rgba::option")]
+    bird: Option<Color>,
+    #[serde(with = "rgba")]
+    ground: Color,
+    #[serde(with = "rgba")]
+    grass: Color,
+    #[serde(with = "rgba")]
+    pipe: Color,
+    #[serde(with = "rgba")]
+    pipe_outline: Color,
+    // Border thickness around pipes in pixels; 0 draws them flat
+    pipe_outline_width: f32,
+    // Score text and the best score line
+    #[serde(with = "rgba")]
+    text: Color,
+    #[serde(with = "rgba")]
+    accent: Color,
 }
 
-impl Game {
-    fn new() -> Self {
+impl Default for Theme {
+    fn default() -> Self {
         Self {
-            bird: Bird::new(150.0, screen_height() / 2.0),
-            pipes: Vec::new(),
-            particles: Vec::new(),
-            score: 0,
-            high_scores: HighScores::load(),
-            state: GameState::Menu,
-            difficulty: Difficulty::Medium,
-            pipe_spawn_timer: 0.0,
-            background_offset: 0.0,
-            show_hitboxes: false,
-            powerup_timer: 0.0,
-            invincible: false,
-            slow_motion: false,
-            slow_motion_timer: 0.0,
+            sky: None,
+            clouds: None,
+            bird: None,
+            ground: Color::from_rgba(139, 69, 19, 255),
+            grass: Color::from_rgba(34, 139, 34, 255),
+            pipe: GREEN,
+            pipe_outline: DARKGREEN,
+            pipe_outline_width: PIPE_OUTLINE_WIDTH,
+            text: WHITE,
+            accent: GOLD,
         }
     }
+}
 
-    fn reset(&mut self) {
-        self.bird = Bird::new(150.0, screen_height() / 2.0);
-        self.pipes.clear();
-        self.particles.clear();
-        self.score = 0;
-        self.pipe_spawn_timer = 0.0;
-        self.invincible = false;
-        self.slow_motion = false;
-        self.slow_motion_timer = 0.0;
+impl Theme {
+    // theme.json next to the executable, or None (with a note on stderr) if
+    // it's missing or unreadable
+    fn load_custom() -> Option<Self> {
+        let theme = read_save_file("theme.json").and_then(|data| match data {
+            Some(_) => parse_save(data).map(Some),
+            None => Ok(None),
+        });
+        match theme {
+            Ok(theme) => theme,
+            Err(err) => {
+                eprintln!(
+                    "Couldn't load theme.json, using the Classic colors: {}",
+                    err
+                );
+                None
+            }
+        }
     }
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+enum ThemeChoice {
+    #[default]
+    Classic,
+    Night,
+    Retro,
+    // Whatever theme.json holds
+    Custom,
+}
+
+impl ThemeChoice {
+    const ALL: [ThemeChoice; 4] = [
+        ThemeChoice::Classic,
+        ThemeChoice::Night,
+        ThemeChoice::Retro,
+        ThemeChoice::Custom,
+    ];
 
-    fn spawn_pipe(&mut self) {
-        let x = screen_width() + 50.0;
-        self.pipes.push(Pipe::new(x, self.difficulty.pipe_gap()));
+    fn step(&self, steps: f32) -> Self {
+        let count = Self::ALL.len();
+        let current = Self::ALL
+            .iter()
+            .position(|choice| choice == self)
+            .unwrap_or(0);
+        let next = if steps > 0.0 {
+            current + 1
+        } else {
+            current + count - 1
+        };
+        Self::ALL[next % count]
     }
 
-    fn spawn_particles(&mut self, x: f32, y: f32, color: Color, count: usize) {
-        let mut rng = rand::thread_rng();
-        for _ in 0..count {
-            self.particles.push(Particle {
-                x,
-                y,
-                vx: rng.gen_range(-3.0..3.0),
-                vy: rng.gen_range(-5.0..-1.0),
-                life: 1.0,
-                color,
-                size: rng.gen_range(2.0..6.0),
-            });
+    fn name(&self) -> &str {
+        match self {
+            ThemeChoice::Classic => "Classic",
+            ThemeChoice::Night => "Night",
+            ThemeChoice::Retro => "Retro",
+            ThemeChoice::Custom => "Custom",
         }
     }
 
-    fn update(&mut self) {
-        match self.state {
-            GameState::Menu => {
-                if is_key_pressed(KeyCode::Space) || is_key_pressed(KeyCode::Enter) {
-                    self.reset();
-                    self.state = GameState::Playing;
-                }
-                if is_key_pressed(KeyCode::Key1) {
-                    self.difficulty = Difficulty::Easy;
-                }
-                if is_key_pressed(KeyCode::Key2) {
-                    self.difficulty = Difficulty::Medium;
-                }
-                if is_key_pressed(KeyCode::Key3) {
-                    self.difficulty = Difficulty::Hard;
-                }
-                if is_key_pressed(KeyCode::Key4) {
-                    self.difficulty = Difficulty::Extreme;
+    // custom is the loaded theme.json, if there was one
+    fn theme(&self, custom: Option<&Theme>) -> Theme {
+        match self {
+            ThemeChoice::Classic => Theme::default(),
+            ThemeChoice::Night => Theme {
+                sky: Some(Color::from_rgba(20, 24, 52, 255)),
+                clouds: Some(Color::from_rgba(60, 66, 100, 255)),
+                bird: Some(Color::from_rgba(255, 240, 170, 255)),
+                ground: Color::from_rgba(52, 36, 30, 255),
+                grass: Color::from_rgba(24, 70, 44, 255),
+                pipe: Color::from_rgba(40, 130, 120, 255),
+                pipe_outline: Color::from_rgba(18, 60, 58, 255),
+                pipe_outline_width: PIPE_OUTLINE_WIDTH,
+                text: WHITE,
+                accent: SKYBLUE,
+            },
+            // Four shades of handheld green
+            ThemeChoice::Retro => {
+                let shade = |i: usize| {
+                    let [r, g, b] = [[155, 188, 15], [139, 172, 15], [48, 98, 48], [15, 56, 15]][i];
+                    Color::from_rgba(r, g, b, 255)
+                };
+                Theme {
+                    sky: Some(shade(0)),
+                    clouds: Some(shade(1)),
+                    bird: Some(shade(3)),
+                    ground: shade(2),
+                    grass: shade(3),
+                    pipe: shade(2),
+                    pipe_outline: shade(3),
+                    pipe_outline_width: PIPE_OUTLINE_WIDTH,
+                    text: shade(3),
+                    accent: shade(2),
                 }
             }
-            GameState::Playing => {
-                if is_key_pressed(KeyCode::Escape) {
-                    self.state = GameState::Paused;
-                    return;
-                }
+            ThemeChoice::Custom => custom.copied().unwrap_or_default(),
+        }
+    }
+}
 
-                // Handle jump
-                if is_key_pressed(KeyCode::Space) || is_mouse_button_pressed(MouseButton::Left) {
-                    self.bird.jump();
-                    self.spawn_particles(self.bird.x, self.bird.y, SKYBLUE, 5);
-                }
+mod key_name {
+    use super::BINDABLE_KEYS;
+    use macroquad::prelude::KeyCode;
+    use serde::{de, Deserialize, Deserializer, Serializer};
 
-                // Toggle hitboxes (debug)
-                if is_key_pressed(KeyCode::H) {
-                    self.show_hitboxes = !self.show_hitboxes;
-                }
+    pub fn serialize<S: Serializer>(key: &KeyCode, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:?}", key))
+    }
 
-                // Cheat codes for fun
-                if is_key_pressed(KeyCode::I) {
-                    self.invincible = !self.invincible;
-                }
-                if is_key_pressed(KeyCode::S) {
-                    self.slow_motion = !self.slow_motion;
-                }
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<KeyCode, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        BINDABLE_KEYS
+            .iter()
+            .find(|key| format!("{:?}", key) == name)
+            .copied()
+            .ok_or_else(|| de::Error::custom(format!("unknown key \"{}\"", name)))
+    }
+}
 
-                let time_scale = if self.slow_motion { 0.5 } else { 1.0 };
+// Name shown on screen: digits drop their "Key" prefix
+fn key_label(key: KeyCode) -> String {
+    let name = format!("{:?}", key);
+    match name.strip_prefix("Key") {
+        Some(digit) if !digit.is_empty() => digit.to_string(),
+        _ => name,
+    }
+}
 
-                // Update bird
-                self.bird.update();
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum HudCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
 
-                // Update background
-                self.background_offset -= 1.0 * time_scale;
-                if self.background_offset <= -50.0 {
-                    self.background_offset = 0.0;
-                }
+impl HudCorner {
+    fn is_right(&self) -> bool {
+        matches!(self, HudCorner::TopRight | HudCorner::BottomRight)
+    }
 
-                // Spawn pipes
-                self.pipe_spawn_timer += 1.0 * time_scale;
-                if self.pipe_spawn_timer > 90.0 {
-                    self.spawn_pipe();
-                    self.pipe_spawn_timer = 0.0;
-                }
+    fn is_bottom(&self) -> bool {
+        matches!(self, HudCorner::BottomLeft | HudCorner::BottomRight)
+    }
+}
 
-                // Update pipes
-                let speed = self.difficulty.pipe_speed() * time_scale;
-                for pipe in &mut self.pipes {
-                    pipe.update(speed);
+#[derive(Clone, Copy, PartialEq)]
+enum HudElement {
+    Score,
+    Best,
+    Status,
+    Difficulty,
+    Timer,
+}
 
-                    // Check if bird passed pipe
-                    if !pipe.scored && pipe.x + PIPE_WIDTH < self.bird.x {
-                        pipe.scored = true;
-                        self.score += 1;
-                        self.spawn_particles(pipe.x + PIPE_WIDTH / 2.0, screen_height() / 2.0, GOLD, 15);
-                    }
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct HudWidget {
+    shown: bool,
+    corner: HudCorner,
+}
 
-                    // Check collision
-                    if !self.invincible && pipe.collides_with(&self.bird) {
-                        self.state = GameState::GameOver;
-                        self.spawn_particles(self.bird.x, self.bird.y, RED, 30);
-                        
-                        // Update high score
-                        if self.high_scores.update(self.difficulty, self.score) {
-                            self.high_scores.save();
-                        }
-                    }
-                }
+impl HudWidget {
+    const fn new(shown: bool, corner: HudCorner) -> Self {
+        Self { shown, corner }
+    }
+}
 
-                // Remove offscreen pipes
-                self.pipes.retain(|pipe| !pipe.is_offscreen());
+// Which in-run HUD elements are drawn and in which corner. Elements sharing a
+// corner stack in the order of the fields below. Only set by editing
+// settings.json.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct HudLayout {
+    score: HudWidget,
+    best: HudWidget,
+    // Debug marker and the active effect countdowns
+    status: HudWidget,
+    // Difficulty plus the mode notes under it (upside-down, practice, adaptive...)
+    difficulty: HudWidget,
+    timer: HudWidget,
+}
 
-                // Check ground/ceiling collision
-                if !self.invincible && (self.bird.y - BIRD_SIZE / 2.0 <= 0.0 
-                    || self.bird.y + BIRD_SIZE / 2.0 >= screen_height() - GROUND_HEIGHT) {
-                    self.state = GameState::GameOver;
-                    self.spawn_particles(self.bird.x, self.bird.y, RED, 30);
-                    
-                    if self.high_scores.update(self.difficulty, self.score) {
-                        self.high_scores.save();
-                    }
-                }
+impl Default for HudLayout {
+    fn default() -> Self {
+        Self {
+            score: HudWidget::new(true, HudCorner::TopLeft),
+            best: HudWidget::new(true, HudCorner::TopLeft),
+            status: HudWidget::new(true, HudCorner::TopLeft),
+            difficulty: HudWidget::new(true, HudCorner::TopRight),
+            timer: HudWidget::new(false, HudCorner::TopRight),
+        }
+    }
+}
 
-                // Update particles
-                for particle in &mut self.particles {
-                    particle.update();
-                }
-                self.particles.retain(|p| !p.is_dead());
+impl HudLayout {
+    fn widgets(&self) -> [(HudElement, HudWidget); 5] {
+        [
+            (HudElement::Score, self.score),
+            (HudElement::Best, self.best),
+            (HudElement::Status, self.status),
+            (HudElement::Difficulty, self.difficulty),
+            (HudElement::Timer, self.timer),
+        ]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct Keybindings {
+    #[serde(with = "key_name")]
+    jump: KeyCode,
+    #[serde(with = "key_name")]
+    pause: KeyCode,
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Self {
+            jump: KeyCode::Space,
+            pause: KeyCode::Escape,
+        }
+    }
+}
+
+impl Keybindings {
+    const ACTIONS: [&'static str; 2] = ["Jump", "Pause"];
+
+    fn get(&self, action: usize) -> KeyCode {
+        match action {
+            0 => self.jump,
+            _ => self.pause,
+        }
+    }
+
+    // Binds key to action unless something else already uses it, in which case
+    // the bindings stay as they were and the reason comes back
+    fn rebind(&mut self, action: usize, key: KeyCode) -> Result<(), String> {
+        if !BINDABLE_KEYS.contains(&key) {
+            return Err(format!("{} can't be bound", key_label(key)));
+        }
+        if let Some(use_) = reserved_key_use(key) {
+            return Err(format!("{} is already used for {}", key_label(key), use_));
+        }
+        if let Some(other) = (0..Self::ACTIONS.len()).find(|&i| i != action && self.get(i) == key) {
+            return Err(format!(
+                "{} is already bound to {}",
+                key_label(key),
+                Self::ACTIONS[other]
+            ));
+        }
+        match action {
+            0 => self.jump = key,
+            _ => self.pause = key,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct Settings {
+    custom: DifficultyParams,
+    quality: GraphicsQuality,
+    gap_guide: bool,
+    // Skips purely decorative motion such as the slow motion vignette, the
+    // near-miss shake and the bird's squash and stretch
+    reduced_motion: bool,
+    // Pause a run after this many seconds without any input
+    auto_pause: bool,
+    auto_pause_seconds: f32,
+    window_mode: WindowMode,
+    // Size of a windowed window when the game last quit, restored on launch
+    window_width: u32,
+    window_height: u32,
+    // How long the game over screen ignores input; only set by editing
+    // settings.json
+    retry_lockout_seconds: f32,
+    // Banner, chime and gold burst every milestone_interval points
+    milestones: bool,
+    milestone_interval: i32,
+    // Renders at PIXEL_ART_HEIGHT lines and upscales for a chunky retro look
+    pixel_art: bool,
+    theme: ThemeChoice,
+    // 0.0 - 1.0; how much the pause and game over overlays darken the board
+    overlay_darkness: f32,
+    // Smaller bird hitbox for players who find the game too punishing; unranked
+    assist: bool,
+    // Fraction of gravity taken off while the bird falls fast, for players who
+    // need longer to react; anything but 0.0 is unranked
+    fall_assist: f32,
+    // A purely cosmetic second bird trailing the player's; hidden with
+    // reduced motion
+    follower: bool,
+    // Well-timed consecutive flaps build a little extra lift; unranked
+    flap_boost: bool,
+    // Keeps recording the last few seconds for saving as a GIF; opt-in since
+    // grabbing the screen costs memory and frame time. Never on Low graphics.
+    gif_clips: bool,
+    // Multiplies how often pipes spawn; anything but 1.0 makes runs unranked
+    pipe_density: f32,
+    // Physics overrides, unranked unless left at GRAVITY / JUMP_STRENGTH
+    gravity: f32,
+    jump_strength: f32,
+    // Gentler pull at the start of a fall that builds up to a terminal speed;
+    // unranked
+    gravity_curve: bool,
+    // Wider gaps on the first few pipes of a run, except on Extreme and in
+    // challenges (their codes have to reproduce the layout exactly). Still
+    // ranked (see modifies_physics).
+    warm_up: bool,
+    // Whether the jump key and the left mouse button flap, and how strongly
+    // relative to jump_strength; a scale other than 1.0 is unranked
+    keyboard_jump: bool,
+    keyboard_jump_scale: f32,
+    mouse_jump: bool,
+    mouse_jump_scale: f32,
+    // Bird's horizontal position, in pixels from the left edge; unranked away
+    // from BIRD_START_X
+    bird_x: f32,
+    // World height as a multiple of the window height. Above 1.0 the camera
+    // follows the bird vertically, and the run is unranked; only reachable by
+    // editing settings.json for now.
+    world_height_scale: f32,
+    // Whose high scores are loaded and saved; each profile has its own file
+    profile: String,
+    // Set once the first-launch tutorial has been finished. Settings files from
+    // before the tutorial existed count as finished, since those players know the game.
+    #[serde(default = "tutorial_completed_default")]
+    tutorial_completed: bool,
+    // Seconds between periodic score saves; only set by editing settings.json
+    save_interval_seconds: f32,
+    // Kiosk and first-timer mode: during a run nearly any key or mouse button
+    // flaps. Pausing, quitting and the menus keep their usual keys. Only set by
+    // editing settings.json.
+    simple_controls: bool,
+    // Appends every finished run to runs.jsonl; also switched on by setting
+    // FLAPPY_LOG_RUNS=1. Only reachable by editing settings.json.
+    log_runs: bool,
+    hud: HudLayout,
+    keys: Keybindings,
+    // Volume of each difficulty's music track, 0.0 - 1.0; only set by editing
+    // settings.json for now
+    music_volume: PerDifficulty<f32>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            custom: DifficultyParams::default(),
+            quality: GraphicsQuality::default(),
+            gap_guide: false,
+            reduced_motion: false,
+            auto_pause: false,
+            auto_pause_seconds: AUTO_PAUSE_CHOICES[1],
+            window_mode: WindowMode::default(),
+            window_width: DEFAULT_WINDOW_SIZE.0,
+            window_height: DEFAULT_WINDOW_SIZE.1,
+            retry_lockout_seconds: RETRY_LOCKOUT_SECONDS,
+            milestones: true,
+            milestone_interval: MILESTONE_CHOICES[1],
+            pixel_art: false,
+            theme: ThemeChoice::default(),
+            overlay_darkness: 1.0,
+            assist: false,
+            fall_assist: 0.0,
+            gif_clips: false,
+            follower: false,
+            flap_boost: false,
+            pipe_density: 1.0,
+            gravity: GRAVITY,
+            jump_strength: JUMP_STRENGTH,
+            gravity_curve: false,
+            warm_up: false,
+            keyboard_jump: true,
+            keyboard_jump_scale: 1.0,
+            mouse_jump: true,
+            mouse_jump_scale: 1.0,
+            bird_x: BIRD_START_X,
+            world_height_scale: 1.0,
+            profile: DEFAULT_PROFILE.to_string(),
+            tutorial_completed: false,
+            save_interval_seconds: SAVE_INTERVAL_SECONDS,
+            simple_controls: false,
+            log_runs: false,
+            hud: HudLayout::default(),
+            keys: Keybindings::default(),
+            music_volume: PerDifficulty {
+                easy: MUSIC_VOLUME,
+                medium: MUSIC_VOLUME,
+                hard: MUSIC_VOLUME,
+                extreme: MUSIC_VOLUME,
+                custom: MUSIC_VOLUME,
+            },
+        }
+    }
+}
+
+fn tutorial_completed_default() -> bool {
+    true
+}
+
+impl Settings {
+    // The saved window size, or the default if it's too small to play in
+    fn window_size(&self) -> (i32, i32) {
+        let too_small =
+            self.window_width < MIN_WINDOW_SIZE.0 || self.window_height < MIN_WINDOW_SIZE.1;
+        let size = if too_small {
+            DEFAULT_WINDOW_SIZE
+        } else {
+            (self.window_width, self.window_height)
+        };
+        (
+            size.0.try_into().unwrap_or(i32::MAX),
+            size.1.try_into().unwrap_or(i32::MAX),
+        )
+    }
+
+    const FIELDS: [&'static str; 24] = [
+        "Graphics",
+        "Gap Guide",
+        "Reduced Motion",
+        "Pipe Density",
+        "Gravity",
+        "Jump Strength",
+        "Gravity Curve",
+        "Warm-Up",
+        "Keyboard Jump",
+        "Mouse Jump",
+        "Auto-Pause",
+        "Window",
+        "Milestones",
+        "Pixel Art",
+        "Theme",
+        "Overlay Darkness",
+        "Assist",
+        "Fall Assist",
+        "GIF Clips",
+        "Follower Bird",
+        "Flap Boost",
+        "Jump Key",
+        "Pause Key",
+        "Reset Keys",
+    ];
+    // Rows from here on are the key bindings, in Keybindings::ACTIONS order,
+    // followed by the reset row
+    const FIRST_KEY_FIELD: usize = 21;
+    const RESET_KEYS_FIELD: usize = 23;
+    // Locked until FOLLOWER_UNLOCK_SCORE, which only Game knows about
+    const FOLLOWER_FIELD: usize = 19;
+
+    fn field_text(&self, field: usize) -> String {
+        let (value, default) = match field {
+            0 => return self.quality.name().to_string(),
+            1 => return if self.gap_guide { "On" } else { "Off" }.to_string(),
+            2 => return if self.reduced_motion { "On" } else { "Off" }.to_string(),
+            3 => (
+                format!("{:.2}x", self.pipe_density),
+                self.pipe_density == 1.0,
+            ),
+            4 => (
+                format!("{:.2}", self.gravity),
+                approx_eq(self.gravity, GRAVITY),
+            ),
+            // Shown as a positive number since bigger means a stronger flap
+            5 => (
+                format!("{:.1}", -self.jump_strength),
+                approx_eq(self.jump_strength, JUMP_STRENGTH),
+            ),
+            6 => (
+                if self.gravity_curve { "On" } else { "Off" }.to_string(),
+                !self.gravity_curve,
+            ),
+            7 if self.warm_up => return "On (first 3 pipes wider, not on Extreme)".to_string(),
+            7 => return "Off".to_string(),
+            8 if !self.keyboard_jump => return "Off".to_string(),
+            8 => (
+                format!("{:.1}x", self.keyboard_jump_scale),
+                approx_eq(self.keyboard_jump_scale, 1.0),
+            ),
+            9 if !self.mouse_jump => return "Off".to_string(),
+            9 => (
+                format!("{:.1}x", self.mouse_jump_scale),
+                approx_eq(self.mouse_jump_scale, 1.0),
+            ),
+            10 if self.auto_pause => return format!("after {}s idle", self.auto_pause_seconds),
+            10 => return "Off".to_string(),
+            11 => return format!("{} (on restart)", self.window_mode.name()),
+            12 if self.milestones => return format!("every {} points", self.milestone_interval),
+            12 => return "Off".to_string(),
+            13 => return if self.pixel_art { "On" } else { "Off" }.to_string(),
+            14 => return self.theme.name().to_string(),
+            15 => return format!("{:.0}%", self.overlay_darkness * 100.0),
+            16 if self.assist => return "On - scores won't be saved to leaderboard".to_string(),
+            16 => return "Off".to_string(),
+            17 if self.fall_assist > 0.0 => (
+                format!("{:.0}% lighter fast falls", self.fall_assist * 100.0),
+                false,
+            ),
+            17 => return "Off".to_string(),
+            18 if !self.gif_clips => return "Off".to_string(),
+            18 if self.quality == GraphicsQuality::Low => {
+                return "On (needs Medium or High graphics)".to_string()
             }
-            GameState::Paused => {
-                if is_key_pressed(KeyCode::Escape) || is_key_pressed(KeyCode::Space) {
-                    self.state = GameState::Playing;
+            18 => return format!("On - F9 saves the last {}s", GIF_CLIP_SECONDS),
+            19 => return if self.follower { "On" } else { "Off" }.to_string(),
+            20 => (
+                if self.flap_boost { "On" } else { "Off" }.to_string(),
+                !self.flap_boost,
+            ),
+            Self::RESET_KEYS_FIELD => return "press ENTER".to_string(),
+            key => return key_label(self.keys.get(key - Self::FIRST_KEY_FIELD)),
+        };
+        if default {
+            value
+        } else {
+            format!("{} (unranked)", value)
+        }
+    }
+
+    fn adjust(&mut self, field: usize, steps: f32) {
+        match field {
+            0 => {
+                self.quality = if steps > 0.0 {
+                    self.quality.next()
+                } else {
+                    self.quality.prev()
+                };
+            }
+            1 => self.gap_guide = !self.gap_guide,
+            2 => self.reduced_motion = !self.reduced_motion,
+            3 => {
+                let (min, max, step) = PIPE_DENSITY_RANGE;
+                self.pipe_density = (self.pipe_density + steps * step).clamp(min, max);
+            }
+            4 => {
+                let (min, max, step) = GRAVITY_RANGE;
+                self.gravity = (self.gravity + steps * step).clamp(min, max);
+            }
+            5 => {
+                let (min, max, step) = JUMP_STRENGTH_RANGE;
+                self.jump_strength = (self.jump_strength - steps * step).clamp(-max, -min);
+            }
+            6 => self.gravity_curve = !self.gravity_curve,
+            7 => self.warm_up = !self.warm_up,
+            // Off, then each strength in turn. A source can only be switched
+            // off while the other one still flaps.
+            8 => {
+                let current = jump_scale_choice(self.keyboard_jump, self.keyboard_jump_scale);
+                let next = cycle_jump_scale(current, steps, self.mouse_jump);
+                self.keyboard_jump = next.is_some();
+                if let Some(i) = next {
+                    self.keyboard_jump_scale = JUMP_SCALE_CHOICES[i];
                 }
-                if is_key_pressed(KeyCode::Q) {
-                    self.state = GameState::Menu;
+            }
+            9 => {
+                let current = jump_scale_choice(self.mouse_jump, self.mouse_jump_scale);
+                let next = cycle_jump_scale(current, steps, self.keyboard_jump);
+                self.mouse_jump = next.is_some();
+                if let Some(i) = next {
+                    self.mouse_jump_scale = JUMP_SCALE_CHOICES[i];
                 }
             }
-            GameState::GameOver => {
-                if is_key_pressed(KeyCode::Space) || is_key_pressed(KeyCode::Enter) {
-                    self.reset();
-                    self.state = GameState::Playing;
+            // Off, then each timeout in turn
+            10 => {
+                let current = AUTO_PAUSE_CHOICES
+                    .iter()
+                    .position(|&secs| approx_eq(secs, self.auto_pause_seconds))
+                    .filter(|_| self.auto_pause);
+                let next = cycle_choice(current, AUTO_PAUSE_CHOICES.len(), steps);
+                self.auto_pause = next.is_some();
+                if let Some(i) = next {
+                    self.auto_pause_seconds = AUTO_PAUSE_CHOICES[i];
                 }
-                if is_key_pressed(KeyCode::Escape) || is_key_pressed(KeyCode::Q) {
-                    self.state = GameState::Menu;
+            }
+            11 => self.window_mode = self.window_mode.toggled(),
+            // Off, then each interval in turn
+            12 => {
+                let current = MILESTONE_CHOICES
+                    .iter()
+                    .position(|&points| points == self.milestone_interval)
+                    .filter(|_| self.milestones);
+                let next = cycle_choice(current, MILESTONE_CHOICES.len(), steps);
+                self.milestones = next.is_some();
+                if let Some(i) = next {
+                    self.milestone_interval = MILESTONE_CHOICES[i];
                 }
             }
+            13 => self.pixel_art = !self.pixel_art,
+            14 => self.theme = self.theme.step(steps),
+            15 => {
+                let (min, max, step) = OVERLAY_DARKNESS_RANGE;
+                self.overlay_darkness = (self.overlay_darkness + steps * step).clamp(min, max);
+            }
+            16 => self.assist = !self.assist,
+            // Off, then each strength in turn
+            17 => {
+                let current = FALL_ASSIST_CHOICES
+                    .iter()
+                    .position(|&choice| approx_eq(choice, self.fall_assist));
+                let next = cycle_choice(current, FALL_ASSIST_CHOICES.len(), steps);
+                self.fall_assist = next.map_or(0.0, |i| FALL_ASSIST_CHOICES[i]);
+            }
+            18 => self.gif_clips = !self.gif_clips,
+            19 => self.follower = !self.follower,
+            20 => self.flap_boost = !self.flap_boost,
+            // Key bindings are changed with ENTER instead
+            _ => {}
         }
     }
 
-    fn draw(&self) {
-        // Draw animated background
-        for i in 0..20 {
-            let offset = (self.background_offset + i as f32 * 50.0) % screen_width();
-            draw_rectangle(
-                offset,
-                0.0,
-                50.0,
-                screen_height() - GROUND_HEIGHT,
-                Color::from_rgba(135 + (i % 3) as u8 * 10, 206, 235, 255),
+    // Only settings that change the pipe layout, the physics or the hitbox
+    // affect ranking; visual aids like the gap guide don't
+    fn is_ranked(&self) -> bool {
+        !self.is_assisted() && !self.modifies_physics()
+    }
+
+    fn is_assisted(&self) -> bool {
+        self.assist || self.fall_assist != 0.0
+    }
+
+    fn modifies_physics(&self) -> bool {
+        self.pipe_density != 1.0
+            || !approx_eq(self.gravity, GRAVITY)
+            || !approx_eq(self.jump_strength, JUMP_STRENGTH)
+            || self.gravity_curve
+            || self.flap_boost
+            || !approx_eq(self.keyboard_jump_scale, 1.0)
+            || !approx_eq(self.mouse_jump_scale, 1.0)
+            // More room before the first pipe, or a taller world, changes
+            // what the run asks of the bird just as much
+            || !approx_eq(self.bird_x, BIRD_START_X)
+            || !approx_eq(self.world_height_scale.max(1.0), 1.0)
+        // warm_up is left out on purpose: it only widens the first three gaps,
+        // never on Extreme, so newcomers can ease in and still make the board
+    }
+
+    // The strength multiplier for a jump from this input, or None if that
+    // input doesn't flap. A settings.json with both switched off still lets
+    // the keyboard flap.
+    fn jump_scale(&self, mouse: bool) -> Option<f32> {
+        if mouse {
+            self.mouse_jump.then_some(self.mouse_jump_scale)
+        } else {
+            (self.keyboard_jump || !self.mouse_jump).then_some(self.keyboard_jump_scale)
+        }
+    }
+
+    fn load() -> io::Result<Self> {
+        parse_save(read_save_file("settings.json")?)
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let data = serde_json::to_string_pretty(self)?;
+        write_save_file("settings.json", &data)
+    }
+}
+
+// Steps through "off" (None) followed by count choices, wrapping at both ends
+fn cycle_choice(current: Option<usize>, count: usize, steps: f32) -> Option<usize> {
+    let current = current.map_or(0, |i| i + 1);
+    let next = if steps > 0.0 {
+        (current + 1) % (count + 1)
+    } else {
+        (current + count) % (count + 1)
+    };
+    next.checked_sub(1)
+}
+
+fn jump_scale_choice(enabled: bool, scale: f32) -> Option<usize> {
+    JUMP_SCALE_CHOICES
+        .iter()
+        .position(|&choice| approx_eq(choice, scale))
+        .filter(|_| enabled)
+}
+
+// Like cycle_choice over JUMP_SCALE_CHOICES, but skips "off" unless
+// off_allowed, so at least one input can always flap
+fn cycle_jump_scale(current: Option<usize>, steps: f32, off_allowed: bool) -> Option<usize> {
+    let next = cycle_choice(current, JUMP_SCALE_CHOICES.len(), steps);
+    if next.is_some() || off_allowed {
+        next
+    } else {
+        cycle_choice(None, JUMP_SCALE_CHOICES.len(), steps)
+    }
+}
+
+// Files from assets/ baked in by build.rs; empty unless built with the
+// embedded-assets feature
+include!(concat!(env!("OUT_DIR"), "/embedded_assets.rs"));
+
+// An asset from assets/ next to the executable, which always wins so files can
+// be swapped in during development, or else one baked into the binary
+fn asset_bytes(name: &str) -> Option<Vec<u8>> {
+    fs::read(save_path(&format!("assets/{}", name)))
+        .ok()
+        .or_else(|| {
+            EMBEDDED_ASSETS
+                .iter()
+                .find(|(asset, _)| *asset == name)
+                .map(|(_, bytes)| bytes.to_vec())
+        })
+}
+
+// Loads a sound from assets/<name> (external or embedded), falling back to the
+// synthesized version if there's no such asset or it can't be decoded
+async fn load_clip(name: &str, synthesize: impl FnOnce() -> Vec<u8>) -> Option<Sound> {
+    if let Some(bytes) = asset_bytes(name) {
+        match load_sound_from_bytes(&bytes).await {
+            Ok(sound) => return Some(sound),
+            Err(err) => eprintln!(
+                "Couldn't load assets/{}, using the built-in sound: {}",
+                name, err
+            ),
+        }
+    }
+    load_sound_from_bytes(&synthesize()).await.ok()
+}
+
+// Whether there's anything to play sound on. Launching with --no-audio or
+// FLAPPY_NO_AUDIO=1 turns sound off up front; otherwise a tiny test clip is
+// loaded, and if even that fails (no audio device, headless machines) the game
+// warns once and carries on silent.
+async fn audio_available() -> bool {
+    let disabled = std::env::args().any(|arg| arg == "--no-audio")
+        || std::env::var("FLAPPY_NO_AUDIO").is_ok_and(|value| value == "1");
+    if disabled {
+        return false;
+    }
+    match load_sound_from_bytes(&melody_wav(&[(0.0, 0.01)])).await {
+        Ok(_) => true,
+        Err(err) => {
+            eprintln!("Audio isn't available, playing without sound: {}", err);
+            false
+        }
+    }
+}
+
+// Shown while the sounds load at startup: the menu bird over a progress bar.
+// Each finished clip calls advance, which presents a frame at most every
+// LOADING_FRAME_SECONDS so fast machines aren't slowed down waiting on vsync.
+struct LoadingScreen {
+    loaded: usize,
+    total: usize,
+    last_frame: f64,
+}
+
+impl LoadingScreen {
+    // Every combo pitch, the six other effects and the four music tracks
+    const CLIPS: usize = COMBO_PITCH_STEPS as usize + 1 + 6 + 4;
+
+    fn new(audio_enabled: bool) -> Self {
+        let total = if audio_enabled { Self::CLIPS } else { 0 };
+        Self {
+            loaded: 0,
+            total,
+            last_frame: f64::NEG_INFINITY,
+        }
+    }
+
+    async fn advance(&mut self) {
+        self.loaded = (self.loaded + 1).min(self.total);
+        self.present().await;
+    }
+
+    // Draws the screen if enough time has passed since the last one
+    async fn present(&mut self) {
+        if get_time() - self.last_frame < LOADING_FRAME_SECONDS {
+            return;
+        }
+        self.last_frame = get_time();
+        clear_background(SKYBLUE);
+        draw_menu_bird(
+            screen_height() / 2.0 - 40.0,
+            GraphicsQuality::default().circle_sides(),
+        );
+
+        let progress = if self.total == 0 {
+            1.0
+        } else {
+            self.loaded as f32 / self.total as f32
+        };
+        let bar_width = 240.0;
+        let bar_x = screen_width() / 2.0 - bar_width / 2.0;
+        let bar_y = screen_height() / 2.0 + 20.0;
+        draw_rectangle(bar_x, bar_y, bar_width, 14.0, Color { a: 0.4, ..BLACK });
+        draw_rectangle(bar_x, bar_y, bar_width * progress, 14.0, YELLOW);
+        draw_rectangle_lines(bar_x, bar_y, bar_width, 14.0, 2.0, WHITE);
+        draw_centered_text("Loading sounds...", bar_y + 40.0, 22.0, WHITE);
+        next_frame().await
+    }
+}
+
+// Sound effects are synthesized at startup so the game doesn't need any audio
+// files next to the executable, though each clip can be replaced by a file in
+// assets/ (see load_clip). Every effect goes through `AudioManager::play`,
+// which is also where volume or mute handling belongs.
+struct AudioManager {
+    // False when audio_available said no; nothing is loaded or played then
+    audio_enabled: bool,
+    // macroquad can't change playback rate, so every combo pitch is its own clip.
+    // Clips that fail to load are skipped and the game just plays fewer (or no)
+    // sounds instead of crashing.
+    point: Vec<Sound>,
+    fanfare: Option<Sound>,
+    milestone: Option<Sound>,
+    denied: Option<Sound>,
+    applause: Option<Sound>,
+    flap: Option<Sound>,
+    heartbeat: Option<Sound>,
+    // When the flap and denied clips last started, so mashing jump doesn't
+    // stack dozens of copies on top of each other
+    last_flap: f64,
+    last_denied: f64,
+}
+
+impl AudioManager {
+    // Nothing loaded, so nothing ever plays
+    fn silent() -> Self {
+        Self {
+            audio_enabled: false,
+            point: Vec::new(),
+            fanfare: None,
+            milestone: None,
+            denied: None,
+            applause: None,
+            flap: None,
+            heartbeat: None,
+            last_flap: f64::NEG_INFINITY,
+            last_denied: f64::NEG_INFINITY,
+        }
+    }
+
+    async fn load(audio_enabled: bool, loading: &mut LoadingScreen) -> Self {
+        if !audio_enabled {
+            return Self::silent();
+        }
+
+        let mut point = Vec::new();
+        for step in 0..=COMBO_PITCH_STEPS {
+            let frequency = POINT_BASE_FREQUENCY * 2f32.powf(step as f32 / 12.0);
+            let name = format!("point_{}.wav", step);
+            if let Some(sound) = load_clip(&name, || melody_wav(&[(frequency, 0.12)])).await {
+                point.push(sound);
+            }
+            loading.advance().await;
+        }
+
+        // Rising C major arpeggio for new high scores
+        let fanfare_notes = [
+            (523.25, 0.12),
+            (659.25, 0.12),
+            (783.99, 0.12),
+            (1046.5, 0.45),
+        ];
+        let fanfare = load_clip("fanfare.wav", || melody_wav(&fanfare_notes)).await;
+        loading.advance().await;
+
+        // Quick G-C leap for score milestones, short enough not to drown the point chime
+        let milestone_notes = [(783.99, 0.1), (1046.5, 0.25)];
+        let milestone = load_clip("milestone.wav", || melody_wav(&milestone_notes)).await;
+        loading.advance().await;
+
+        // Low buzz for a flap refused on an empty fuel tank
+        let denied = load_clip("denied.wav", || melody_wav(&[(140.0, 0.12)])).await;
+        loading.advance().await;
+
+        let applause = load_clip("applause.wav", applause_wav).await;
+        loading.advance().await;
+
+        // Short soft blip for every flap
+        let flap = load_clip("flap.wav", || melody_wav(&[(392.0, 0.05)])).await;
+        loading.advance().await;
+
+        // Low double thump for a near miss
+        let heartbeat_notes = [(90.0, 0.08), (0.0, 0.06), (75.0, 0.12)];
+        let heartbeat = load_clip("heartbeat.wav", || melody_wav(&heartbeat_notes)).await;
+        loading.advance().await;
+
+        Self {
+            audio_enabled,
+            point,
+            fanfare,
+            milestone,
+            denied,
+            applause,
+            flap,
+            heartbeat,
+            last_flap: f64::NEG_INFINITY,
+            last_denied: f64::NEG_INFINITY,
+        }
+    }
+
+    fn play(&self, sound: Option<&Sound>, volume: f32) {
+        if !self.audio_enabled {
+            return;
+        }
+        if let Some(sound) = sound {
+            play_sound(
+                sound,
+                PlaySoundParams {
+                    looped: false,
+                    volume,
+                },
             );
         }
+    }
+
+    fn play_point(&self, combo: u32) {
+        let step = (combo.min(COMBO_PITCH_STEPS) as usize).min(self.point.len().saturating_sub(1));
+        self.play(self.point.get(step), 0.5);
+    }
+
+    fn play_fanfare(&self) {
+        self.play(self.fanfare.as_ref(), 0.6);
+    }
+
+    fn play_milestone(&self) {
+        self.play(self.milestone.as_ref(), 0.5);
+    }
+
+    fn play_applause(&self) {
+        self.play(self.applause.as_ref(), 0.6);
+    }
+
+    fn play_heartbeat(&self) {
+        self.play(self.heartbeat.as_ref(), 0.5);
+    }
+
+    // Skipped entirely if the last flap started under FLAP_SOUND_MIN_GAP ago;
+    // otherwise the previous flap is cut off so at most one ever plays
+    fn play_flap(&mut self, now: f64) {
+        if now - self.last_flap < FLAP_SOUND_MIN_GAP {
+            return;
+        }
+        self.last_flap = now;
+        if let Some(sound) = &self.flap {
+            stop_sound(sound);
+        }
+        self.play(self.flap.as_ref(), 0.35);
+    }
+
+    fn play_denied(&mut self, now: f64) {
+        if now - self.last_denied < FLAP_SOUND_MIN_GAP {
+            return;
+        }
+        self.last_denied = now;
+        if let Some(sound) = &self.denied {
+            stop_sound(sound);
+        }
+        self.play(self.denied.as_ref(), 0.5);
+    }
+
+    fn stop_fanfare(&self) {
+        if let Some(sound) = &self.fanfare {
+            stop_sound(sound);
+        }
+    }
+}
+
+// Looping background music, one track per difficulty where it has its own and a
+// default track everywhere else. Switching tracks crossfades over a second or so.
+struct Music {
+    tracks: Vec<MusicTrack>,
+    default_track: Option<usize>,
+    difficulty_tracks: PerDifficulty<Option<usize>>,
+    current: Option<usize>,
+    // Multiplies every track's volume; eases towards duck_target. The music is
+    // the only gameplay ambience, so it's all that ducks; sound effects from
+    // AudioManager are one-shots and play at full volume on any screen.
+    duck: f32,
+    duck_target: f32,
+}
+
+struct MusicTrack {
+    sound: Sound,
+    volume: f32,
+    target: f32,
+    playing: bool,
+}
+
+impl Music {
+    // No tracks at all, as when audio is off
+    fn silent() -> Self {
+        Self {
+            tracks: Vec::new(),
+            default_track: None,
+            difficulty_tracks: PerDifficulty::default(),
+            current: None,
+            duck: 1.0,
+            duck_target: 1.0,
+        }
+    }
+
+    // Without audio there are no tracks, so nothing ever plays
+    async fn load(audio_enabled: bool, loading: &mut LoadingScreen) -> Self {
+        // (frequency, seconds) loops: the default is a bright major arpeggio,
+        // Easy slows down to a pentatonic stroll and Hard / Extreme turn minor
+        // and speed up
+        const C4: f32 = 261.63;
+        const D4: f32 = 293.66;
+        const E4: f32 = 329.63;
+        const F4: f32 = 349.23;
+        const G4: f32 = 392.0;
+        const A4: f32 = 440.0;
+        const E3: f32 = 164.81;
+        const G3: f32 = 196.0;
+        const A3: f32 = 220.0;
+        const B3: f32 = 246.94;
+        const B4: f32 = 493.88;
+        let default = [C4, E4, G4, E4, F4, A4, G4, E4].map(|f| (f, 0.3));
+        let easy = [C4, D4, E4, G4, A4, G4, E4, D4].map(|f| (f, 0.4));
+        let hard = [A3, C4, E4, A4, G4, E4, C4, E4].map(|f| (f, 0.22));
+        let extreme = [E3, E4, G3, G4, B3, B4, D4, B3].map(|f| (f, 0.15));
+
+        let mut music = Self::silent();
+        if !audio_enabled {
+            return music;
+        }
+        music.default_track = music.add_track("music.wav", &default, loading).await;
+        music.difficulty_tracks.easy = music.add_track("music_easy.wav", &easy, loading).await;
+        music.difficulty_tracks.hard = music.add_track("music_hard.wav", &hard, loading).await;
+        music.difficulty_tracks.extreme = music
+            .add_track("music_extreme.wav", &extreme, loading)
+            .await;
+        music
+    }
+
+    // Tracks that fail to load are left out; their difficulty uses the default
+    async fn add_track(
+        &mut self,
+        name: &str,
+        notes: &[(f32, f32)],
+        loading: &mut LoadingScreen,
+    ) -> Option<usize> {
+        let sound = load_clip(name, || melody_wav(notes)).await;
+        loading.advance().await;
+        let sound = sound?;
+        self.tracks.push(MusicTrack {
+            sound,
+            volume: 0.0,
+            target: 0.0,
+            playing: false,
+        });
+        Some(self.tracks.len() - 1)
+    }
+
+    // Fades over to the difficulty's track (or the default) at the given volume
+    fn play_for(&mut self, difficulty: Difficulty, volume: f32) {
+        let next = self
+            .difficulty_tracks
+            .get(difficulty)
+            .or(self.default_track);
+        if next != self.current {
+            if let Some(track) = self.current.and_then(|i| self.tracks.get_mut(i)) {
+                track.target = 0.0;
+            }
+            self.current = next;
+        }
+        if let Some(track) = next.and_then(|i| self.tracks.get_mut(i)) {
+            track.target = volume.clamp(0.0, 1.0);
+            if !track.playing {
+                let volume = track.volume * self.duck;
+                play_sound(
+                    &track.sound,
+                    PlaySoundParams {
+                        looped: true,
+                        volume,
+                    },
+                );
+                track.playing = true;
+            }
+        }
+    }
+
+    // Lowers the music to MUSIC_DUCK_VOLUME, or brings it back up, over a
+    // few frames
+    fn set_ducked(&mut self, ducked: bool) {
+        self.duck_target = if ducked { MUSIC_DUCK_VOLUME } else { 1.0 };
+    }
+
+    // Cuts every track off at once, before the tracks are replaced
+    fn stop(&mut self) {
+        for track in &mut self.tracks {
+            if track.playing {
+                stop_sound(&track.sound);
+                track.playing = false;
+            }
+        }
+        self.current = None;
+    }
+
+    // Steps every crossfade and the ducking along; called once per frame
+    fn update(&mut self) {
+        let duck_step = (self.duck_target - self.duck).clamp(-MUSIC_DUCK_STEP, MUSIC_DUCK_STEP);
+        self.duck += duck_step;
+        for track in &mut self.tracks {
+            if !track.playing {
+                continue;
+            }
+            let fading = track.volume != track.target;
+            if fading {
+                let step = (track.target - track.volume).clamp(-MUSIC_FADE_STEP, MUSIC_FADE_STEP);
+                track.volume += step;
+            }
+            if fading || duck_step != 0.0 {
+                set_sound_volume(&track.sound, track.volume * self.duck);
+            }
+            if track.volume <= 0.0 && track.target <= 0.0 {
+                stop_sound(&track.sound);
+                track.playing = false;
+            }
+        }
+    }
+}
+
+const SAMPLE_RATE: u32 = 44_100;
+
+// Builds a mono 16-bit WAV from (frequency, seconds) notes played back to back,
+// each a sine tone that fades out over its duration
+fn melody_wav(notes: &[(f32, f32)]) -> Vec<u8> {
+    let mut samples = Vec::new();
+    for &(frequency, duration) in notes {
+        let count = (SAMPLE_RATE as f32 * duration) as u32;
+        for i in 0..count {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            let envelope = 1.0 - i as f32 / count as f32;
+            let sample = (t * frequency * std::f32::consts::TAU).sin() * envelope * 0.6;
+            samples.push((sample * i16::MAX as f32) as i16);
+        }
+    }
+    wav_bytes(&samples)
+}
+
+// A couple of seconds of scattered claps (short bursts of decaying noise) that
+// thicken and then thin out, like a crowd starting and finishing a round of applause
+fn applause_wav() -> Vec<u8> {
+    let count = (SAMPLE_RATE as f32 * 2.0) as usize;
+    let clap_len = (SAMPLE_RATE as f32 * 0.015) as usize;
+    let mut mix = vec![0.0f32; count];
+    let mut rng = StdRng::seed_from_u64(0xC1A9);
+    for _ in 0..400 {
+        // Bunched towards the middle by averaging two uniform picks
+        let start = (rng.gen_range(0..count) + rng.gen_range(0..count)) / 2;
+        for i in 0..clap_len.min(count - start) {
+            let envelope = 1.0 - i as f32 / clap_len as f32;
+            mix[start + i] += rng.gen_range(-1.0..1.0) * envelope * 0.25;
+        }
+    }
+    let samples: Vec<i16> = mix
+        .iter()
+        .map(|sample| (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+        .collect();
+    wav_bytes(&samples)
+}
+
+// Wraps 16-bit mono samples at SAMPLE_RATE in a WAV header
+fn wav_bytes(samples: &[i16]) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes()); // fmt chunk size
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes()); // byte rate
+    wav.extend_from_slice(&2u16.to_le_bytes()); // block align
+    wav.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+// Every GIF clip frame uses the same 256 colors: 6 levels of red, 7 of green
+// and 6 of blue (green gets the extra since eyes are most sensitive to it),
+// padded with black
+fn clip_palette() -> Vec<u8> {
+    let mut palette = Vec::with_capacity(256 * 3);
+    for r in 0..6u32 {
+        for g in 0..7u32 {
+            for b in 0..6u32 {
+                palette.extend([r * 255 / 5, g * 255 / 6, b * 255 / 5].map(|c| c as u8));
+            }
+        }
+    }
+    palette.resize(256 * 3, 0);
+    palette
+}
+
+fn clip_palette_index(r: u8, g: u8, b: u8) -> u8 {
+    let level = |c: u8, levels: u32| (c as u32 * (levels - 1) + 127) / 255;
+    (level(r, 6) * 42 + level(g, 7) * 6 + level(b, 6)) as u8
+}
+
+// Builds a looping GIF from frames of clip_palette() indices, each
+// width x height and shown for delay hundredths of a second
+fn gif_bytes(width: u16, height: u16, frames: &[Vec<u8>], delay: u16) -> io::Result<Vec<u8>> {
+    let encode = || -> Result<Vec<u8>, gif::EncodingError> {
+        let palette = clip_palette();
+        let mut encoder = gif::Encoder::new(Vec::new(), width, height, &palette)?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        for indices in frames {
+            let frame = gif::Frame {
+                width,
+                height,
+                delay,
+                buffer: std::borrow::Cow::Borrowed(indices),
+                ..gif::Frame::default()
+            };
+            encoder.write_frame(&frame)?;
+        }
+        Ok(encoder.into_inner()?)
+    };
+    encode().map_err(io::Error::other)
+}
+
+// The last GIF_CLIP_SECONDS of the screen, downscaled to GIF_WIDTH and mapped
+// onto clip_palette() as it's grabbed so the buffer stays small
+struct ClipRecorder {
+    frames: VecDeque<Vec<u8>>,
+    width: usize,
+    height: usize,
+    // Seconds since the last frame was grabbed
+    since_capture: f32,
+}
+
+impl ClipRecorder {
+    fn new() -> Self {
+        Self {
+            frames: VecDeque::new(),
+            width: 0,
+            height: 0,
+            since_capture: 0.0,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.frames.clear();
+        self.since_capture = GIF_FRAME_SECONDS;
+    }
+
+    // Grabs the screen once a frame is due, dropping the oldest frame past the
+    // clip length
+    fn capture(&mut self, dt: f32) {
+        self.since_capture += dt;
+        if self.since_capture < GIF_FRAME_SECONDS {
+            return;
+        }
+        self.since_capture = 0.0;
+
+        let screen = get_screen_data();
+        let (src_width, src_height) = (screen.width as usize, screen.height as usize);
+        if src_width == 0 || src_height == 0 {
+            return;
+        }
+        let width = GIF_WIDTH.min(src_width);
+        let height = (width * src_height / src_width).max(1);
+        if (width, height) != (self.width, self.height) {
+            self.frames.clear();
+            self.width = width;
+            self.height = height;
+        }
+        // The screen comes back bottom row first
+        let mut frame = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let src_y = src_height - 1 - y * src_height / height;
+            for x in 0..width {
+                let i = (src_y * src_width + x * src_width / width) * 4;
+                let pixel = &screen.bytes[i..i + 3];
+                frame.push(clip_palette_index(pixel[0], pixel[1], pixel[2]));
+            }
+        }
+        let max_frames = (GIF_CLIP_SECONDS / GIF_FRAME_SECONDS).ceil() as usize;
+        if self.frames.len() >= max_frames {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+    }
+
+    // None with nothing recorded yet
+    fn encode(&self) -> Option<io::Result<Vec<u8>>> {
+        if self.frames.is_empty() {
+            return None;
+        }
+        let frames: Vec<Vec<u8>> = self.frames.iter().cloned().collect();
+        // At most GIF_WIDTH across; only an absurdly tall window could make
+        // frames taller than a GIF allows
+        let (width, height) = (self.width as u16, self.height.min(u16::MAX as usize) as u16);
+        Some(gif_bytes(width, height, &frames, GIF_FRAME_DELAY))
+    }
+}
+
+// Runs finished since the game was launched, across every difficulty and
+// mode. Only kept in memory, so each session starts from zero.
+#[derive(Default)]
+struct SessionStats {
+    runs: u32,
+    total_score: i64,
+    best: i32,
+}
+
+impl SessionStats {
+    fn record(&mut self, score: i32) {
+        self.runs += 1;
+        self.total_score += score as i64;
+        self.best = self.best.max(score);
+    }
+
+    fn average(&self) -> f32 {
+        if self.runs == 0 {
+            0.0
+        } else {
+            self.total_score as f32 / self.runs as f32
+        }
+    }
+}
+
+// How a particle is drawn; each kind of burst picks its own
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+enum ParticleShape {
+    #[default]
+    Circle,
+    // Debris
+    Square,
+    Star,
+    // A small leaf shape pointing the way it flies
+    Feather,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Particle {
+    x: f32,
+    y: f32,
+    vx: f32,
+    vy: f32,
+    life: f32,
+    color: Color,
+    size: f32,
+    // Downward acceleration and life lost per 60fps frame
+    gravity: f32,
+    fade: f32,
+    // Drawn with additive blending where available, so overlapping sparks
+    // brighten each other
+    glow: bool,
+    shape: ParticleShape,
+}
+
+impl Particle {
+    // frames is how many 60fps frames this update covers, so particles move
+    // and fade at the same speed whatever the frame rate
+    fn update(&mut self, frames: f32) {
+        self.x += self.vx * frames;
+        self.y += self.vy * frames;
+        self.vy += self.gravity * frames;
+        self.life -= self.fade * frames;
+    }
+
+    fn draw(&self, sides: u8) {
+        let alpha = (self.life * 255.0) as u8;
+        let color = Color::new(
+            self.color.r,
+            self.color.g,
+            self.color.b,
+            alpha as f32 / 255.0,
+        );
+        match self.shape {
+            ParticleShape::Circle => draw_round(self.x, self.y, self.size, sides, color),
+            ParticleShape::Square => {
+                let side = self.size * 1.6;
+                draw_rectangle(self.x - side / 2.0, self.y - side / 2.0, side, side, color);
+            }
+            ParticleShape::Star => draw_star(self.x, self.y, self.size * 1.5, color),
+            ParticleShape::Feather => {
+                let direction = vec2(self.vx, self.vy).normalize_or(vec2(1.0, 0.0));
+                let along = direction * self.size * 1.5;
+                let across = vec2(-direction.y, direction.x) * self.size * 0.5;
+                let center = vec2(self.x, self.y);
+                draw_triangle(center + along, center + across, center - across, color);
+                draw_triangle(center - along, center + across, center - across, color);
+            }
+        }
+    }
+
+    fn is_dead(&self) -> bool {
+        self.life <= 0.0
+    }
+}
+
+// Particles launch on their own generator seeded from the run seed, so two
+// runs on the same seed with the same inputs burst exactly alike
+fn particle_rng(run_seed: u32) -> StdRng {
+    StdRng::seed_from_u64(run_seed as u64 ^ 0x9A27_1C1E)
+}
+
+// Adds a burst of count particles flying out from origin, launch speeds
+// multiplied by spread, with launches rolled on rng. The oldest particles are
+// nearly faded anyway, so past MAX_PARTICLES they make way for the new ones.
+fn launch_particles(
+    particles: &mut Vec<Particle>,
+    rng: &mut StdRng,
+    origin: Vec2,
+    color: Color,
+    count: usize,
+    spread: f32,
+    shape: ParticleShape,
+) {
+    for _ in 0..count {
+        particles.push(Particle {
+            x: origin.x,
+            y: origin.y,
+            vx: rng.gen_range(-3.0..3.0) * spread,
+            vy: rng.gen_range(-5.0..-1.0) * spread,
+            life: 1.0,
+            color,
+            size: rng.gen_range(2.0..6.0),
+            gravity: PARTICLE_GRAVITY,
+            fade: PARTICLE_FADE,
+            glow: false,
+            shape,
+        });
+    }
+    if particles.len() > MAX_PARTICLES {
+        let excess = particles.len() - MAX_PARTICLES;
+        particles.drain(..excess);
+    }
+}
+
+// Moves every particle on by frames 60fps frames and drops the faded ones
+fn update_particles(particles: &mut Vec<Particle>, frames: f32) {
+    for particle in particles.iter_mut() {
+        particle.update(frames);
+    }
+    particles.retain(|p| !p.is_dead());
+}
+
+struct Game {
+    // The current (or last) run; drawing and input sit on top of it
+    sim: Simulation,
+    particles: Vec<Particle>,
+    // Seeded from the run seed, so a replayed or shared seed also gets the
+    // same particle bursts
+    particle_rng: StdRng,
+    high_scores: HighScores,
+    settings: Settings,
+    state: GameState,
+    difficulty: Difficulty,
+    background_offset: f32,
+    // How far the cloud layer has scrolled, and how fast it moves relative to
+    // the background stripes
+    cloud_offset: f32,
+    cloud_parallax: f32,
+    show_hitboxes: bool,
+    // Debug overlay of where the bird is headed if it doesn't flap
+    show_trajectory: bool,
+    debug_mode: bool,
+    // Index into DEBUG_TIME_SCALES
+    debug_speed: usize,
+    // trace.log while started with --trace, otherwise None and never touched
+    trace: Option<io::BufWriter<fs::File>>,
+    powerup_timer: f32,
+    // Set by any cheat key during the run, which keeps it off the leaderboard
+    cheated: bool,
+    slow_motion: bool,
+    slow_motion_timer: f32,
+    // 0.0 - 1.0, eases toward 1.0 while slow motion is on; drives the vignette
+    slow_motion_fade: f32,
+    gravity_sign: f32,
+    random_start: bool,
+    fuel_mode: bool,
+    // Endless flying with no deaths and no score on screen; never ranked
+    zen_mode: bool,
+    // Thickness of the ground strip; themes and modes may change it between runs
+    ground_height: f32,
+    adaptive: bool,
+    adaptive_offset: f32,
+    recent_scores: VecDeque<i32>,
+    run_seed: u32,
+    fixed_seed: Option<u32>,
+    // Pipes to clear in the challenge being played, if it has a goal, and the
+    // goal put into the code shown on the game over screen
+    challenge_goal: Option<u32>,
+    share_goal: Option<u32>,
+    code_input: String,
+    // Why the typed code can't be played, shown under it
+    code_error: Option<String>,
+    // Optional pipe to practice from on the code screen, and whether that
+    // field (rather than the code) is being typed into
+    practice_input: String,
+    practice_field: bool,
+    // Set for practice runs, which start just before this pipe (1-based)
+    practice_pipe: Option<u32>,
+    // The hand-made level from level.json, if there is one, why it can't be
+    // played in this window, and whether the current run is playing it
+    level: Option<Level>,
+    level_error: Option<String>,
+    playing_level: bool,
+    custom_field: usize,
+    settings_field: usize,
+    // Action waiting for its new key on the settings screen, and why the last
+    // key pressed for it was refused
+    rebinding: Option<usize>,
+    rebind_warning: Option<String>,
+    // Saved profile names for the profiles screen with each one's guided
+    // unlock score, the selected row, any edit in progress and the last error
+    profiles: Vec<String>,
+    profile_guides: Vec<Option<i32>>,
+    profile_field: usize,
+    profile_edit: ProfileEdit,
+    profile_error: Option<String>,
+    // Input is ignored while this runs
+    transition: Option<Transition>,
+    how_to_play_page: usize,
+    new_high_score: bool,
+    new_best_time: bool,
+    death_cause: Option<DeathCause>,
+    // Seconds left of the slow zoom on the crash; the game over screen waits
+    death_timer: f32,
+    // Seconds into the run. Kept as an f64 so adding one frame at a time
+    // doesn't drift on runs that last for hours.
+    run_elapsed: f64,
+    resumed_from_pause: bool,
+    // Frames of input read so far, and the last one whose presses changed the
+    // state (see InputState::unspent)
+    input_frame: u64,
+    spent_input_frame: Option<u64>,
+    // Set when a slow frame paused the run, for a note on the pause screen
+    lag_paused: bool,
+    // get_time() until which a pending jump press is still honored
+    jump_buffered_until: f64,
+    // Strength multiplier of the buffered jump, from the input that made it
+    jump_buffered_scale: f32,
+    // Real seconds since the last key press or click during a run
+    idle_time: f32,
+    // Highest milestone celebrated this run, frames left of its banner and what
+    // it says (a ladder stage being cleared uses the same banner)
+    last_milestone: i32,
+    milestone_timer: f32,
+    milestone_text: String,
+    // Index into LADDER of the stage being climbed while on the hardcore
+    // ladder, and whether the last stage was just cleared
+    ladder_stage: Option<usize>,
+    ladder_cleared: bool,
+    // Whether any stage of this climb so far went unranked. Each stage is a
+    // fresh run that forgets its own cheats, so the climb remembers them.
+    ladder_cheated: bool,
+    // Whether this run has crossed LEGENDARY_SCORE, and frames left of its banner
+    legendary: bool,
+    legendary_timer: f32,
+    // Whether this run has beaten the stored best, and frames left of the
+    // "BEST!" flash
+    passed_best: bool,
+    best_flash_timer: f32,
+    // Whether this run is the tutorial, whether the bird has flapped since it last
+    // (re)started, and how visible its prompts are (they fade out once it's done)
+    tutorial: bool,
+    tutorial_flapped: bool,
+    tutorial_fade: f32,
+    // Whether this run started as the tutorial. It stays unranked after the
+    // tutorial ends, since its opening pipes went by slowed down.
+    tutorial_run: bool,
+    // Seconds left of the flap reminder, and flaps since the game started
+    hint_timer: f32,
+    session_flaps: u32,
+    audio: AudioManager,
+    music: Music,
+    score_warning: Option<String>,
+    settings_warning: Option<String>,
+    // Set when a save file couldn't be read or moved aside; it's then never
+    // written, so the defaults in its place can't replace the player's data
+    settings_locked: bool,
+    scores_locked: bool,
+    should_quit: bool,
+    // Frames left of the new high score celebration on the game over screen
+    celebration_timer: f32,
+    // Current screen shake strength in pixels; anything that shakes the
+    // screen adds to it, and it wears off by SHAKE_DECAY per frame
+    shake: f32,
+    show_build_info: bool,
+    // The bird's recent (y, rotation), newest last, for the follower to replay
+    bird_history: VecDeque<(f32, f32)>,
+    session: SessionStats,
+    // Recent frames for GIF clips, whether a new high score's clip gets saved
+    // once the crash is on it, and the last save's result with when it happened
+    clip: ClipRecorder,
+    clip_pending: bool,
+    clip_notice: Option<(String, f64)>,
+    game_over_time: f64,
+    credits_started: f64,
+    credits_return: GameState,
+    // The pause overlay's low-res copy of the scene, and the frame pixel art
+    // mode draws into. Both are sized from the window, so until it reports a
+    // size there are none and frames go straight to the window unblurred.
+    blur_target: Option<RenderTarget>,
+    // Scores changed since they were last written, and when that was
+    scores_dirty: bool,
+    last_scores_save: f64,
+    // Additive blending for glowing particles; None if the shader couldn't be
+    // built, in which case they blend normally
+    glow_material: Option<Material>,
+    pixel_target: Option<RenderTarget>,
+    // Whether the render targets and the idle simulation were built against
+    // the real window; some window managers report a 0x0 window at first
+    sized: bool,
+    // Whether this session opened a normal window; a borderless window's
+    // size is the screen's, so it isn't saved
+    windowed: bool,
+    // theme.json as loaded at startup, for the Custom theme
+    custom_theme: Option<Theme>,
+    // F5 in debug builds; the main loop runs hot_reload before the next frame
+    reload_requested: bool,
+}
+
+impl Game {
+    fn new(audio: AudioManager, music: Music) -> Self {
+        let mut game = Self::without_graphics(audio, music);
+        game.glow_material = load_glow_material();
+        if has_window_size() {
+            game.fit_window();
+        }
+        game
+    }
+
+    // Everything but what lives on the GPU: the saves, the profiles and the
+    // menu's idle simulation
+    fn without_graphics(audio: AudioManager, music: Music) -> Self {
+        let (settings, settings_warning, settings_locked) = match Settings::load() {
+            Ok(settings) => (settings, None, false),
+            Err(err) => {
+                let backup = back_up_save_file("settings.json");
+                let (note, locked) = backup_note(backup);
+                let warning = format!("Settings couldn't be loaded: {}{}", err, note);
+                (Settings::default(), Some(warning), locked)
+            }
+        };
+        let (high_scores, score_warning, scores_locked) = load_high_scores(&settings.profile);
+        let windowed = settings.window_mode == WindowMode::Windowed;
+
+        let mut game = Self {
+            sim: Simulation::new(SimConfig::new(
+                Difficulty::Medium,
+                0,
+                screen_width(),
+                screen_height(),
+            )),
+            particles: Vec::new(),
+            particle_rng: StdRng::seed_from_u64(0),
+            high_scores,
+            settings,
+            state: GameState::Menu,
+            difficulty: Difficulty::Medium,
+            background_offset: 0.0,
+            cloud_offset: 0.0,
+            cloud_parallax: CLOUD_PARALLAX,
+            show_hitboxes: false,
+            show_trajectory: false,
+            debug_mode: std::env::args().any(|arg| arg == "--debug"),
+            debug_speed: DEBUG_TIME_SCALE_NORMAL,
+            trace: open_trace(),
+            powerup_timer: 0.0,
+            cheated: false,
+            slow_motion: false,
+            slow_motion_timer: 0.0,
+            slow_motion_fade: 0.0,
+            gravity_sign: 1.0,
+            random_start: false,
+            fuel_mode: false,
+            zen_mode: false,
+            ground_height: GROUND_HEIGHT,
+            adaptive: false,
+            adaptive_offset: 0.0,
+            recent_scores: VecDeque::new(),
+            run_seed: 0,
+            fixed_seed: None,
+            challenge_goal: None,
+            share_goal: None,
+            code_input: String::new(),
+            code_error: None,
+            practice_input: String::new(),
+            practice_field: false,
+            practice_pipe: None,
+            level: load_level(),
+            level_error: None,
+            playing_level: false,
+            custom_field: 0,
+            settings_field: 0,
+            rebinding: None,
+            rebind_warning: None,
+            profiles: Vec::new(),
+            profile_guides: Vec::new(),
+            profile_field: 0,
+            profile_edit: ProfileEdit::None,
+            profile_error: None,
+            transition: None,
+            how_to_play_page: 0,
+            new_high_score: false,
+            new_best_time: false,
+            death_cause: None,
+            death_timer: 0.0,
+            run_elapsed: 0.0,
+            resumed_from_pause: false,
+            input_frame: 0,
+            spent_input_frame: None,
+            lag_paused: false,
+            jump_buffered_until: 0.0,
+            jump_buffered_scale: 1.0,
+            idle_time: 0.0,
+            last_milestone: 0,
+            milestone_timer: 0.0,
+            milestone_text: String::new(),
+            ladder_stage: None,
+            ladder_cleared: false,
+            ladder_cheated: false,
+            legendary: false,
+            legendary_timer: 0.0,
+            passed_best: false,
+            best_flash_timer: 0.0,
+            tutorial: false,
+            tutorial_flapped: false,
+            tutorial_fade: 0.0,
+            tutorial_run: false,
+            hint_timer: 0.0,
+            session_flaps: 0,
+            audio,
+            music,
+            score_warning,
+            settings_warning,
+            settings_locked,
+            scores_locked,
+            should_quit: false,
+            celebration_timer: 0.0,
+            shake: 0.0,
+            show_build_info: false,
+            bird_history: VecDeque::new(),
+            session: SessionStats::default(),
+            clip: ClipRecorder::new(),
+            clip_pending: false,
+            clip_notice: None,
+            game_over_time: 0.0,
+            credits_started: 0.0,
+            credits_return: GameState::Menu,
+            blur_target: None,
+            scores_dirty: false,
+            last_scores_save: 0.0,
+            glow_material: None,
+            pixel_target: None,
+            sized: false,
+            windowed,
+            custom_theme: Theme::load_custom(),
+            reload_requested: false,
+        };
+        game.check_difficulty_unlocked();
+        // A shared machine starts on the profile picker; a lone player goes
+        // straight to the menu
+        game.refresh_profiles();
+        if game.profiles.len() > 1 {
+            game.state = GameState::Profiles;
+        }
+        game
+    }
+
+    // Rebuilds everything sized from the window once it reports real
+    // dimensions. A run started before that restarts so the bird and pipes
+    // are placed in the actual playfield instead of at the top edge.
+    fn fit_window(&mut self) {
+        self.sized = true;
+        self.blur_target = Some(Self::create_blur_target());
+        self.pixel_target = Some(Self::create_pixel_target());
+        if self.state == GameState::Playing {
+            self.reset();
+        } else {
+            self.sim = Simulation::new(SimConfig::new(
+                self.difficulty,
+                0,
+                screen_width(),
+                self.world_height(),
+            ));
+        }
+    }
+
+    fn create_blur_target() -> RenderTarget {
+        let target = render_target(
+            (screen_width() / 4.0).max(1.0) as u32,
+            (screen_height() / 4.0).max(1.0) as u32,
+        );
+        target.texture.set_filter(FilterMode::Linear);
+        target
+    }
+
+    fn theme(&self) -> Theme {
+        self.settings.theme.theme(self.custom_theme.as_ref())
+    }
+
+    fn create_pixel_target() -> RenderTarget {
+        let width = (PIXEL_ART_HEIGHT * screen_width() / screen_height().max(1.0)).round();
+        let target = render_target(width.max(1.0) as u32, PIXEL_ART_HEIGHT as u32);
+        target.texture.set_filter(FilterMode::Nearest);
+        target
+    }
+
+    // Where this frame is drawn: straight to the window, or into the low-res
+    // target in pixel art mode. Layout always works in window coordinates; only
+    // the resolution behind them changes.
+    fn frame_target(&self) -> Option<&RenderTarget> {
+        self.pixel_target
+            .as_ref()
+            .filter(|_| self.settings.pixel_art)
+    }
+
+    fn reset(&mut self) {
+        // Challenge runs replay the same seed; everything else gets a fresh one
+        self.run_seed = self
+            .fixed_seed
+            .unwrap_or_else(|| ::rand::thread_rng().gen());
+        self.share_goal = self.challenge_goal;
+        // Retrying skips whatever is left of a high score celebration
+        self.celebration_timer = 0.0;
+        self.shake = 0.0;
+        self.audio.stop_fanfare();
+        let volume = self.settings.music_volume.get(self.difficulty);
+        self.music.play_for(self.difficulty, volume);
+
+        let (min_gravity, max_gravity, _) = GRAVITY_RANGE;
+        let (min_jump, max_jump, _) = JUMP_STRENGTH_RANGE;
+        let (gap_offset, speed_offset) = if self.adaptive {
+            (
+                self.adaptive_offset * ADAPTIVE_MAX_GAP_OFFSET,
+                self.adaptive_offset * ADAPTIVE_MAX_SPEED_OFFSET,
+            )
+        } else {
+            (0.0, 0.0)
+        };
+        let mut config = SimConfig::new(
+            self.difficulty,
+            self.run_seed,
+            screen_width(),
+            self.world_height(),
+        );
+        config.gravity = self.settings.gravity.clamp(min_gravity, max_gravity);
+        config.jump_strength = self.settings.jump_strength.clamp(-max_jump, -min_jump);
+        config.gravity_sign = self.gravity_sign;
+        config.random_start = self.random_start;
+        config.fuel = self.fuel_mode;
+        config.zen = self.zen_mode;
+        config.assist = self.settings.assist;
+        config.fall_assist = self.settings.fall_assist.clamp(0.0, 1.0);
+        config.gravity_curve = self.settings.gravity_curve;
+        config.flap_boost = self.settings.flap_boost;
+        config.warm_up = self.settings.warm_up && self.fixed_seed.is_none() && !self.playing_level;
+        config.gap_offset = gap_offset;
+        config.speed_offset = speed_offset;
+        config.pipe_density = self.settings.pipe_density;
+        // The max() keeps clamp from panicking on a window that isn't sized yet
+        let max_bird_x = (screen_width() / 2.0).max(BIRD_SIZE);
+        config.bird_x = self.settings.bird_x.clamp(BIRD_SIZE, max_bird_x);
+        config.ground_height = self.ground_height.clamp(0.0, self.world_height() / 2.0);
+        self.sim = Simulation::new(config);
+        if self.playing_level {
+            self.sim.level = self.level.clone();
+        }
+        if let Some(pipe) = self.practice_pipe {
+            self.sim.advance_to_pipe(pipe);
+        }
+        write_trace(
+            &mut self.trace,
+            format_args!("# seed {} {}\n", self.run_seed, self.difficulty.name()),
+        );
+
+        self.particles.clear();
+        self.bird_history.clear();
+        self.clip.clear();
+        self.clip_pending = false;
+        self.particle_rng = particle_rng(self.run_seed);
+        self.powerup_timer = 0.0;
+        self.cheated = false;
+        self.slow_motion = false;
+        self.slow_motion_timer = 0.0;
+        self.slow_motion_fade = 0.0;
+        self.new_high_score = false;
+        self.new_best_time = false;
+        self.death_cause = None;
+        self.death_timer = 0.0;
+        self.ladder_cleared = false;
+        self.run_elapsed = 0.0;
+        self.resumed_from_pause = false;
+        self.lag_paused = false;
+        self.jump_buffered_until = 0.0;
+        self.idle_time = 0.0;
+        self.last_milestone = 0;
+        self.milestone_timer = 0.0;
+        self.legendary = false;
+        self.legendary_timer = 0.0;
+        self.passed_best = false;
+        self.best_flash_timer = 0.0;
+        self.tutorial_flapped = false;
+        self.tutorial_fade = if self.tutorial { 1.0 } else { 0.0 };
+        self.tutorial_run = self.tutorial;
+        // The tutorial has its own flap prompt
+        self.hint_timer = if self.tutorial || self.session_flaps >= HINT_SESSION_FLAPS {
+            0.0
+        } else {
+            HINT_SECONDS
+        };
+    }
+
+    // Starts a run from the menu, as the tutorial until it has been finished once
+    fn fade_to(&mut self, change: StateChange) {
+        if self.transition.is_none() {
+            self.transition = Some(Transition {
+                started: get_time(),
+                change,
+                applied: false,
+            });
+        }
+    }
+
+    // Runs the pending state change once the screen is fully dark, and ends
+    // the transition once it has faded back in
+    fn update_transition(&mut self) {
+        let Some(mut transition) = self.transition else {
+            return;
+        };
+        let elapsed = get_time() - transition.started;
+        if !transition.applied && elapsed >= TRANSITION_SECONDS / 2.0 {
+            transition.applied = true;
+            match transition.change {
+                StateChange::StartRun(tutorial) => self.start_run(tutorial),
+                StateChange::StartChallenge(challenge) => self.start_challenge(challenge),
+                StateChange::StartLevel => self.start_level(),
+                StateChange::StartLadder => self.start_ladder(),
+                StateChange::ToMenu => self.return_to_menu(),
+            }
+        }
+        self.transition = (elapsed < TRANSITION_SECONDS).then_some(transition);
+    }
+
+    fn draw_transition(&self) {
+        let Some(transition) = self.transition else {
+            return;
+        };
+        let half = TRANSITION_SECONDS / 2.0;
+        let elapsed = get_time() - transition.started;
+        let alpha = (1.0 - ((elapsed - half).abs() / half)).clamp(0.0, 1.0) as f32;
+        let color = Color::new(0.0, 0.0, 0.0, alpha);
+        draw_rectangle(0.0, 0.0, screen_width(), screen_height(), color);
+    }
+
+    fn start_run(&mut self, tutorial: bool) {
+        self.tutorial = tutorial;
+        self.reset();
+        self.state = GameState::Playing;
+    }
+
+    // The tutorial is over once enough pipes are behind the bird; the run then
+    // carries on at full speed
+    fn check_tutorial(&mut self) {
+        if self.tutorial && self.sim.score >= TUTORIAL_PIPES {
+            self.tutorial = false;
+            self.settings.tutorial_completed = true;
+            self.save_settings();
+        }
+        if !self.tutorial && self.tutorial_fade > 0.0 {
+            self.tutorial_fade = (self.tutorial_fade - 1.0 / TUTORIAL_FADE_FRAMES).max(0.0);
+        }
+    }
+
+    // Real time since the last frame, clamped so a long stall can't produce a huge
+    // step. The frame right after unpausing spans the whole pause, so it gets a
+    // nominal step instead.
+    fn frame_dt(&mut self, dt: f32) -> f32 {
+        if self.resumed_from_pause {
+            self.resumed_from_pause = false;
+            return NOMINAL_FRAME_DT;
+        }
+        dt.min(MAX_FRAME_DT)
+    }
+
+    // A pass by a hair gets a heartbeat and, motion allowing, a small shake
+    fn near_miss(&mut self) {
+        self.audio.play_heartbeat();
+        if !self.settings.reduced_motion {
+            self.shake = self.shake.max(NEAR_MISS_SHAKE);
+        }
+    }
+
+    // Where the shake has knocked the world this frame
+    fn shake_offset(&self) -> Vec2 {
+        let t = get_time() as f32;
+        Vec2::new((t * 53.0).sin(), (t * 71.0).cos()) * self.shake
+    }
+
+    // Made it past the last pipe of the level
+    fn complete_level(&mut self) {
+        self.state = GameState::GameOver;
+        self.shake = 0.0;
+        self.spawn_particles(
+            self.sim.bird.x,
+            self.sim.bird.y,
+            GOLD,
+            30,
+            ParticleShape::Star,
+        );
+        self.audio.play_fanfare();
+        self.record_run();
+    }
+
+    fn die(&mut self, cause: DeathCause) {
+        self.state = GameState::GameOver;
+        self.shake = 0.0;
+        self.death_cause = Some(cause);
+        self.death_timer = DEATH_ZOOM_SECONDS;
+        let color = death_particle_color(cause);
+        self.spawn_particles(
+            self.sim.bird.x,
+            self.sim.bird.y,
+            color,
+            30,
+            ParticleShape::Square,
+        );
+        // Scores are saved right away; the zoom is only for show
+        self.record_run();
+    }
+
+    // 0..1 through the crash zoom, eased out, or None once it's over
+    fn death_zoom_progress(&self) -> Option<f32> {
+        if self.state != GameState::GameOver || self.death_timer <= 0.0 {
+            return None;
+        }
+        let t = 1.0 - self.death_timer / DEATH_ZOOM_SECONDS;
+        Some(1.0 - (1.0 - t.clamp(0.0, 1.0)).powi(3))
+    }
+
+    fn game_over_elapsed(&self) -> f32 {
+        (get_time() - self.game_over_time) as f32
+    }
+
+    fn retry_lockout(&self) -> f32 {
+        self.settings.retry_lockout_seconds.clamp(0.0, 3.0)
+    }
+
+    fn overlay_darkness(&self) -> f32 {
+        let (min, max, _) = OVERLAY_DARKNESS_RANGE;
+        self.settings.overlay_darkness.clamp(min, max)
+    }
+
+    // Updates the high score, star and time records for the run that just ended
+    fn record_run(&mut self) {
+        self.game_over_time = get_time();
+        self.log_run();
+        self.session.record(self.sim.score);
+        let mut changed = false;
+        if self.adaptive {
+            self.update_adaptive_offset();
+        }
+        if !self.is_ranked() {
+            self.record_flagged_run();
+            return;
+        }
+        if self
+            .high_scores
+            .update_on(self.leaderboard(), self.difficulty, self.sim.score)
+        {
+            if self.leaderboard() == Leaderboard::Normal {
+                *self.high_scores.best_splits.get_mut(self.difficulty) =
+                    self.sim.score_frames.clone();
+            }
+            self.new_high_score = true;
+            self.clip_pending = self.clips_enabled();
+            self.celebration_timer = CELEBRATION_FRAMES;
+            self.audio.play_fanfare();
+            changed = true;
+        }
+        if self
+            .high_scores
+            .update_stars(self.difficulty, self.sim.star_rating())
+        {
+            changed = true;
+        }
+        if self
+            .high_scores
+            .update_time(self.difficulty, self.run_elapsed as f32)
+        {
+            self.new_best_time = true;
+            changed = true;
+        }
+        if changed {
+            self.scores_dirty = true;
+        }
+        // Game over is a natural pause in the action, so write now
+        self.flush_high_scores(true);
+    }
+
+    // A run kept off the board only by a cheat, an assist or modified physics
+    // still has its best kept on the normal table, marked with why
+    fn record_flagged_run(&mut self) {
+        let plain_run = self.practice_pipe.is_none()
+            && !self.zen_mode
+            && !self.playing_level
+            && !self.tutorial_run
+            && self.leaderboard() == Leaderboard::Normal;
+        if plain_run
+            && self
+                .high_scores
+                .update_flagged(self.difficulty, self.sim.score, self.run_flags())
+        {
+            self.scores_dirty = true;
+            self.flush_high_scores(true);
+        }
+    }
+
+    // A cheat on an earlier ladder stage flags every stage after it too
+    fn run_flags(&self) -> RunFlags {
+        RunFlags {
+            cheated: self.cheated || self.ladder_cheated,
+            assisted: self.settings.is_assisted(),
+            modified_physics: self.settings.modifies_physics() || self.difficulty.is_retuned(),
+        }
+    }
+
+    // Run logging is for external tools, so a failed write is only reported on
+    // stderr and never shown in game
+    fn log_run(&self) {
+        let from_env = std::env::var("FLAPPY_LOG_RUNS").is_ok_and(|value| value == "1");
+        if !self.settings.log_runs && !from_env {
+            return;
+        }
+        let flags = self.run_flags();
+        let record = RunRecord {
+            score: self.sim.score,
+            difficulty: self.difficulty.name().to_string(),
+            duration_secs: self.run_elapsed as f32,
+            seed: self.run_seed,
+            max_combo: self.sim.max_combo,
+            bonus_gates: self.sim.bonus_gates,
+            death_cause: self.sim.death,
+            ranked: self.is_ranked(),
+            profile: self.settings.profile.clone(),
+            cheated: flags.cheated,
+            assisted: flags.assisted,
+            modified_physics: flags.modified_physics,
+        };
+        if let Err(err) = append_run_record(&record) {
+            eprintln!("Couldn't append to runs.jsonl: {}", err);
+        }
+    }
+
+    // Save failures don't interrupt play; they leave a warning on screen until
+    // a later save of the same file succeeds
+    fn save_high_scores(&mut self) {
+        if self.scores_locked {
+            return;
+        }
+        self.score_warning = self
+            .high_scores
+            .save(&self.settings.profile)
+            .err()
+            .map(|err| format!("Scores can't be saved: {}", err));
+    }
+
+    // Writes changed scores once save_interval_seconds have passed since the
+    // last write, or right away if forced. A failed write stays dirty and is
+    // retried at the next opportunity.
+    fn flush_high_scores(&mut self, force: bool) {
+        if !self.scores_dirty {
+            return;
+        }
+        let interval = self.settings.save_interval_seconds.max(0.0) as f64;
+        if !force && get_time() - self.last_scores_save < interval {
+            return;
+        }
+        self.save_high_scores();
+        self.last_scores_save = get_time();
+        self.scores_dirty = self.score_warning.is_some();
+    }
+
+    fn save_settings(&mut self) {
+        if self.settings_locked {
+            return;
+        }
+        self.settings_warning = self
+            .settings
+            .save()
+            .err()
+            .map(|err| format!("Settings can't be saved: {}", err));
+    }
+
+    // Rereads theme.json, level.json and the sounds in assets/ without a
+    // restart, for iterating on them in debug builds. A run in progress is
+    // paused first, since the sounds take a few frames to load.
+    async fn hot_reload(&mut self) {
+        self.reload_requested = false;
+        if self.state == GameState::Playing {
+            self.flush_high_scores(true);
+            self.state = GameState::Paused;
+        }
+
+        self.custom_theme = Theme::load_custom();
+        eprintln!(
+            "Reloaded theme.json: {}",
+            if self.custom_theme.is_some() {
+                "Custom colors updated"
+            } else {
+                "no theme loaded"
+            }
+        );
+
+        self.level = load_level();
+        self.level_error = None;
+        self.check_level();
+        match (&self.level, &self.level_error) {
+            (Some(_), Some(err)) => eprintln!("Reloaded level.json: can't be played here: {}", err),
+            (Some(level), None) => eprintln!("Reloaded level.json: {} pipes", level.pipes.len()),
+            (None, _) => eprintln!("Reloaded level.json: no level loaded"),
+        }
+
+        let audio_enabled = self.audio.audio_enabled;
+        self.music.stop();
+        let mut loading = LoadingScreen::new(audio_enabled);
+        loading.present().await;
+        self.audio = AudioManager::load(audio_enabled, &mut loading).await;
+        self.music = Music::load(audio_enabled, &mut loading).await;
+        let volume = self.settings.music_volume.get(self.difficulty);
+        self.music.play_for(self.difficulty, volume);
+        if audio_enabled {
+            eprintln!("Reloaded sounds: {} clips", LoadingScreen::CLIPS);
+        } else {
+            eprintln!("Sounds not reloaded: audio is off");
+        }
+    }
+
+    // Called once on the way out. A run still in progress counts as if it ended
+    // here, then everything is written out one last time.
+    fn shutdown(&mut self) {
+        if matches!(self.state, GameState::Playing | GameState::Paused) {
+            self.state = GameState::GameOver;
+            self.record_run();
+        }
+        if let Some(trace) = &mut self.trace {
+            if let Err(err) = trace.flush() {
+                eprintln!("Couldn't write trace.log: {}", err);
+            }
+        }
+        // The last save always writes, changed or not, except over a file
+        // that failed to load and couldn't be moved aside
+        self.scores_dirty = !self.scores_locked;
+        self.flush_high_scores(true);
+        if self.windowed && has_window_size() {
+            self.settings.window_width = screen_width() as u32;
+            self.settings.window_height = screen_height() as u32;
+        }
+        self.save_settings();
+    }
+
+    // Rereads the saved profiles, making sure the active one is listed even
+    // before its first save, and selects it
+    fn refresh_profiles(&mut self) {
+        let mut profiles = list_profiles().unwrap_or_else(|err| {
+            self.profile_error = Some(format!("Profiles couldn't be listed: {}", err));
+            Vec::new()
+        });
+        if !profiles.contains(&self.settings.profile) {
+            profiles.push(self.settings.profile.clone());
+            profiles.sort();
+        }
+        self.profile_field = profiles
+            .iter()
+            .position(|name| *name == self.settings.profile)
+            .unwrap_or(0);
+        self.profile_guides = profiles
+            .iter()
+            .map(|name| {
+                if *name == self.settings.profile {
+                    self.high_scores.unlock_score
+                } else {
+                    HighScores::load(name)
+                        .ok()
+                        .and_then(|scores| scores.unlock_score)
+                }
+            })
+            .collect();
+        self.profiles = profiles;
+    }
+
+    // Steps the selected profile's guided progression through off and each of
+    // UNLOCK_SCORE_CHOICES, saving it straight away
+    fn cycle_guided(&mut self) {
+        let Some(name) = self.profiles.get(self.profile_field).cloned() else {
+            return;
+        };
+        let playing = name == self.settings.profile;
+        let mut scores = if playing {
+            if self.scores_locked {
+                let err = format!("{}'s scores couldn't be loaded, so they aren't saved", name);
+                self.profile_error = Some(err);
+                return;
+            }
+            std::mem::take(&mut self.high_scores)
+        } else {
+            match HighScores::load(&name) {
+                Ok(scores) => scores,
+                Err(err) => {
+                    self.profile_error = Some(format!("Couldn't read {}: {}", name, err));
+                    return;
+                }
+            }
+        };
+        let current = scores.unlock_score.and_then(|score| {
+            UNLOCK_SCORE_CHOICES
+                .iter()
+                .position(|&choice| choice == score)
+        });
+        let next = cycle_choice(current, UNLOCK_SCORE_CHOICES.len(), 1.0);
+        scores.unlock_score = next.map(|i| UNLOCK_SCORE_CHOICES[i]);
+        let result = scores.save(&name);
+        if let Some(guide) = self.profile_guides.get_mut(self.profile_field) {
+            *guide = scores.unlock_score;
+        }
+        if playing {
+            self.high_scores = scores;
+            self.check_difficulty_unlocked();
+        }
+        self.profile_error = result
+            .err()
+            .map(|err| format!("Couldn't save {}: {}", name, err));
+    }
+
+    fn open_profiles(&mut self) {
+        self.profile_edit = ProfileEdit::None;
+        self.profile_error = None;
+        self.refresh_profiles();
+        self.state = GameState::Profiles;
+    }
+
+    // Loads the profile's scores. Adaptive mode's history belongs to whoever
+    // was playing, so it starts over too.
+    fn switch_profile(&mut self, name: &str) {
+        if self.settings.profile == name {
+            return;
+        }
+        self.flush_high_scores(true);
+        self.settings.profile = name.to_string();
+        self.save_settings();
+        self.scores_dirty = false;
+        (self.high_scores, self.score_warning, self.scores_locked) = load_high_scores(name);
+        self.recent_scores.clear();
+        self.adaptive_offset = 0.0;
+        self.check_difficulty_unlocked();
+    }
+
+    fn is_unlocked(&self, difficulty: Difficulty) -> bool {
+        self.high_scores.is_unlocked(difficulty)
+    }
+
+    // What a guided profile still has to score to play a locked difficulty
+    fn locked_text(&self, difficulty: Difficulty) -> String {
+        match self.high_scores.unlock_requirement(difficulty) {
+            Some((previous, score)) => {
+                format!(
+                    "Score {} on {} to unlock {}",
+                    score,
+                    previous.name(),
+                    difficulty.name()
+                )
+            }
+            None => String::new(),
+        }
+    }
+
+    // Locked difficulties can't be picked; the key just buzzes
+    fn select_difficulty(&mut self, difficulty: Difficulty) {
+        if self.is_unlocked(difficulty) {
+            self.difficulty = difficulty;
+        } else {
+            self.audio.play_denied(get_time());
+        }
+    }
+
+    // Drops back to Easy when the profile hasn't unlocked the selected difficulty
+    fn check_difficulty_unlocked(&mut self) {
+        if !self.is_unlocked(self.difficulty) {
+            self.difficulty = Difficulty::Easy;
+        }
+    }
+
+    // A typed name that's valid and not already taken (ignoring case, and
+    // ignoring the profile being renamed)
+    fn check_profile_name(&self, typed: &str, renaming: Option<&str>) -> Result<String, String> {
+        let name = sanitize_profile_name(typed)
+            .ok_or_else(|| format!("Use 1-{} letters, digits, spaces, - or _", PROFILE_NAME_MAX))?;
+        let taken = self
+            .profiles
+            .iter()
+            .any(|other| Some(other.as_str()) != renaming && other.eq_ignore_ascii_case(&name));
+        if taken {
+            return Err(format!("{} already exists", name));
+        }
+        Ok(name)
+    }
+
+    fn update_profiles(&mut self) {
+        let selected = self
+            .profiles
+            .get(self.profile_field)
+            .cloned()
+            .unwrap_or_default();
+        match self.profile_edit.clone() {
+            ProfileEdit::Create(mut typed) | ProfileEdit::Rename(mut typed) => {
+                while let Some(c) = get_char_pressed() {
+                    if !c.is_control() && typed.chars().count() < PROFILE_NAME_MAX {
+                        typed.push(c);
+                    }
+                }
+                if is_key_pressed(KeyCode::Backspace) {
+                    typed.pop();
+                }
+                let creating = matches!(self.profile_edit, ProfileEdit::Create(_));
+                self.profile_edit = if creating {
+                    ProfileEdit::Create(typed.clone())
+                } else {
+                    ProfileEdit::Rename(typed.clone())
+                };
+
+                if is_key_pressed(KeyCode::Enter) {
+                    let renaming = (!creating).then_some(selected.as_str());
+                    let name = match self.check_profile_name(&typed, renaming) {
+                        Ok(name) => name,
+                        Err(err) => {
+                            self.profile_error = Some(err);
+                            return;
+                        }
+                    };
+                    let result = if creating {
+                        HighScores::default().save(&name)
+                    } else if selected == self.settings.profile {
+                        // Written out under the new name so scores that only
+                        // live in memory so far aren't lost
+                        rename_profile(&selected, &name).and_then(|_| self.high_scores.save(&name))
+                    } else {
+                        rename_profile(&selected, &name)
+                    };
+                    if let Err(err) = result {
+                        self.profile_error = Some(format!("Couldn't save {}: {}", name, err));
+                        return;
+                    }
+                    if !creating && selected == self.settings.profile {
+                        self.settings.profile = name.clone();
+                        self.save_settings();
+                    }
+                    self.profile_edit = ProfileEdit::None;
+                    self.profile_error = None;
+                    self.refresh_profiles();
+                    if let Some(i) = self.profiles.iter().position(|other| *other == name) {
+                        self.profile_field = i;
+                    }
+                }
+                if is_key_pressed(KeyCode::Escape) {
+                    self.profile_edit = ProfileEdit::None;
+                    self.profile_error = None;
+                }
+            }
+            ProfileEdit::Delete => {
+                if is_key_pressed(KeyCode::Y) {
+                    if let Err(err) = delete_profile(&selected) {
+                        self.profile_error = Some(format!("Couldn't delete {}: {}", selected, err));
+                    } else {
+                        self.profiles.retain(|name| *name != selected);
+                        if selected == self.settings.profile {
+                            let next = self.profiles.first().cloned().unwrap_or_default();
+                            self.switch_profile(&next);
+                        }
+                        self.refresh_profiles();
+                    }
+                    self.profile_edit = ProfileEdit::None;
+                }
+                if is_key_pressed(KeyCode::N) || is_key_pressed(KeyCode::Escape) {
+                    self.profile_edit = ProfileEdit::None;
+                }
+            }
+            ProfileEdit::None => {
+                let count = self.profiles.len().max(1);
+                if is_key_pressed(KeyCode::Up) {
+                    self.profile_field = (self.profile_field + count - 1) % count;
+                }
+                if is_key_pressed(KeyCode::Down) {
+                    self.profile_field = (self.profile_field + 1) % count;
+                }
+                if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::Space) {
+                    self.switch_profile(&selected);
+                    self.state = GameState::Menu;
+                }
+                // Drop the key that opened the name box from the text queue
+                if is_key_pressed(KeyCode::N) {
+                    while get_char_pressed().is_some() {}
+                    self.profile_error = None;
+                    if self.profiles.len() >= MAX_PROFILES {
+                        self.profile_error = Some(format!("At most {} profiles", MAX_PROFILES));
+                    } else {
+                        self.profile_edit = ProfileEdit::Create(String::new());
+                    }
+                }
+                if is_key_pressed(KeyCode::R) {
+                    while get_char_pressed().is_some() {}
+                    self.profile_error = None;
+                    self.profile_edit = ProfileEdit::Rename(selected.clone());
+                }
+                if is_key_pressed(KeyCode::G) {
+                    self.cycle_guided();
+                }
+                if is_key_pressed(KeyCode::D) {
+                    if self.profiles.len() <= 1 {
+                        self.profile_error = Some("Can't delete the only profile".to_string());
+                    } else {
+                        self.profile_error = None;
+                        self.profile_edit = ProfileEdit::Delete;
+                    }
+                }
+                if is_key_pressed(KeyCode::Escape) {
+                    self.state = GameState::Menu;
+                }
+            }
+        }
+    }
+
+    fn is_upside_down(&self) -> bool {
+        self.gravity_sign < 0.0
+    }
+
+    // Adaptive takes precedence since it changes the difficulty itself, then
+    // fuel since it limits the controls
+    fn leaderboard(&self) -> Leaderboard {
+        if self.adaptive {
+            Leaderboard::Adaptive
+        } else if self.fuel_mode {
+            Leaderboard::Fuel
+        } else if self.is_upside_down() {
+            Leaderboard::UpsideDown
+        } else {
+            Leaderboard::Normal
+        }
+    }
+
+    fn best_score(&self) -> i32 {
+        self.high_scores.get_on(self.leaderboard(), self.difficulty)
+    }
+
+    // Nudges the adaptive offset a step towards easier when recent runs end
+    // quickly and towards harder when the player is cruising
+    fn update_adaptive_offset(&mut self) {
+        self.recent_scores.push_back(self.sim.score);
+        if self.recent_scores.len() > ADAPTIVE_WINDOW {
+            self.recent_scores.pop_front();
+        }
+        let average =
+            self.recent_scores.iter().sum::<i32>() as f32 / self.recent_scores.len() as f32;
+        let target = if average < ADAPTIVE_STRUGGLING_SCORE {
+            -1.0
+        } else if average > ADAPTIVE_CRUISING_SCORE {
+            1.0
+        } else {
+            0.0
+        };
+        let step = (target - self.adaptive_offset).clamp(-ADAPTIVE_STEP, ADAPTIVE_STEP);
+        self.adaptive_offset = (self.adaptive_offset + step).clamp(-1.0, 1.0);
+    }
+
+    // The debug speed multiplier; always 1.0 outside debug mode
+    fn debug_time_scale(&self) -> f32 {
+        if self.debug_mode {
+            DEBUG_TIME_SCALES[self.debug_speed]
+        } else {
+            1.0
+        }
+    }
+
+    fn set_debug_mode(&mut self, enabled: bool) {
+        self.debug_mode = enabled;
+        if !enabled {
+            // Drop any debug-only toggles; timed power-up effects stay
+            self.show_hitboxes = false;
+            self.show_trajectory = false;
+            self.debug_speed = DEBUG_TIME_SCALE_NORMAL;
+            if self.slow_motion_timer <= 0.0 {
+                self.slow_motion = false;
+            }
+            self.sim.cheat_invincible = false;
+        }
+    }
+
+    // Runs count for high scores, stars and challenge codes unless a setting
+    // or difficulties.json changed the layout or physics, a cheat was used, or
+    // it's a practice, zen, level or tutorial run. A ladder stage also needs
+    // every stage before it on the climb to have been ranked.
+    fn is_ranked(&self) -> bool {
+        self.settings.is_ranked()
+            && !self.difficulty.is_retuned()
+            && !self.cheated
+            && self.practice_pipe.is_none()
+            && !self.zen_mode
+            && !self.playing_level
+            && !self.tutorial_run
+            && !self.ladder_cheated
+    }
+
+    fn current_challenge(&self) -> Challenge {
+        Challenge {
+            seed: self.run_seed,
+            difficulty: self.difficulty,
+            upside_down: self.is_upside_down(),
+            random_start: self.random_start,
+            fuel: self.fuel_mode,
+            goal: self.share_goal,
+        }
+    }
+
+    fn start_challenge(&mut self, challenge: Challenge) {
+        self.difficulty = challenge.difficulty;
+        self.gravity_sign = if challenge.upside_down { -1.0 } else { 1.0 };
+        self.random_start = challenge.random_start;
+        self.fuel_mode = challenge.fuel;
+        // Adaptive tuning would change the layout, so challenges are played straight
+        self.adaptive = false;
+        self.fixed_seed = Some(challenge.seed);
+        self.challenge_goal = challenge.goal;
+        self.reset();
+        self.state = GameState::Playing;
+    }
+
+    fn start_ladder(&mut self) {
+        self.ladder_stage = Some(0);
+        self.ladder_cheated = false;
+        self.difficulty = LADDER[0];
+        self.start_run(false);
+    }
+
+    // Reaching the target moves straight on to the next difficulty on the same
+    // life, or ends the climb after the last one
+    fn check_ladder(&mut self) {
+        let Some(stage) = self.ladder_stage else {
+            return;
+        };
+        if self.sim.score < LADDER_TARGET {
+            return;
+        }
+        // Checked before the next stage's reset clears `cheated`
+        if !self.is_ranked() {
+            self.ladder_cheated = true;
+        }
+        if !self.ladder_cheated && self.high_scores.update_ladder(stage as u32 + 1) {
+            self.scores_dirty = true;
+        }
+        if stage + 1 == LADDER.len() {
+            self.ladder_cleared = true;
+            self.state = GameState::GameOver;
+            self.shake = 0.0;
+            self.spawn_particles(
+                self.sim.bird.x,
+                self.sim.bird.y,
+                GOLD,
+                30,
+                ParticleShape::Star,
+            );
+            self.audio.play_fanfare();
+            self.record_run();
+            return;
+        }
+        self.ladder_stage = Some(stage + 1);
+        self.difficulty = LADDER[stage + 1];
+        self.reset();
+        self.milestone_timer = MILESTONE_BANNER_FRAMES;
+        self.milestone_text = format!(
+            "STAGE {}: {}",
+            stage + 2,
+            self.difficulty.name().to_uppercase()
+        );
+        self.audio.play_milestone();
+    }
+
+    fn start_level(&mut self) {
+        self.playing_level = true;
+        self.start_run(false);
+    }
+
+    // Whether level.json's gaps fit this window; the menu shows why not
+    fn check_level(&mut self) -> bool {
+        let Some(level) = &self.level else {
+            return false;
+        };
+        let floor = self.world_height() - self.ground_height.clamp(0.0, self.world_height() / 2.0);
+        self.level_error = level.validate(floor).err();
+        self.level_error.is_none()
+    }
+
+    fn return_to_menu(&mut self) {
+        self.playing_level = false;
+        self.ladder_stage = None;
+        self.fixed_seed = None;
+        self.challenge_goal = None;
+        self.practice_pipe = None;
+        self.tutorial = false;
+        self.state = GameState::Menu;
+    }
+
+    fn show_credits(&mut self) {
+        self.credits_return = self.state;
+        self.credits_started = get_time();
+        self.state = GameState::Credits;
+    }
+
+    // Height of the playfield. It matches the window unless settings ask for a
+    // taller world, in which case the camera scrolls to follow the bird.
+    fn world_height(&self) -> f32 {
+        screen_height() * self.settings.world_height_scale.max(1.0)
+    }
+
+    // Top of the visible slice of the world, kept within the world's edges
+    fn camera_y(&self) -> f32 {
+        let slack = self.world_height() - screen_height();
+        if slack <= 0.0 {
+            return 0.0;
+        }
+        (self.sim.bird.y - screen_height() / 2.0).clamp(0.0, slack)
+    }
+
+    fn spawn_particles(
+        &mut self,
+        x: f32,
+        y: f32,
+        color: Color,
+        count: usize,
+        shape: ParticleShape,
+    ) {
+        self.spawn_spread_particles(x, y, color, count, 1.0, shape);
+    }
+
+    // Like spawn_particles, with launch speeds multiplied by spread
+    fn spawn_spread_particles(
+        &mut self,
+        x: f32,
+        y: f32,
+        color: Color,
+        count: usize,
+        spread: f32,
+        shape: ParticleShape,
+    ) {
+        let (particles, rng) = (&mut self.particles, &mut self.particle_rng);
+        launch_particles(particles, rng, vec2(x, y), color, count, spread, shape);
+    }
+
+    // Gold burst for a point, bigger and wider on harder difficulties and
+    // growing with the combo the same way the point chime climbs
+    fn score_burst(&mut self, x: f32) {
+        let (base, spread) = self.difficulty.score_burst();
+        let combo = self.sim.combo.min(COMBO_PITCH_STEPS) as usize;
+        let count = ((base + combo) as f32 * self.settings.quality.particle_scale()).round();
+        let count = (count as usize).min(MAX_PARTICLES);
+        let y = self.world_height() / 2.0;
+        self.spawn_spread_particles(x, y, GOLD, count, spread, ParticleShape::Star);
+        self.glow_newest(count);
+    }
+
+    // Marks the most recently spawned particles as glowing
+    fn glow_newest(&mut self, count: usize) {
+        for particle in self.particles.iter_mut().rev().take(count) {
+            particle.glow = true;
+        }
+    }
+
+    // Fires once per threshold, so a bonus gate that jumps past one still counts
+    fn check_milestone(&mut self) {
+        if !self.settings.milestones || self.settings.milestone_interval <= 0 {
+            return;
+        }
+        let interval = self.settings.milestone_interval;
+        let reached = self.sim.score / interval * interval;
+        if reached <= self.last_milestone {
+            return;
+        }
+        self.last_milestone = reached;
+        self.milestone_timer = MILESTONE_BANNER_FRAMES;
+        self.milestone_text = format!("{} POINTS!", reached);
+        self.audio.play_milestone();
+        let (x, y) = (self.sim.bird.x, self.sim.bird.y);
+        self.spawn_particles(x, y, GOLD, 30, ParticleShape::Star);
+    }
+
+    // Once per run. Its banner takes over from a milestone banner fired on the
+    // same point.
+    fn check_legendary(&mut self) {
+        if self.legendary || self.sim.score < LEGENDARY_SCORE {
+            return;
+        }
+        self.legendary = true;
+        self.legendary_timer = LEGENDARY_BANNER_FRAMES;
+        self.milestone_timer = 0.0;
+        self.audio.play_applause();
+        if self.settings.reduced_motion {
+            return;
+        }
+
+        // Confetti drifting down from the top of the view
+        let colors = [RED, GOLD, SKYBLUE, GREEN, VIOLET, PINK];
+        let top = self.camera_y();
+        // A minimized window can report no width at all, and an empty range panics
+        let width = screen_width().max(1.0);
+        for i in 0..80 {
+            let x = self.particle_rng.gen_range(0.0..width);
+            self.spawn_particles(x, top, colors[i % colors.len()], 1, ParticleShape::Square);
+            if let Some(particle) = self.particles.last_mut() {
+                particle.vy = self.particle_rng.gen_range(0.5..2.5);
+                particle.gravity = 0.03;
+                particle.fade = 0.006;
+            }
+        }
+    }
+
+    // Once per run, the moment the score passes a best that would actually be
+    // replaced. A first run has no record to beat, so it doesn't flash.
+    fn check_best(&mut self) {
+        let best = self.best_score();
+        if self.passed_best || !self.is_ranked() || best <= 0 || self.sim.score <= best {
+            return;
+        }
+        self.passed_best = true;
+        self.best_flash_timer = BEST_FLASH_FRAMES;
+        self.audio.play_milestone();
+    }
+
+    // Gold fountain rising from the ground behind the game over text. The scene
+    // is otherwise frozen, so particles only move while this runs.
+    fn update_celebration(&mut self) {
+        self.celebration_timer -= 1.0;
+        for _ in 0..self.difficulty.celebration_particles() {
+            let x = screen_width() / 2.0 + self.particle_rng.gen_range(-200.0..200.0);
+            let y = self.sim.ground_y();
+            self.spawn_particles(x, y, GOLD, 1, ParticleShape::Star);
+            if let Some(particle) = self.particles.last_mut() {
+                particle.vy = self.particle_rng.gen_range(-12.0..-7.0);
+            }
+        }
+        let frames = get_frame_time().min(MAX_FRAME_DT) / NOMINAL_FRAME_DT;
+        update_particles(&mut self.particles, frames);
+    }
+
+    fn update(&mut self) {
+        self.input_frame += 1;
+        let input = InputState::read(self.settings.keys, self.input_frame);
+        self.tick(input, get_frame_time());
+    }
+
+    // Advances one frame of dt seconds. Runs are driven entirely by input;
+    // the menus and other screens still read the keyboard themselves. Only the
+    // state the tick started in reads input, and a press that changes it is
+    // consumed, so the SPACE that retries a run is never also its first flap,
+    // not even when the same frame's input drives another tick.
+    fn tick(&mut self, input: InputState, dt: f32) {
+        let mut input = input.unspent(self.spent_input_frame);
+        // Closing the window goes through the same path as quitting from the menu
+        if is_quit_requested() {
+            self.should_quit = true;
+        }
+        self.music.set_ducked(matches!(
+            self.state,
+            GameState::Paused | GameState::GameOver
+        ));
+        self.music.update();
+        self.flush_high_scores(false);
+        if !self.sized && has_window_size() {
+            self.fit_window();
+        }
+        if self.transition.is_some() {
+            self.update_transition();
+            return;
+        }
+
+        if is_key_pressed(KeyCode::F1) {
+            self.show_build_info = !self.show_build_info;
+        }
+        if is_key_pressed(KeyCode::F9) && self.clips_enabled() {
+            self.save_clip();
+        }
+        if cfg!(debug_assertions) && is_key_pressed(KeyCode::F5) {
+            self.reload_requested = true;
+        }
+
+        // CTRL+SHIFT+D toggles debug mode from any screen
+        let ctrl = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+        let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+        if ctrl && shift && is_key_pressed(KeyCode::D) {
+            self.set_debug_mode(!self.debug_mode);
+        }
+
+        match self.state {
+            GameState::Menu => {
+                if is_key_pressed(KeyCode::Space) || is_key_pressed(KeyCode::Enter) {
+                    self.fade_to(StateChange::StartRun(!self.settings.tutorial_completed));
+                }
+                if is_key_pressed(KeyCode::T) {
+                    self.fade_to(StateChange::StartRun(true));
+                }
+                if is_key_pressed(KeyCode::Key1) {
+                    self.select_difficulty(Difficulty::Easy);
+                }
+                if is_key_pressed(KeyCode::Key2) {
+                    self.select_difficulty(Difficulty::Medium);
+                }
+                if is_key_pressed(KeyCode::Key3) {
+                    self.select_difficulty(Difficulty::Hard);
+                }
+                if is_key_pressed(KeyCode::Key4) {
+                    self.select_difficulty(Difficulty::Extreme);
+                }
+                if is_key_pressed(KeyCode::Key5) {
+                    self.difficulty = Difficulty::Custom(self.settings.custom);
+                    self.state = GameState::CustomSetup;
+                }
+                if is_key_pressed(KeyCode::S) {
+                    self.settings_field = 0;
+                    self.state = GameState::Settings;
+                }
+                if is_key_pressed(KeyCode::P) {
+                    self.open_profiles();
+                }
+                if is_key_pressed(KeyCode::Escape) {
+                    self.should_quit = true;
+                }
+                if is_key_pressed(KeyCode::H) {
+                    self.how_to_play_page = 0;
+                    self.state = GameState::HowToPlay;
+                }
+                if is_key_pressed(KeyCode::C) {
+                    self.show_credits();
+                }
+                if is_key_pressed(KeyCode::U) {
+                    self.gravity_sign = -self.gravity_sign;
+                }
+                if is_key_pressed(KeyCode::A) {
+                    self.adaptive = !self.adaptive;
+                }
+                if is_key_pressed(KeyCode::R) {
+                    self.random_start = !self.random_start;
+                }
+                if is_key_pressed(KeyCode::F) {
+                    self.fuel_mode = !self.fuel_mode;
+                }
+                if is_key_pressed(KeyCode::Z) {
+                    self.zen_mode = !self.zen_mode;
+                }
+                if is_key_pressed(KeyCode::M) {
+                    // The ladder climbs every difficulty, so all of them have
+                    // to be unlocked
+                    if self.is_unlocked(Difficulty::Extreme) {
+                        self.fade_to(StateChange::StartLadder);
+                    } else {
+                        self.audio.play_denied(get_time());
+                    }
+                }
+                if is_key_pressed(KeyCode::L) && self.check_level() {
+                    self.fade_to(StateChange::StartLevel);
+                }
+                if is_key_pressed(KeyCode::E) {
+                    self.code_input.clear();
+                    self.code_error = None;
+                    self.practice_input.clear();
+                    self.practice_field = false;
+                    // Drop the 'e' that opened this screen from the text queue
+                    while get_char_pressed().is_some() {}
+                    self.state = GameState::EnterCode;
+                }
+            }
+            GameState::EnterCode => {
+                if is_key_pressed(KeyCode::Tab) {
+                    self.practice_field = !self.practice_field;
+                }
+                while let Some(c) = get_char_pressed() {
+                    if self.practice_field {
+                        if c.is_ascii_digit() && self.practice_input.len() < 4 {
+                            self.practice_input.push(c);
+                        }
+                    } else if c.is_ascii_alphanumeric() && self.code_input.len() < 10 {
+                        self.code_input.push(c.to_ascii_uppercase());
+                        self.code_error = None;
+                    }
+                }
+                if is_key_pressed(KeyCode::Backspace) {
+                    if self.practice_field {
+                        self.practice_input.pop();
+                    } else {
+                        self.code_input.pop();
+                        self.code_error = None;
+                    }
+                }
+                if is_key_pressed(KeyCode::Enter) {
+                    match decode_challenge(&self.code_input) {
+                        Some(challenge) if !self.is_unlocked(challenge.difficulty) => {
+                            self.code_error = Some(self.locked_text(challenge.difficulty));
+                        }
+                        Some(challenge) => {
+                            // Practice needs the code's fixed seed, so the pipe
+                            // field only counts alongside a valid code
+                            self.practice_pipe =
+                                self.practice_input.parse().ok().filter(|&pipe| pipe > 0);
+                            self.fade_to(StateChange::StartChallenge(challenge));
+                        }
+                        None => self.code_error = Some("That code isn't valid".to_string()),
+                    }
+                }
+                if is_key_pressed(KeyCode::Escape) {
+                    self.state = GameState::Menu;
+                }
+            }
+            GameState::Credits => {
+                // Leave on any input, or once the last line has scrolled away
+                let scrolled = (get_time() - self.credits_started) as f32 * CREDITS_SCROLL_SPEED;
+                let finished =
+                    scrolled > screen_height() + CREDITS.len() as f32 * CREDITS_LINE_HEIGHT;
+                if finished
+                    || get_last_key_pressed().is_some()
+                    || is_mouse_button_pressed(MouseButton::Left)
+                {
+                    self.state = self.credits_return;
+                }
+            }
+            GameState::HowToPlay => {
+                let page_count = HOW_TO_PLAY_PAGES.len();
+                if is_key_pressed(KeyCode::Left) {
+                    self.how_to_play_page = (self.how_to_play_page + page_count - 1) % page_count;
+                }
+                if is_key_pressed(KeyCode::Right) {
+                    self.how_to_play_page = (self.how_to_play_page + 1) % page_count;
+                }
+                if is_key_pressed(KeyCode::Escape) {
+                    self.state = GameState::Menu;
+                }
+            }
+            GameState::Profiles => self.update_profiles(),
+            GameState::Settings => {
+                if let Some(action) = self.rebinding {
+                    // ESC backs out; the key that started the capture was
+                    // handled last frame so it can't bind itself
+                    if let Some(key) = get_last_key_pressed() {
+                        if key == KeyCode::Escape {
+                            self.rebinding = None;
+                            self.rebind_warning = None;
+                        } else {
+                            match self.settings.keys.rebind(action, key) {
+                                Ok(()) => {
+                                    self.rebinding = None;
+                                    self.rebind_warning = None;
+                                    self.save_settings();
+                                }
+                                Err(warning) => self.rebind_warning = Some(warning),
+                            }
+                        }
+                    }
+                    return;
+                }
+
+                let field_count = Settings::FIELDS.len();
+                if is_key_pressed(KeyCode::Up) {
+                    self.settings_field = (self.settings_field + field_count - 1) % field_count;
+                }
+                if is_key_pressed(KeyCode::Down) {
+                    self.settings_field = (self.settings_field + 1) % field_count;
+                }
+                let locked =
+                    self.settings_field == Settings::FOLLOWER_FIELD && !self.follower_unlocked();
+                if is_key_pressed(KeyCode::Left) && !locked {
+                    self.settings.adjust(self.settings_field, -1.0);
+                }
+                if is_key_pressed(KeyCode::Right) && !locked {
+                    self.settings.adjust(self.settings_field, 1.0);
+                }
+                if is_key_pressed(KeyCode::Enter) {
+                    if self.settings_field == Settings::RESET_KEYS_FIELD {
+                        self.settings.keys = Keybindings::default();
+                        self.rebind_warning = None;
+                        self.save_settings();
+                        return;
+                    }
+                    if self.settings_field >= Settings::FIRST_KEY_FIELD {
+                        self.rebinding = Some(self.settings_field - Settings::FIRST_KEY_FIELD);
+                        self.rebind_warning = None;
+                        return;
+                    }
+                }
+                if is_key_pressed(KeyCode::Escape) || is_key_pressed(KeyCode::Enter) {
+                    self.rebind_warning = None;
+                    self.save_settings();
+                    self.state = GameState::Menu;
+                }
+            }
+            GameState::CustomSetup => {
+                let field_count = DifficultyParams::FIELDS.len();
+                if is_key_pressed(KeyCode::Up) {
+                    self.custom_field = (self.custom_field + field_count - 1) % field_count;
+                }
+                if is_key_pressed(KeyCode::Down) {
+                    self.custom_field = (self.custom_field + 1) % field_count;
+                }
+                if is_key_pressed(KeyCode::Left) {
+                    self.settings.custom.adjust(self.custom_field, -1.0);
+                }
+                if is_key_pressed(KeyCode::Right) {
+                    self.settings.custom.adjust(self.custom_field, 1.0);
+                }
+                self.difficulty = Difficulty::Custom(self.settings.custom);
+
+                if is_key_pressed(KeyCode::Space) || is_key_pressed(KeyCode::Enter) {
+                    self.save_settings();
+                    self.reset();
+                    self.state = GameState::Playing;
+                    return;
+                }
+                if is_key_pressed(KeyCode::Escape) {
+                    self.save_settings();
+                    self.state = GameState::Menu;
+                }
+            }
+            GameState::Playing => {
+                if input.pause {
+                    self.flush_high_scores(true);
+                    self.state = GameState::Paused;
+                    return;
+                }
+                // Nothing from a frame that long is simulated; the player
+                // couldn't have reacted to it
+                if is_stalled_frame(dt, self.resumed_from_pause) {
+                    self.flush_high_scores(true);
+                    self.lag_paused = true;
+                    self.state = GameState::Paused;
+                    return;
+                }
+
+                // Handle jump. Presses are buffered briefly so one that lands
+                // just before the cooldown ends still flaps instead of being dropped.
+                let now = get_time();
+                let key_scale = input.jump.then(|| self.settings.jump_scale(false));
+                let mouse_scale = input.click.then(|| self.settings.jump_scale(true));
+                let tap_scale = (self.settings.simple_controls && input.tap).then(|| {
+                    self.settings
+                        .jump_scale(false)
+                        .or(self.settings.jump_scale(true))
+                });
+                let scale = key_scale
+                    .flatten()
+                    .or(mouse_scale.flatten())
+                    .or(tap_scale.flatten());
+                if let Some(scale) = scale {
+                    if self.sim.out_of_fuel() {
+                        self.audio.play_denied(now);
+                    } else {
+                        self.jump_buffered_until = now + JUMP_BUFFER_SECONDS;
+                        self.jump_buffered_scale = scale;
+                    }
+                }
+                let banked = self.sim.bird.extra_jumps;
+                if now <= self.jump_buffered_until && self.sim.jump(now, self.jump_buffered_scale) {
+                    self.jump_buffered_until = 0.0;
+                    self.tutorial_flapped = true;
+                    self.session_flaps = self.session_flaps.saturating_add(1);
+                    self.hint_timer = self.hint_timer.min(HINT_FADE_SECONDS);
+                    self.audio.play_flap(now);
+                    self.spawn_particles(
+                        self.sim.bird.x,
+                        self.sim.bird.y,
+                        WHITE,
+                        5,
+                        ParticleShape::Feather,
+                    );
+                    // An extra flap bursts bigger and brighter
+                    if self.sim.bird.extra_jumps < banked {
+                        let (x, y) = (self.sim.bird.x, self.sim.bird.y);
+                        self.spawn_particles(x, y, WHITE, 16, ParticleShape::Feather);
+                        self.glow_newest(16);
+                    }
+                }
+
+                // Debug toggles and cheats are only live in debug mode so a
+                // stray key press can't flip them during normal play
+                if self.debug_mode {
+                    if input.toggle_hitboxes {
+                        self.show_hitboxes = !self.show_hitboxes;
+                    }
+                    if input.toggle_trajectory {
+                        self.show_trajectory = !self.show_trajectory;
+                    }
+                    if input.toggle_invincible {
+                        self.sim.cheat_invincible = !self.sim.cheat_invincible;
+                        self.cheated = true;
+                    }
+                    if input.toggle_slow_motion {
+                        self.slow_motion = !self.slow_motion;
+                        self.slow_motion_timer = 0.0;
+                        self.cheated = true;
+                    }
+                    if input.slower {
+                        self.debug_speed = self.debug_speed.saturating_sub(1);
+                    }
+                    if input.faster {
+                        self.debug_speed = (self.debug_speed + 1).min(DEBUG_TIME_SCALES.len() - 1);
+                    }
+                }
+
+                let mut time_scale = if self.slow_motion { 0.5 } else { 1.0 };
+                if self.tutorial {
+                    time_scale *= TUTORIAL_TIME_SCALE;
+                }
+                let debug_scale = self.debug_time_scale();
+                if debug_scale != 1.0 {
+                    time_scale *= debug_scale;
+                    self.cheated = true;
+                }
+                let dt = self.frame_dt(dt);
+                self.run_elapsed += (dt * time_scale) as f64;
+                self.hint_timer = (self.hint_timer - dt).max(0.0);
+
+                // Only pause for idleness while the bird is safely inside the
+                // playfield, so it never freezes mid-crash
+                if input.any {
+                    self.idle_time = 0.0;
+                } else {
+                    self.idle_time += dt;
+                }
+                let bird_y = self.sim.bird.y;
+                let inside = bird_y - BIRD_SIZE / 2.0 > 0.0
+                    && bird_y + BIRD_SIZE / 2.0 < self.sim.ground_y();
+                if self.settings.auto_pause
+                    && self.idle_time >= self.settings.auto_pause_seconds
+                    && inside
+                {
+                    self.idle_time = 0.0;
+                    self.flush_high_scores(true);
+                    self.state = GameState::Paused;
+                    return;
+                }
+
+                // Power-up invincibility wears off; the cheat toggle has no timer
+                if self.powerup_timer > 0.0 {
+                    self.powerup_timer -= time_scale;
+                    if self.powerup_timer <= 0.0 {
+                        self.powerup_timer = 0.0;
+                        self.sim.powerup_invincible = false;
+                    }
+                }
+                // Timed slow motion counts down in real frames so it isn't stretched by itself
+                if self.slow_motion_timer > 0.0 {
+                    self.slow_motion_timer -= 1.0;
+                    if self.slow_motion_timer <= 0.0 {
+                        self.slow_motion_timer = 0.0;
+                        self.slow_motion = false;
+                    }
+                }
+                let fade_target = if self.slow_motion { 1.0 } else { 0.0 };
+                let fade_step = 1.0 / SLOW_MOTION_FADE_FRAMES;
+                self.slow_motion_fade = if self.slow_motion_fade < fade_target {
+                    (self.slow_motion_fade + fade_step).min(fade_target)
+                } else {
+                    (self.slow_motion_fade - fade_step).max(fade_target)
+                };
+
+                // Update background
+                let scroll = BACKGROUND_SCROLL_SPEED * self.difficulty.scroll_factor() * time_scale;
+                self.background_offset -= scroll;
+                // Wrapping by the stripe width rather than snapping to zero
+                // keeps the scroll smooth and the offset small forever
+                if self.background_offset <= -50.0 {
+                    self.background_offset += 50.0;
+                }
+                self.cloud_offset = (self.cloud_offset + self.cloud_parallax * scroll)
+                    .rem_euclid(cloud_wrap_width());
+
+                // Bird, pipes, scoring and collisions
+                let events = self.sim.step(time_scale);
+                self.bird_history
+                    .push_back((self.sim.bird.y, self.sim.bird.rotation));
+                if self.bird_history.len() > FOLLOWER_DELAY_FRAMES {
+                    self.bird_history.pop_front();
+                }
+                write_trace(
+                    &mut self.trace,
+                    format_args!(
+                        "{} {}{} {} {} {} {}\n",
+                        self.sim.elapsed,
+                        if input.jump { 'J' } else { '-' },
+                        if input.click { 'M' } else { '-' },
+                        self.sim.bird.y,
+                        self.sim.bird.velocity,
+                        self.sim.pipes.len(),
+                        self.sim.score
+                    ),
+                );
+                for event in events {
+                    match event {
+                        SimEvent::Scored { x, near_miss, .. } => {
+                            self.audio.play_point(self.sim.combo);
+                            self.score_burst(x);
+                            if near_miss {
+                                self.near_miss();
+                            }
+                        }
+                        SimEvent::BonusGate { x, y } => {
+                            self.audio.play_point(COMBO_PITCH_STEPS);
+                            self.spawn_particles(x, y, GOLD, 20, ParticleShape::Star);
+                            self.glow_newest(20);
+                        }
+                        SimEvent::ExtraJump { x, y } => {
+                            self.audio.play_milestone();
+                            self.spawn_particles(x, y, SKYBLUE, 12, ParticleShape::Circle);
+                            self.glow_newest(12);
+                        }
+                        // Crashing in the tutorial just starts it over
+                        SimEvent::Died(_) if self.tutorial => {
+                            self.reset();
+                            break;
+                        }
+                        SimEvent::Died(cause) => self.die(cause),
+                        SimEvent::LevelComplete => self.complete_level(),
+                    }
+                }
+                self.shake = (self.shake - SHAKE_DECAY * time_scale).max(0.0);
+                // Zen runs have no score to celebrate
+                if self.state == GameState::Playing && !self.zen_mode {
+                    self.check_milestone();
+                    self.check_legendary();
+                    self.check_best();
+                }
+                if self.state == GameState::Playing {
+                    self.check_tutorial();
+                    self.check_ladder();
+                }
+                if self.milestone_timer > 0.0 {
+                    self.milestone_timer = (self.milestone_timer - time_scale).max(0.0);
+                }
+                if self.legendary_timer > 0.0 {
+                    self.legendary_timer = (self.legendary_timer - time_scale).max(0.0);
+                }
+                if self.best_flash_timer > 0.0 {
+                    self.best_flash_timer = (self.best_flash_timer - time_scale).max(0.0);
+                }
+
+                // Update particles
+                let frames = dt * time_scale / NOMINAL_FRAME_DT;
+                update_particles(&mut self.particles, frames);
+            }
+            GameState::Paused => {
+                if input.pause || input.jump {
+                    self.state = GameState::Playing;
+                    self.resumed_from_pause = true;
+                    self.lag_paused = false;
+                    // A flap buffered just before pausing doesn't go off on resume
+                    self.jump_buffered_until = 0.0;
+                    self.spent_input_frame = Some(input.frame);
+                    input.consume();
+                }
+                if input.quit {
+                    self.fade_to(StateChange::ToMenu);
+                }
+            }
+            GameState::GameOver => {
+                // The crash plays out in slow motion before anything else
+                if self.death_timer > 0.0 {
+                    self.death_timer = (self.death_timer - dt.min(MAX_FRAME_DT)).max(0.0);
+                    let frames = dt.min(MAX_FRAME_DT) * DEATH_TIME_SCALE / NOMINAL_FRAME_DT;
+                    update_particles(&mut self.particles, frames);
+                    return;
+                }
+                if self.celebration_timer > 0.0 {
+                    self.update_celebration();
+                }
+                if self.game_over_elapsed() < self.retry_lockout() {
+                    return;
+                }
+                if input.confirm {
+                    // A ladder always starts over at the bottom
+                    if self.ladder_stage.is_some() {
+                        self.ladder_stage = Some(0);
+                        self.ladder_cheated = false;
+                        self.difficulty = LADDER[0];
+                    }
+                    self.reset();
+                    self.state = GameState::Playing;
+                    self.spent_input_frame = Some(input.frame);
+                    input.consume();
+                }
+                if input.back || input.quit {
+                    self.fade_to(StateChange::ToMenu);
+                }
+                if input.cycle_goal {
+                    let current = self
+                        .share_goal
+                        .and_then(|goal| CHALLENGE_GOALS.iter().position(|&g| g == goal));
+                    let next = cycle_choice(current, CHALLENGE_GOALS.len(), 1.0);
+                    self.share_goal = next.map(|i| CHALLENGE_GOALS[i]);
+                }
+                if self.new_high_score && input.credits {
+                    self.show_credits();
+                }
+            }
+        }
+    }
+
+    fn draw(&self) {
+        let target = self.frame_target();
+        if target.is_some() {
+            set_camera(&screen_camera(0.0, target));
+            clear_background(SKYBLUE);
+        }
+        self.draw_background();
+
+        match self.state {
+            GameState::Menu => self.draw_menu(),
+            GameState::CustomSetup => self.draw_custom_setup(),
+            GameState::Settings => self.draw_settings(),
+            GameState::Profiles => self.draw_profiles(),
+            GameState::HowToPlay => self.draw_how_to_play(),
+            GameState::Credits => self.draw_credits(),
+            GameState::EnterCode => self.draw_enter_code(),
+            GameState::Playing => self.draw_playing(target),
+            GameState::Paused => {
+                self.draw_playing(target);
+                self.draw_pause_overlay();
+            }
+            GameState::GameOver => {
+                self.draw_playing(target);
+                if self.death_zoom_progress().is_none() {
+                    self.draw_game_over();
+                }
+            }
+        }
+
+        self.draw_save_warnings();
+        self.draw_clip_notice();
+        if self.show_build_info {
+            self.draw_build_info();
+        }
+        self.draw_transition();
+
+        if let Some(target) = target {
+            set_default_camera();
+            draw_texture_ex(
+                &target.texture,
+                0.0,
+                0.0,
+                WHITE,
+                DrawTextureParams {
+                    dest_size: Some(vec2(screen_width(), screen_height())),
+                    flip_y: true,
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    // F1 overlay for bug reports: exactly which build is running and how
+    fn draw_build_info(&self) {
+        let profile = if cfg!(debug_assertions) {
+            "debug"
+        } else {
+            "release"
+        };
+        let lines = [
+            format!(
+                "Flappy Bird v{} ({} build)",
+                env!("CARGO_PKG_VERSION"),
+                profile
+            ),
+            format!(
+                "Target: {} {}",
+                std::env::consts::OS,
+                std::env::consts::ARCH
+            ),
+            format!("Debug mode: {}", if self.debug_mode { "On" } else { "Off" }),
+            "F1 - Hide".to_string(),
+        ];
+        let width = lines
+            .iter()
+            .map(|line| measure_text(line, None, 18, 1.0).width)
+            .fold(0.0, f32::max);
+        let height = lines.len() as f32 * 20.0 + 10.0;
+        let x = screen_width() - width - 20.0;
+        let y = screen_height() - height - 10.0;
+
+        draw_rectangle(
+            x - 10.0,
+            y,
+            width + 20.0,
+            height,
+            Color::from_rgba(0, 0, 0, 170),
+        );
+        for (i, line) in lines.iter().enumerate() {
+            draw_text(line, x, y + 22.0 + i as f32 * 20.0, 18.0, WHITE);
+        }
+    }
+
+    // Non-blocking notice along the top edge while a save file is unusable
+    fn follower_unlocked(&self) -> bool {
+        [
+            Difficulty::Easy,
+            Difficulty::Medium,
+            Difficulty::Hard,
+            Difficulty::Extreme,
+        ]
+        .iter()
+        .any(|&difficulty| self.high_scores.get(difficulty) >= FOLLOWER_UNLOCK_SCORE)
+    }
+
+    fn show_follower(&self) -> bool {
+        self.settings.follower && !self.settings.reduced_motion && self.follower_unlocked()
+    }
+
+    // A second bird behind the player's, replaying its flight a few frames
+    // late. Just for looks: nothing collides with it.
+    fn draw_follower(&self) {
+        let Some(&(y, rotation)) = self.bird_history.front() else {
+            return;
+        };
+        let mut follower = self.sim.bird.clone();
+        follower.x -= FOLLOWER_OFFSET_X;
+        follower.y = y;
+        follower.rotation = rotation;
+        draw_bird(
+            &follower,
+            FOLLOWER_COLOR,
+            None,
+            1.0,
+            self.settings.quality.circle_sides(),
+        );
+    }
+
+    fn clips_enabled(&self) -> bool {
+        self.settings.gif_clips && self.settings.quality != GraphicsQuality::Low
+    }
+
+    // Called after each frame is drawn. Runs are recorded while playing and
+    // for a moment after a crash; a new high score's clip is saved then.
+    fn record_clip_frame(&mut self) {
+        if !self.clips_enabled() {
+            self.clip.clear();
+            return;
+        }
+        let recording = match self.state {
+            GameState::Playing => true,
+            GameState::GameOver => self.game_over_elapsed() < GIF_GAME_OVER_SECONDS,
+            _ => false,
+        };
+        if recording {
+            self.clip.capture(get_frame_time());
+        } else if self.clip_pending && self.state == GameState::GameOver {
+            self.clip_pending = false;
+            self.save_clip();
+        }
+    }
+
+    // Writes the recorded frames to clips/ next to the executable
+    fn save_clip(&mut self) {
+        let Some(gif) = self.clip.encode() else {
+            return;
+        };
+        let seconds = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let name = format!("clips/clip-{}.gif", seconds);
+        let path = save_path(&name);
+        let result = gif.and_then(|gif| {
+            path.parent().map_or(Ok(()), fs::create_dir_all)?;
+            fs::write(&path, gif)
+        });
+        let notice = match result {
+            Ok(()) => format!("Saved {}", name),
+            Err(err) => format!("Couldn't save {}: {}", name, err),
+        };
+        self.clip_notice = Some((notice, get_time()));
+    }
+
+    fn draw_clip_notice(&self) {
+        let Some((notice, time)) = &self.clip_notice else {
+            return;
+        };
+        if get_time() - time > CLIP_NOTICE_SECONDS {
+            return;
+        }
+        let y = screen_height() - 16.0;
+        let width = measure_text(notice, None, 20, 1.0).width;
+        let x = screen_width() / 2.0 - width / 2.0;
+        draw_rectangle(
+            x - 8.0,
+            y - 16.0,
+            width + 16.0,
+            22.0,
+            Color::from_rgba(0, 0, 0, 160),
+        );
+        draw_text(notice, x, y, 20.0, WHITE);
+    }
+
+    fn draw_save_warnings(&self) {
+        let warnings = [&self.score_warning, &self.settings_warning];
+        for (i, warning) in warnings.into_iter().flatten().enumerate() {
+            let y = 22.0 + i as f32 * 24.0;
+            let width = measure_text(warning, None, 20, 1.0).width;
+            let x = screen_width() / 2.0 - width / 2.0;
+            draw_rectangle(
+                x - 8.0,
+                y - 16.0,
+                width + 16.0,
+                22.0,
+                Color::from_rgba(0, 0, 0, 160),
+            );
+            draw_text(warning, x, y, 20.0, ORANGE);
+        }
+    }
+
+    fn draw_background(&self) {
+        let theme = self.theme();
+        let (stripes, cloud) = self.difficulty.background_colors();
+        let stripes = theme.sky.map_or(stripes, sky_stripes);
+        let cloud = theme.clouds.unwrap_or(cloud);
+        draw_rectangle(0.0, 0.0, screen_width(), screen_height(), stripes[0]);
+
+        // Draw animated background
+        for i in 0..20 {
+            let offset = (self.background_offset + i as f32 * 50.0) % screen_width();
+            draw_rectangle(
+                offset,
+                0.0,
+                50.0,
+                screen_height() - self.sim.config.ground_height,
+                stripes[i % 3],
+            );
+        }
+
+        // Draw clouds, spread evenly over the wrap width whatever their number.
+        // Each cloud spans x - 40 to x + 110, so it leaves fully off the left
+        // edge before reappearing fully off the right one.
+        let density = self.difficulty.cloud_density();
+        let cloud_count = (self.settings.quality.cloud_count() as f32 * density).round() as usize;
+        let wrap = cloud_wrap_width();
+        let sides = self.settings.quality.circle_sides();
+        let spacing = wrap / cloud_count.max(1) as f32;
+        for i in 0..cloud_count {
+            let x = (i as f32 * spacing - self.cloud_offset).rem_euclid(wrap) - CLOUD_WIDTH + 40.0;
+            let y = 100.0 + (i % 5) as f32 * 50.0;
+            draw_round(x, y, 40.0, sides, cloud);
+            draw_round(x + 30.0, y, 50.0, sides, cloud);
+            draw_round(x + 60.0, y, 40.0, sides, cloud);
+        }
+    }
+
+    fn draw_menu(&self) {
+        let title = "FLAPPY BIRD";
+        let title_size = 80.0;
+        let title_width = measure_text(title, None, title_size as u16, 1.0).width;
+
+        draw_text(
+            title,
+            screen_width() / 2.0 - title_width / 2.0,
+            110.0,
+            title_size,
+            YELLOW,
+        );
+
+        let mut y = 220.0;
+        draw_centered_text("Press SPACE or ENTER to Start", y, 25.0, WHITE);
+        y += 45.0;
+        draw_centered_text("Select Difficulty:", y, 35.0, WHITE);
+        y += 40.0;
+
+        // Difficulty lines are colored by intensity, with the selected one boxed
+        let difficulties = [
+            Difficulty::Easy,
+            Difficulty::Medium,
+            Difficulty::Hard,
+            Difficulty::Extreme,
+            Difficulty::Custom(self.settings.custom),
+        ];
+        for (i, difficulty) in difficulties.iter().enumerate() {
+            // Guided profiles grey out what they haven't unlocked yet
+            if let Some((previous, score)) = self.high_scores.unlock_requirement(*difficulty) {
+                let line = format!(
+                    "[{}] {} - Locked: score {} on {}",
+                    i + 1,
+                    difficulty.name(),
+                    score,
+                    previous.name()
+                );
+                draw_centered_text(&line, y, 30.0, GRAY);
+                y += 34.0;
+                continue;
+            }
+            let best_time = self.high_scores.best_time.get(*difficulty);
+            let time = if best_time > 0.0 {
+                format!(" ({:.1}s)", best_time)
+            } else {
+                String::new()
+            };
+            // A flagged run is only worth showing when it beat the real best
+            let high_score = self.high_scores.get(*difficulty);
+            let flagged = self.high_scores.flagged.get(*difficulty);
+            let flagged = if flagged.score > high_score {
+                format!(" | {}* ({})", flagged.score, flagged.flags.mark())
+            } else {
+                String::new()
+            };
+            let line = format!(
+                "[{}] {} - High Score: {}{}{}",
+                i + 1,
+                difficulty.name(),
+                high_score,
+                time,
+                flagged
+            );
+            let selected =
+                std::mem::discriminant(difficulty) == std::mem::discriminant(&self.difficulty);
+            if selected {
+                let width = measure_text(&line, None, 30, 1.0).width;
+                let box_x = screen_width() / 2.0 - width / 2.0 - 12.0;
+                draw_rectangle(
+                    box_x,
+                    y - 26.0,
+                    width + 24.0,
+                    36.0,
+                    Color::from_rgba(0, 0, 0, 100),
+                );
+                draw_rectangle_lines(box_x, y - 26.0, width + 24.0, 36.0, 3.0, difficulty.color());
+            }
+            draw_centered_text(&line, y, 30.0, difficulty.color());
+            y += 34.0;
+        }
+
+        y += 16.0;
+        let on_off = |enabled: bool| if enabled { "On" } else { "Off" };
+        draw_centered_text(
+            "H - How to Play    T - Tutorial    C - Credits    E - Enter Code",
+            y,
+            22.0,
+            WHITE,
+        );
+        y += 26.0;
+        let color = if self.settings.is_ranked() {
+            WHITE
+        } else {
+            ORANGE
+        };
+        let ladder = if self.is_unlocked(Difficulty::Extreme) {
+            format!("{}/{}", self.high_scores.best_ladder_stage, LADDER.len())
+        } else {
+            "locked".to_string()
+        };
+        let options = format!(
+            "S - Settings    P - Profile: {}    M - Ladder ({})    ESC - Quit",
+            self.settings.profile, ladder
+        );
+        draw_centered_text(&options, y, 22.0, color);
+        y += 26.0;
+        let modifiers = [
+            format!(
+                "U - Upside-Down: {}    A - Adaptive: {}",
+                on_off(self.is_upside_down()),
+                on_off(self.adaptive)
+            ),
+            format!(
+                "R - Random Start: {}    F - Fuel: {}    Z - Zen: {}",
+                on_off(self.random_start),
+                on_off(self.fuel_mode),
+                on_off(self.zen_mode)
+            ),
+        ];
+        let modified = self.is_upside_down()
+            || self.adaptive
+            || self.random_start
+            || self.fuel_mode
+            || self.zen_mode;
+        for line in &modifiers {
+            draw_centered_text(line, y, 22.0, if modified { VIOLET } else { WHITE });
+            y += 26.0;
+        }
+        if let Some(level) = &self.level {
+            match &self.level_error {
+                Some(err) => {
+                    let text = format!("Can't play level.json here: {}", err);
+                    draw_centered_text(&text, y, 22.0, RED);
+                }
+                None => {
+                    let name = if level.name.is_empty() {
+                        "level.json"
+                    } else {
+                        &level.name
+                    };
+                    draw_centered_text(&format!("L - Play Level: {}", name), y, 22.0, SKYBLUE);
+                }
+            }
+        }
+
+        self.draw_session_stats();
+        self.draw_difficulty_preview();
+
+        draw_menu_bird(160.0, self.settings.quality.circle_sides());
+    }
+
+    // The next second of flight without a flap, with a dot where it meets each
+    // upcoming pipe: green if that's inside the gap, red if it would crash
+    fn draw_trajectory(&self) {
+        let path = self.sim.predicted_path(TRAJECTORY_FRAMES);
+        let mut from = (self.sim.bird.x, self.sim.bird.y);
+        for &to in &path {
+            draw_line(from.0, from.1, to.0, to.1, 2.0, Color { a: 0.7, ..WHITE });
+            from = to;
+        }
+        for pipe in self
+            .sim
+            .pipes
+            .iter()
+            .filter(|pipe| pipe.x > self.sim.bird.x)
+        {
+            let Some(&(x, y)) = path.iter().find(|(x, _)| *x >= pipe.x) else {
+                continue;
+            };
+            let inside = y > pipe.gap_y && y < pipe.gap_y + pipe.gap_height;
+            draw_circle(x, y, 5.0, if inside { GREEN } else { RED });
+        }
+    }
+
+    // Small panel in the top left corner once a run has finished this session
+    fn draw_session_stats(&self) {
+        if self.session.runs == 0 {
+            return;
+        }
+        let lines = [
+            "This session:".to_string(),
+            format!("Runs: {}", self.session.runs),
+            format!("Average: {:.1}", self.session.average()),
+            format!("Best: {}", self.session.best),
+        ];
+        draw_rectangle(6.0, 6.0, 130.0, 84.0, Color::from_rgba(0, 0, 0, 100));
+        for (i, line) in lines.iter().enumerate() {
+            draw_text(line, 12.0, 24.0 + i as f32 * 20.0, 18.0, WHITE);
+        }
+    }
+
+    // Top right of the menu: the highlighted difficulty's gap drawn to scale
+    // next to an arrow as long as its pipe speed, then the numbers themselves
+    fn draw_difficulty_preview(&self) {
+        let difficulty = self.difficulty;
+        let (width, height) = (140.0, 164.0);
+        let x = screen_width() - width - 6.0;
+        let y = 6.0;
+        draw_rectangle(x, y, width, height, Color::from_rgba(0, 0, 0, 100));
+        draw_text(
+            difficulty.name(),
+            x + 8.0,
+            y + 20.0,
+            20.0,
+            difficulty.color(),
+        );
+
+        let (top, bottom) = (y + 28.0, y + 88.0);
+        let gap = (difficulty.pipe_gap() * 0.25).min(bottom - top);
+        let gap_y = (top + bottom) / 2.0 - gap / 2.0;
+        draw_rectangle(x + 10.0, top, 14.0, gap_y - top, GREEN);
+        draw_rectangle(x + 10.0, gap_y + gap, 14.0, bottom - gap_y - gap, GREEN);
+        let arrow_y = (top + bottom) / 2.0;
+        let length = (difficulty.pipe_speed() * 20.0).clamp(16.0, width - 44.0);
+        let arrow_x = x + 34.0 + length;
+        draw_arrow(vec2(arrow_x, arrow_y), vec2(x + 34.0, arrow_y), WHITE);
+
+        let lines = [
+            format!(
+                "Gap: {:.0} (min {:.0})",
+                difficulty.pipe_gap(),
+                difficulty.min_gap()
+            ),
+            format!("Speed: {:.1}", difficulty.pipe_speed()),
+            format!(
+                "Pipe every {:.1}s",
+                difficulty.spawn_interval() * NOMINAL_FRAME_DT
+            ),
+        ];
+        for (i, line) in lines.iter().enumerate() {
+            draw_text(line, x + 8.0, bottom + 24.0 + i as f32 * 20.0, 18.0, WHITE);
+        }
+    }
+
+    fn draw_how_to_play(&self) {
+        let title = "HOW TO PLAY";
+        let title_width = measure_text(title, None, 60, 1.0).width;
+        draw_text(
+            title,
+            screen_width() / 2.0 - title_width / 2.0,
+            90.0,
+            60.0,
+            YELLOW,
+        );
+
+        let mut y = 160.0;
+        for (heading, lines) in HOW_TO_PLAY_PAGES[self.how_to_play_page] {
+            draw_text(heading, 80.0, y, 35.0, GOLD);
+            y += 40.0;
+            for line in *lines {
+                draw_text(line, 100.0, y, 25.0, WHITE);
+                y += 32.0;
+            }
+            y += 20.0;
+        }
+
+        let footer = format!(
+            "Page {}/{}    LEFT / RIGHT - Change Page    ESC - Back",
+            self.how_to_play_page + 1,
+            HOW_TO_PLAY_PAGES.len()
+        );
+        let footer_width = measure_text(&footer, None, 25, 1.0).width;
+        draw_text(
+            &footer,
+            screen_width() / 2.0 - footer_width / 2.0,
+            screen_height() - 40.0,
+            25.0,
+            WHITE,
+        );
+    }
+
+    fn draw_profiles(&self) {
+        draw_rectangle(
+            0.0,
+            0.0,
+            screen_width(),
+            screen_height(),
+            Color::from_rgba(0, 0, 0, 160),
+        );
+        draw_centered_text("WHO'S PLAYING?", 120.0, 50.0, YELLOW);
+
+        let x = screen_width() / 2.0 - 150.0;
+        for (i, name) in self.profiles.iter().enumerate() {
+            let y = 180.0 + i as f32 * 32.0;
+            let selected = i == self.profile_field;
+            let color = if selected { GOLD } else { WHITE };
+            if selected {
+                draw_text(">", x - 30.0, y, 28.0, GOLD);
+            }
+            let mut text = name.clone();
+            if *name == self.settings.profile {
+                text.push_str(" (current)");
+            }
+            if let Some(Some(score)) = self.profile_guides.get(i) {
+                text.push_str(&format!(" - guided: {}", score));
+            }
+            draw_text(&text, x, y, 28.0, color);
+        }
+
+        let y = screen_height() - 170.0;
+        let selected = self
+            .profiles
+            .get(self.profile_field)
+            .map_or("", String::as_str);
+        match &self.profile_edit {
+            ProfileEdit::Create(typed) | ProfileEdit::Rename(typed) => {
+                let prompt = if matches!(self.profile_edit, ProfileEdit::Create(_)) {
+                    "New profile name:".to_string()
+                } else {
+                    format!("Rename {} to:", selected)
+                };
+                draw_centered_text(&prompt, y - 40.0, 25.0, WHITE);
+                let box_width = 320.0;
+                let box_x = screen_width() / 2.0 - box_width / 2.0;
+                draw_rectangle(
+                    box_x,
+                    y - 30.0,
+                    box_width,
+                    44.0,
+                    Color::from_rgba(0, 0, 0, 160),
+                );
+                draw_rectangle_lines(box_x, y - 30.0, box_width, 44.0, 3.0, WHITE);
+                let cursor = if (get_time() * 2.0) as i64 % 2 == 0 {
+                    "_"
+                } else {
+                    " "
+                };
+                let text = format!("{}{}", typed, cursor);
+                draw_centered_text(&text, y + 2.0, 32.0, WHITE);
+            }
+            ProfileEdit::Delete => {
+                let text = format!("Delete {} and all its high scores?", selected);
+                draw_centered_text(&text, y, 28.0, ORANGE);
+            }
+            ProfileEdit::None => {}
+        }
+        if let Some(error) = &self.profile_error {
+            draw_centered_text(error, y + 40.0, 22.0, RED);
+        }
+
+        let hints: [&str; 2] = match self.profile_edit {
+            ProfileEdit::Create(_) | ProfileEdit::Rename(_) => {
+                ["Type a name", "ENTER - Confirm    ESC - Cancel"]
+            }
+            ProfileEdit::Delete => ["This can't be undone", "Y - Delete    N - Keep"],
+            ProfileEdit::None => [
+                "UP/DOWN - Select    ENTER - Play    N - New    R - Rename    D - Delete",
+                "G - Guided unlocks    ESC - Back",
+            ],
+        };
+        for (i, hint) in hints.iter().enumerate() {
+            draw_centered_text(hint, screen_height() - 80.0 + i as f32 * 30.0, 22.0, WHITE);
+        }
+    }
+
+    fn draw_enter_code(&self) {
+        draw_rectangle(
+            0.0,
+            0.0,
+            screen_width(),
+            screen_height(),
+            Color::from_rgba(0, 0, 0, 160),
+        );
+
+        draw_centered_text("ENTER CHALLENGE CODE", 180.0, 50.0, YELLOW);
+
+        let box_width = 360.0;
+        let box_x = screen_width() / 2.0 - box_width / 2.0;
+        draw_rectangle(
+            box_x,
+            230.0,
+            box_width,
+            60.0,
+            Color::from_rgba(0, 0, 0, 160),
+        );
+        draw_rectangle_lines(box_x, 230.0, box_width, 60.0, 3.0, WHITE);
+
+        // Blinking cursor after whichever field is being typed into
+        let cursor = if (get_time() * 2.0) as i64 % 2 == 0 {
+            "_"
+        } else {
+            " "
+        };
+        let code_cursor = if self.practice_field { "" } else { cursor };
+        let text = format!("{}{}", self.code_input, code_cursor);
+        draw_centered_text(&text, 275.0, 45.0, WHITE);
+
+        if let Some(error) = &self.code_error {
+            draw_centered_text(error, 325.0, 28.0, RED);
+        }
+
+        let pipe_cursor = if self.practice_field { cursor } else { "" };
+        let practice = format!("Practice from pipe: {}{}", self.practice_input, pipe_cursor);
+        let practice_color = if self.practice_field { WHITE } else { GRAY };
+        draw_centered_text(&practice, 370.0, 28.0, practice_color);
+        draw_centered_text("(optional, practice runs are unranked)", 398.0, 20.0, GRAY);
+
+        draw_centered_text(
+            "ENTER - Start    TAB - Switch Field    ESC - Back",
+            450.0,
+            25.0,
+            WHITE,
+        );
+    }
+
+    fn draw_credits(&self) {
+        draw_rectangle(
+            0.0,
+            0.0,
+            screen_width(),
+            screen_height(),
+            Color::from_rgba(0, 0, 0, 160),
+        );
+
+        let scrolled = (get_time() - self.credits_started) as f32 * CREDITS_SCROLL_SPEED;
+        let version = env!("CARGO_PKG_VERSION");
+        for (i, line) in CREDITS.iter().enumerate() {
+            let y = screen_height() + i as f32 * CREDITS_LINE_HEIGHT - scrolled;
+            if y < -CREDITS_LINE_HEIGHT || y > screen_height() + CREDITS_LINE_HEIGHT {
+                continue;
+            }
+
+            let text = line.replace("{version}", version);
+            let (size, color) = if i == 0 {
+                (60.0, YELLOW)
+            } else if CREDITS[i - 1].is_empty() {
+                (32.0, GOLD)
+            } else {
+                (26.0, WHITE)
+            };
+            let width = measure_text(&text, None, size as u16, 1.0).width;
+            draw_text(&text, screen_width() / 2.0 - width / 2.0, y, size, color);
+        }
+
+        let hint = "Press any key to return";
+        let hint_width = measure_text(hint, None, 20, 1.0).width;
+        draw_text(
+            hint,
+            screen_width() / 2.0 - hint_width / 2.0,
+            screen_height() - 20.0,
+            20.0,
+            GRAY,
+        );
+    }
+
+    fn draw_custom_setup(&self) {
+        let title = "CUSTOM DIFFICULTY";
+        let title_width = measure_text(title, None, 60, 1.0).width;
+        draw_text(
+            title,
+            screen_width() / 2.0 - title_width / 2.0,
+            120.0,
+            60.0,
+            YELLOW,
+        );
+
+        let bar_x = screen_width() / 2.0 - 150.0;
+        let bar_width = 300.0;
+        let params = &self.settings.custom;
+
+        for (i, label) in DifficultyParams::FIELDS.iter().enumerate() {
+            let y = 200.0 + i as f32 * 80.0;
+            let selected = i == self.custom_field;
+            let color = if selected { GOLD } else { WHITE };
+
+            let value_text = format!("{}: {}", label, params.field_text(i));
+            draw_text(&value_text, bar_x, y, 30.0, color);
+
+            // Value bar
+            draw_rectangle(
+                bar_x,
+                y + 15.0,
+                bar_width,
+                20.0,
+                Color::from_rgba(0, 0, 0, 120),
+            );
+            draw_rectangle(
+                bar_x,
+                y + 15.0,
+                bar_width * params.field_fraction(i),
+                20.0,
+                if selected { GOLD } else { GREEN },
+            );
+            draw_rectangle_lines(bar_x, y + 15.0, bar_width, 20.0, 2.0, color);
+        }
+
+        let hints = [
+            "UP / DOWN - Select    LEFT / RIGHT - Adjust",
+            &format!(
+                "SPACE / ENTER - Start    ESC - Back    High Score: {}",
+                self.high_scores.custom
+            ),
+        ];
+        for (i, hint) in hints.iter().enumerate() {
+            let width = measure_text(hint, None, 25, 1.0).width;
+            draw_text(
+                hint,
+                screen_width() / 2.0 - width / 2.0,
+                screen_height() - 100.0 + i as f32 * 35.0,
+                25.0,
+                WHITE,
+            );
+        }
+    }
+
+    fn draw_settings(&self) {
+        draw_centered_text("SETTINGS", 120.0, 60.0, YELLOW);
+
+        // Keep the selected row in the middle where the list allows, with
+        // markers on the edge rows while there's more beyond them
+        let x = screen_width() / 2.0 - 170.0;
+        let field_count = Settings::FIELDS.len();
+        let visible = SETTINGS_VISIBLE_ROWS.min(field_count);
+        let first = self
+            .settings_field
+            .saturating_sub(visible / 2)
+            .min(field_count - visible);
+        let rows = Settings::FIELDS
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(visible);
+        for (row, (i, label)) in rows.enumerate() {
+            let y = 150.0 + row as f32 * 20.0;
+            let selected = i == self.settings_field;
+            let color = if selected { GOLD } else { WHITE };
+            if selected {
+                draw_text(">", x - 30.0, y, 20.0, GOLD);
+            } else if row == 0 && first > 0 {
+                draw_text("^", x - 30.0, y, 20.0, GRAY);
+            } else if row == visible - 1 && first + visible < field_count {
+                draw_text("v", x - 30.0, y, 20.0, GRAY);
+            }
+            let capturing = selected && self.rebinding.is_some();
+            let value = if capturing {
+                "press a key...".to_string()
+            } else if i == Settings::FOLLOWER_FIELD && !self.follower_unlocked() {
+                format!("Locked - score {} on any difficulty", FOLLOWER_UNLOCK_SCORE)
+            } else {
+                self.settings.field_text(i)
+            };
+            let text = format!("{}: {}", label, value);
+            draw_text(&text, x, y, 20.0, color);
+        }
+        if let Some(warning) = &self.rebind_warning {
+            draw_centered_text(warning, screen_height() - 120.0, 22.0, ORANGE);
+        }
+
+        let hints: [&str; 2] = if self.rebinding.is_some() {
+            ["Press the new key", "ESC - Cancel"]
+        } else {
+            [
+                "UP/DOWN - Select    LEFT/RIGHT - Change    ENTER - Rebind",
+                "ESC - Save and go back",
+            ]
+        };
+        for (i, hint) in hints.iter().enumerate() {
+            draw_centered_text(hint, screen_height() - 100.0 + i as f32 * 35.0, 25.0, WHITE);
+        }
+    }
+
+    // Shrinks from wide to hugging the bird as the next pipe gets closer, with
+    // the seconds left above it, so flaps can be planned ahead
+    fn draw_timing_ring(&self) {
+        let Some(frames) = self.sim.frames_to_next_pipe() else {
+            return;
+        };
+        if frames <= 0.0 || frames > TIMING_RING_FRAMES {
+            return;
+        }
+        let t = frames / TIMING_RING_FRAMES;
+        let (x, y) = (self.sim.bird.x, self.sim.bird.y);
+        let radius = BIRD_SIZE * (0.75 + 1.5 * t);
+        let color = Color::new(1.0, 1.0, 1.0, 0.6 * (1.0 - t) + 0.2);
+        draw_circle_lines(x, y, radius, 2.0, color);
+        let text = format!("{:.1}", frames * NOMINAL_FRAME_DT);
+        let width = measure_text(&text, None, 18, 1.0).width;
+        draw_text(&text, x - width / 2.0, y - radius - 6.0, 18.0, color);
+    }
+
+    // Faint dashed line from the bird to the middle of the next gap, with a
+    // tick at the bird's x so it's clear which way to steer
+    fn draw_gap_guide(&self) {
+        let next = self
+            .sim
+            .pipes
+            .iter()
+            .filter(|pipe| !pipe.scored && pipe.x + pipe.width > self.sim.bird.x)
+            .min_by(|a, b| a.x.total_cmp(&b.x));
+        let Some(pipe) = next else {
+            return;
+        };
+        let target_y = pipe.gap_y + pipe.gap_height / 2.0;
+        let color = Color::new(1.0, 1.0, 1.0, 0.35 * pipe.entrance_progress());
+
+        let mut x = self.sim.bird.x;
+        while x < pipe.x + pipe.width {
+            draw_line(
+                x,
+                target_y,
+                (x + 10.0).min(pipe.x + pipe.width),
+                target_y,
+                2.0,
+                color,
+            );
+            x += 20.0;
+        }
+
+        let tick = BIRD_SIZE * 0.75;
+        draw_line(
+            self.sim.bird.x - tick,
+            target_y,
+            self.sim.bird.x + tick,
+            target_y,
+            3.0,
+            color,
+        );
+        let direction = if target_y < self.sim.bird.y {
+            -1.0
+        } else {
+            1.0
+        };
+        if (target_y - self.sim.bird.y).abs() > pipe.gap_height / 4.0 {
+            let tip = self.sim.bird.y + direction * (BIRD_SIZE / 2.0 + 16.0);
+            draw_triangle(
+                vec2(self.sim.bird.x, tip),
+                vec2(self.sim.bird.x - 6.0, tip - direction * 8.0),
+                vec2(self.sim.bird.x + 6.0, tip - direction * 8.0),
+                color,
+            );
+        }
+    }
+
+    // Everything up to the ground is drawn in world space through the follow
+    // camera; the HUD goes on top in screen space. Drawing into a render target
+    // (for the pause blur) goes through the same cameras.
+    fn draw_playing(&self, target: Option<&RenderTarget>) {
+        let mut camera = screen_camera(self.camera_y(), target);
+        camera.target += self.shake_offset();
+        // Reduced motion keeps the pause after a crash but skips the zoom
+        if let Some(zoom) = self
+            .death_zoom_progress()
+            .filter(|_| !self.settings.reduced_motion)
+        {
+            let bird = vec2(self.sim.bird.x, self.sim.bird.y);
+            camera.target = camera.target.lerp(bird, zoom);
+            camera.zoom *= 1.0 + (DEATH_ZOOM - 1.0) * zoom;
+        }
+        set_camera(&camera);
+        let theme = self.theme();
+
+        // Draw pipes
+        let pipe_colors = PipeColors {
+            top: theme.pipe,
+            bottom: theme.pipe,
+            outline: theme.pipe_outline,
+            outline_width: theme.pipe_outline_width,
+        };
+        let bands = self.settings.quality.pipe_shade_bands();
+        for pipe in &self.sim.pipes {
+            draw_pipe(pipe, &pipe_colors, bands);
+            draw_bonus_gate(pipe);
+            draw_extra_jump_pickup(pipe, self.settings.quality.circle_sides());
+
+            if self.show_hitboxes {
+                // Draw pipe hitboxes, caps included
+                for hitbox in pipe.hitboxes() {
+                    draw_rectangle_lines(hitbox.x, hitbox.y, hitbox.w, hitbox.h, 2.0, RED);
+                }
+            }
+        }
+        for enemy in &self.sim.enemies {
+            draw_enemy(enemy, self.settings.quality.circle_sides());
+            if self.show_hitboxes {
+                let hitbox = enemy.hitbox();
+                draw_rectangle_lines(hitbox.x, hitbox.y, hitbox.w, hitbox.h, 2.0, RED);
+            }
+        }
+
+        // Draw particles, then the glowing ones together in one additive pass.
+        // Low quality blends them normally, in order with the rest.
+        let sides = self.settings.quality.circle_sides();
+        let glow_material = self
+            .glow_material
+            .as_ref()
+            .filter(|_| self.settings.quality != GraphicsQuality::Low);
+        for particle in &self.particles {
+            if !particle.glow || glow_material.is_none() {
+                particle.draw(sides);
+            }
+        }
+        if let Some(material) = glow_material {
+            gl_use_material(material);
+            for particle in self.particles.iter().filter(|particle| particle.glow) {
+                particle.draw(sides);
+            }
+            gl_use_default_material();
+        }
+
+        if self.settings.gap_guide && self.sim.score < GAP_GUIDE_MAX_SCORE {
+            self.draw_gap_guide();
+        }
+        if self.settings.assist && self.sim.score < TIMING_RING_MAX_SCORE {
+            self.draw_timing_ring();
+        }
+
+        // Draw bird, cycling colors while invincible and blinking when a
+        // power-up is about to run out
+        let tint = if self.sim.invincible() {
+            let expiring =
+                !self.sim.cheat_invincible && self.powerup_timer > 0.0 && self.powerup_timer < 60.0;
+            if expiring && (get_time() * 8.0) as i64 % 2 == 0 {
+                None
+            } else {
+                Some(macroquad::color::hsl_to_rgb(
+                    (get_time() * 1.5).fract() as f32,
+                    1.0,
+                    0.6,
+                ))
+            }
+        } else {
+            None
+        };
+        let stretch = if self.settings.reduced_motion {
+            1.0
+        } else {
+            bird_stretch(self.sim.bird.velocity)
+        };
+        if self.show_follower() {
+            self.draw_follower();
+        }
+        draw_bird(
+            &self.sim.bird,
+            theme.bird.unwrap_or(self.difficulty.bird_color()),
+            tint,
+            stretch,
+            self.settings.quality.circle_sides(),
+        );
+        draw_extra_jump_pips(&self.sim.bird, self.settings.quality.circle_sides());
+        draw_boost_meter(&self.sim.bird, self.sim.boost);
+
+        if self.show_hitboxes {
+            let bounds = self.sim.bird.get_bounds();
+            draw_rectangle_lines(bounds.x, bounds.y, bounds.w, bounds.h, 2.0, RED);
+        }
+        if self.show_trajectory {
+            self.draw_trajectory();
+        }
+
+        // Draw ground
+        let ground_y = self.sim.ground_y();
+        draw_rectangle(
+            0.0,
+            ground_y,
+            screen_width(),
+            self.sim.config.ground_height,
+            theme.ground,
+        );
+
+        // Draw grass on ground
+        for i in 0..((screen_width() / 20.0) as i32) {
+            draw_rectangle(i as f32 * 20.0, ground_y, 20.0, 10.0, theme.grass);
+        }
+
+        set_camera(&screen_camera(0.0, target));
+
+        if self.slow_motion_fade > 0.0 && !self.settings.reduced_motion {
+            draw_vignette(Color::from_rgba(20, 40, 110, 255), self.slow_motion_fade);
+        }
+        if !self.settings.reduced_motion {
+            self.draw_speed_lines();
+        }
+
+        self.draw_hud();
+        if let Some(goal) = self.challenge_goal {
+            self.draw_goal_progress(goal);
+        }
+        if self.sim.config.fuel {
+            self.draw_fuel_bar(20.0, screen_height() - 45.0);
+        }
+
+        if self.state == GameState::Playing && self.milestone_timer > 0.0 {
+            self.draw_milestone_banner();
+        }
+        if self.state == GameState::Playing && self.legendary_timer > 0.0 {
+            self.draw_legendary_banner();
+        }
+        if self.state == GameState::Playing && self.tutorial_fade > 0.0 {
+            self.draw_tutorial();
+        }
+        if self.state == GameState::Playing && self.hint_timer > 0.0 {
+            self.draw_control_hint();
+        }
+    }
+
+    // How to flap with the current controls
+    fn flap_prompt(&self) -> String {
+        let keys = self.settings.keys;
+        match (self.settings.keyboard_jump, self.settings.mouse_jump) {
+            (true, true) => format!("Press {} or click to flap", key_label(keys.jump)),
+            (false, true) => "Click to flap".to_string(),
+            _ => format!("Press {} to flap", key_label(keys.jump)),
+        }
+    }
+
+    // The flap reminder, small and just above the bird
+    fn draw_control_hint(&self) {
+        let alpha = (self.hint_timer / HINT_FADE_SECONDS).min(1.0) * 0.9;
+        let text = self.flap_prompt();
+        let width = measure_text(&text, None, 20, 1.0).width;
+        let x = (self.sim.bird.x - width / 2.0).max(10.0);
+        let y = self.sim.bird.y - self.camera_y() - BIRD_SIZE - 10.0;
+        draw_text(
+            &text,
+            x + 1.0,
+            y + 1.0,
+            20.0,
+            Color::new(0.0, 0.0, 0.0, alpha * 0.6),
+        );
+        draw_text(&text, x, y, 20.0, Color { a: alpha, ..WHITE });
+    }
+
+    // Prompts in screen space: flap first, then aim for the gap, with a reminder
+    // about pausing once the first pipe is behind
+    fn draw_tutorial(&self) {
+        let color = Color {
+            a: self.tutorial_fade,
+            ..WHITE
+        };
+        let accent = Color {
+            a: self.tutorial_fade,
+            ..GOLD
+        };
+        let camera_y = self.camera_y();
+        let keys = self.settings.keys;
+
+        let progress = format!(
+            "TUTORIAL - fly through {} pipes ({}/{})",
+            TUTORIAL_PIPES,
+            self.sim.score.min(TUTORIAL_PIPES),
+            TUTORIAL_PIPES
+        );
+        draw_centered_text(&progress, 130.0, 22.0, accent);
+
+        let bird = Vec2::new(self.sim.bird.x, self.sim.bird.y - camera_y);
+        if !self.tutorial_flapped {
+            let text = self.flap_prompt();
+            let label = bird + Vec2::new(50.0, -60.0);
+            draw_text(&text, label.x, label.y, 28.0, color);
+            draw_arrow(
+                label + Vec2::new(-5.0, 5.0),
+                bird + Vec2::new(14.0, -14.0),
+                accent,
+            );
+            return;
+        }
+
+        let next_pipe = self
+            .sim
+            .pipes
+            .iter()
+            .find(|pipe| pipe.x + pipe.width > self.sim.bird.x);
+        if let Some(pipe) = next_pipe {
+            let gap = Vec2::new(pipe.x, pipe.gap_y + pipe.gap_height / 2.0 - camera_y);
+            let tail = Vec2::new((gap.x - 120.0).max(10.0), gap.y);
+            let text = "Fly through the gap";
+            let width = measure_text(text, None, 28, 1.0).width;
+            let text_x = (tail.x - width / 2.0).max(10.0);
+            draw_text(text, text_x, tail.y - 16.0, 28.0, color);
+            draw_arrow(tail, gap - Vec2::new(8.0, 0.0), accent);
+        }
+
+        if self.sim.score >= 1 {
+            let text = format!("{} - pause any time", key_label(keys.pause));
+            draw_centered_text(&text, self.sim.ground_y() - camera_y - 30.0, 26.0, color);
+        }
+    }
+
+    // Draws the enabled HUD widgets, stacking those that share a corner. Bottom
+    // corners stack upwards from the bottom edge, above the fuel bar when it's
+    // shown.
+    fn draw_hud(&self) {
+        let layout = self.settings.hud;
+        let shown = layout
+            .widgets()
+            .into_iter()
+            .filter(|(_, widget)| widget.shown);
+        let corner_height = |corner: HudCorner| -> f32 {
+            shown
+                .clone()
+                .filter(|(_, widget)| widget.corner == corner)
+                .map(|(element, _)| self.hud_height(element))
+                .sum()
+        };
+
+        // Indexed by HudCorner
+        let corners = [
+            HudCorner::TopLeft,
+            HudCorner::TopRight,
+            HudCorner::BottomLeft,
+            HudCorner::BottomRight,
+        ];
+        let mut tops = corners.map(|corner| {
+            if !corner.is_bottom() {
+                0.0
+            } else if corner == HudCorner::BottomLeft && self.sim.config.fuel {
+                screen_height() - 70.0 - corner_height(corner)
+            } else {
+                screen_height() - 10.0 - corner_height(corner)
+            }
+        });
+        for (element, widget) in shown {
+            let x = if widget.corner.is_right() {
+                screen_width() - 200.0
+            } else {
+                20.0
+            };
+            let top = &mut tops[widget.corner as usize];
+            self.draw_hud_element(element, x, *top);
+            *top += self.hud_height(element);
+        }
+    }
+
+    // Notes drawn under the difficulty, in order
+    fn difficulty_notes(&self) -> Vec<(String, Color)> {
+        let mut notes = Vec::new();
+        if self.is_upside_down() {
+            notes.push(("Upside-Down".to_string(), VIOLET));
+        }
+        if let Some(pipe) = self.practice_pipe {
+            notes.push((format!("PRACTICE - Pipe {}", pipe), ORANGE));
+        } else if self.fixed_seed.is_some() {
+            notes.push(("Challenge".to_string(), SKYBLUE));
+        } else if let Some(stage) = self.ladder_stage {
+            let note = format!(
+                "Ladder {}/{}: reach {}",
+                stage + 1,
+                LADDER.len(),
+                LADDER_TARGET
+            );
+            notes.push((note, GOLD));
+        } else if let Some(level) = self.sim.level.as_ref() {
+            let name = if level.name.is_empty() {
+                "Level"
+            } else {
+                level.name.as_str()
+            };
+            let note = if level.looped {
+                name.to_string()
+            } else {
+                format!("{} - {}/{}", name, self.sim.total_passes, level.pipes.len())
+            };
+            notes.push((note, SKYBLUE));
+        } else if !self.is_ranked() {
+            notes.push(("Unranked".to_string(), ORANGE));
+        }
+        if self.adaptive {
+            let trend = if self.adaptive_offset < 0.0 {
+                "Adaptive (easier)"
+            } else if self.adaptive_offset > 0.0 {
+                "Adaptive (harder)"
+            } else {
+                "Adaptive"
+            };
+            notes.push((trend.to_string(), VIOLET));
+        }
+        notes
+    }
 
-        // Draw clouds
-        for i in 0..5 {
-            let x = (self.background_offset * 0.5 + i as f32 * 250.0) % (screen_width() + 100.0);
-            draw_circle(x, 100.0 + i as f32 * 50.0, 40.0, WHITE);
-            draw_circle(x + 30.0, 100.0 + i as f32 * 50.0, 50.0, WHITE);
-            draw_circle(x + 60.0, 100.0 + i as f32 * 50.0, 40.0, WHITE);
+    fn hud_height(&self, element: HudElement) -> f32 {
+        match element {
+            HudElement::Score => 60.0,
+            HudElement::Best if self.zen_mode => 0.0,
+            HudElement::Best => 40.0,
+            HudElement::Status => 30.0 + self.active_effects().len() as f32 * 34.0,
+            HudElement::Difficulty => 60.0 + self.difficulty_notes().len() as f32 * 30.0,
+            HudElement::Timer => 30.0,
         }
+    }
 
-        match self.state {
-            GameState::Menu => self.draw_menu(),
-            GameState::Playing => self.draw_playing(),
-            GameState::Paused => {
-                self.draw_playing();
-                self.draw_pause_overlay();
+    // Draws one HUD element with its top edge at y
+    fn draw_hud_element(&self, element: HudElement, x: f32, y: f32) {
+        let theme = self.theme();
+        match element {
+            // Just the mode's name in zen mode, where nothing counts
+            HudElement::Score => {
+                let score_text = if self.zen_mode {
+                    "Zen".to_string()
+                } else {
+                    format!("Score: {}", self.sim.score)
+                };
+                draw_text(&score_text, x, y + 50.0, 40.0, theme.text);
+                draw_text(&score_text, x - 2.0, y + 48.0, 40.0, BLACK);
             }
-            GameState::GameOver => {
-                self.draw_playing();
-                self.draw_game_over();
+            HudElement::Best => {
+                if self.zen_mode {
+                    return;
+                }
+                // Once this run beats the best the live score is the best, in gold
+                let (high_score, color) = if self.passed_best {
+                    (self.sim.score, GOLD)
+                } else {
+                    (self.best_score(), theme.accent)
+                };
+                let hs_text = format!("Best: {}", high_score);
+                draw_text(&hs_text, x, y + 30.0, 30.0, color);
+                let delta_x = x + measure_text(&hs_text, None, 30, 1.0).width + 15.0;
+                if self.best_flash_timer > 0.0 {
+                    self.draw_best_flash(delta_x, y + 30.0);
+                } else {
+                    self.draw_best_delta(delta_x, y + 30.0);
+                }
+            }
+            HudElement::Status => {
+                if self.debug_mode {
+                    let scale = self.debug_time_scale();
+                    let text = if scale == 1.0 {
+                        "DEBUG".to_string()
+                    } else {
+                        format!("DEBUG {}x", scale)
+                    };
+                    draw_text(&text, x, y + 20.0, 22.0, ORANGE);
+                }
+                self.draw_active_effects(x, y + 50.0);
+            }
+            HudElement::Difficulty => {
+                let diff_text = format!("Difficulty: {}", self.difficulty.name());
+                draw_text(&diff_text, x, y + 50.0, 25.0, WHITE);
+                for (i, (note, color)) in self.difficulty_notes().into_iter().enumerate() {
+                    draw_text(&note, x, y + 80.0 + i as f32 * 30.0, 25.0, color);
+                }
+            }
+            HudElement::Timer => {
+                let text = format!("Time: {:.1}s", self.run_elapsed);
+                draw_text(&text, x, y + 20.0, 25.0, WHITE);
             }
         }
     }
 
-    fn draw_menu(&self) {
-        let title = "FLAPPY BIRD";
-        let title_size = 80.0;
-        let title_width = measure_text(title, None, title_size as u16, 1.0).width;
-        
+    // Thin bar along the top edge filling towards a challenge's goal, with a
+    // tick at the best score on this difficulty if it falls short of the goal.
+    // It turns gold once the goal is reached.
+    fn draw_goal_progress(&self, goal: u32) {
+        let (x, y) = (20.0, 6.0);
+        let width = screen_width() - 40.0;
+        let height = 6.0;
+        let goal = goal.max(1) as f32;
+        let score = self.sim.score.max(0) as f32;
+        let done = score >= goal;
+        let fill = if done { GOLD } else { SKYBLUE };
+        draw_rectangle(x, y, width, height, Color::from_rgba(0, 0, 0, 150));
+        draw_rectangle(x, y, width * (score / goal).min(1.0), height, fill);
+        let best = self.best_score() as f32;
+        if best > 0.0 && best < goal {
+            let best_x = x + width * best / goal;
+            draw_line(best_x, y - 3.0, best_x, y + height + 3.0, 2.0, WHITE);
+        }
+        let label = if done {
+            "GOAL!".to_string()
+        } else {
+            format!("{} / {}", score, goal)
+        };
+        let label_width = measure_text(&label, None, 18, 1.0).width;
         draw_text(
-            title,
-            screen_width() / 2.0 - title_width / 2.0,
-            200.0,
-            title_size,
-            YELLOW,
+            &label,
+            x + width - label_width,
+            y + height + 16.0,
+            18.0,
+            fill,
         );
+    }
 
-        let instructions = vec![
-            "Press SPACE or ENTER to Start",
-            "",
-            "Select Difficulty:",
-            &format!("[1] Easy - High Score: {}", self.high_scores.easy),
-            &format!("[2] Medium - High Score: {}", self.high_scores.medium),
-            &format!("[3] Hard - High Score: {}", self.high_scores.hard),
-            &format!("[4] Extreme - High Score: {}", self.high_scores.extreme),
-            "",
-            &format!("Current: {}", self.difficulty.name()),
-            "",
-            "Controls:",
-            "SPACE / LEFT CLICK - Jump",
-            "ESC - Pause",
-            "H - Toggle Hitboxes (debug)",
-            "I - Toggle Invincibility (cheat)",
-            "S - Toggle Slow Motion (cheat)",
-        ];
-
-        let mut y = 300.0;
-        for line in instructions {
-            let size = if line.starts_with('[') || line.starts_with("Current:") {
-                30.0
-            } else if line.starts_with("Controls:") || line.starts_with("Select") {
-                35.0
-            } else {
-                25.0
-            };
-            
-            let color = if line.starts_with("Current:") {
-                GOLD
-            } else if line.contains("High Score") {
-                GREEN
-            } else {
-                WHITE
-            };
+    // Faint streaks rushing past near the top and bottom of the sky, longer and
+    // brighter the faster the pipes move. They follow simulated time, so they
+    // freeze with the run.
+    fn draw_speed_lines(&self) {
+        let speed = self.sim.effective_speed();
+        let strength = (speed - SPEED_LINES_START) / (SPEED_LINES_FULL - SPEED_LINES_START);
+        let strength = strength.clamp(0.0, 1.0);
+        let count = self.settings.quality.speed_lines();
+        if strength <= 0.0 || count == 0 {
+            return;
+        }
+        let width = screen_width();
+        let sky = screen_height() - self.sim.config.ground_height;
+        let length = 40.0 + 60.0 * strength;
+        let color = Color {
+            a: 0.25 * strength,
+            ..WHITE
+        };
+        for i in 0..count {
+            // Spread over the top and bottom fifth of the sky, alternating
+            let depth = 15.0 + (i * 37 % 7) as f32 / 7.0 * sky * 0.2;
+            let y = if i % 2 == 0 { depth } else { sky - depth };
+            let phase = (i * 131) as f32;
+            let x = (phase - self.sim.elapsed * speed * 4.0).rem_euclid(width + length) - length;
+            draw_line(x, y, x + length, y, 2.0, color);
+        }
+    }
 
-            let width = measure_text(line, None, size as u16, 1.0).width;
-            draw_text(
-                line,
-                screen_width() / 2.0 - width / 2.0,
+    // Tick marks split the bar into flaps; it turns red once there isn't
+    // enough for another
+    fn draw_fuel_bar(&self, x: f32, y: f32) {
+        let width = 160.0;
+        let height = 14.0;
+        let color = if self.sim.out_of_fuel() { RED } else { ORANGE };
+        draw_text("FUEL", x, y - 6.0, 20.0, WHITE);
+        draw_rectangle(x, y, width, height, Color::from_rgba(0, 0, 0, 150));
+        draw_rectangle(x, y, width * self.sim.fuel.clamp(0.0, 1.0), height, color);
+        let flaps = (1.0 / FUEL_PER_FLAP).round() as i32;
+        for i in 1..flaps {
+            let tick_x = x + width * i as f32 / flaps as f32;
+            draw_line(
+                tick_x,
                 y,
-                size,
-                color,
+                tick_x,
+                y + height,
+                2.0,
+                Color::from_rgba(0, 0, 0, 150),
             );
-            y += size + 10.0;
         }
-
-        // Draw animated bird
-        let bird_x = screen_width() / 2.0;
-        let bird_y = 250.0 + (get_time() * 2.0).sin() as f32 * 10.0;
-        draw_circle(bird_x, bird_y, BIRD_SIZE / 2.0, YELLOW);
-        draw_circle(bird_x + 8.0, bird_y - 5.0, 5.0, WHITE);
-        draw_circle(bird_x + 10.0, bird_y - 5.0, 3.0, BLACK);
+        draw_rectangle_lines(x, y, width, height, 2.0, WHITE);
     }
 
-    fn draw_playing(&self) {
-        // Draw pipes
-        for pipe in &self.pipes {
-            pipe.draw();
-            
-            if self.show_hitboxes {
-                // Draw pipe hitboxes
-                draw_rectangle_lines(pipe.x, 0.0, PIPE_WIDTH, pipe.gap_y, 2.0, RED);
-                let bottom_y = pipe.gap_y + pipe.gap_height;
-                draw_rectangle_lines(
-                    pipe.x,
-                    bottom_y,
-                    PIPE_WIDTH,
-                    screen_height() - bottom_y - GROUND_HEIGHT,
-                    2.0,
-                    RED,
-                );
-            }
-        }
+    // Gold strip across the upper part of the screen; the text pulses unless
+    // reduced motion is on, and everything fades over the last half second
+    fn draw_legendary_banner(&self) {
+        let alpha = (self.legendary_timer / 30.0).min(1.0);
+        let height = 80.0;
+        let y = screen_height() * 0.25;
+        let backdrop = Color::from_rgba(0, 0, 0, (150.0 * alpha) as u8);
+        draw_rectangle(0.0, y, screen_width(), height, backdrop);
+        draw_rectangle(0.0, y, screen_width(), 4.0, Color { a: alpha, ..GOLD });
+        draw_rectangle(
+            0.0,
+            y + height - 4.0,
+            screen_width(),
+            4.0,
+            Color { a: alpha, ..GOLD },
+        );
 
-        // Draw particles
-        for particle in &self.particles {
-            particle.draw();
-        }
+        let pulse = if self.settings.reduced_motion {
+            1.0
+        } else {
+            1.0 + (get_time() * 6.0).sin() as f32 * 0.06
+        };
+        let size = 60.0 * pulse;
+        let text = "LEGENDARY!";
+        let width = measure_text(text, None, size as u16, 1.0).width;
+        let baseline = y + height / 2.0 + size * 0.3;
+        draw_text(
+            text,
+            (screen_width() - width) / 2.0,
+            baseline,
+            size,
+            Color { a: alpha, ..GOLD },
+        );
+    }
 
-        // Draw bird
-        self.bird.draw();
-        
-        if self.show_hitboxes {
-            let bounds = self.bird.get_bounds();
-            draw_rectangle_lines(bounds.x, bounds.y, bounds.w, bounds.h, 2.0, RED);
-        }
+    // Full-width strip that slides in from the left, holds, then slides out to the
+    // right. Reduced motion shows it in place for the same time.
+    fn draw_milestone_banner(&self) {
+        let progress = 1.0 - self.milestone_timer / MILESTONE_BANNER_FRAMES;
+        let slide = 0.2;
+        let offset = if self.settings.reduced_motion {
+            0.0
+        } else if progress < slide {
+            (progress / slide - 1.0) * screen_width()
+        } else if progress > 1.0 - slide {
+            (progress - (1.0 - slide)) / slide * screen_width()
+        } else {
+            0.0
+        };
 
-        // Draw ground
+        let height = 56.0;
+        let y = screen_height() * 0.25;
         draw_rectangle(
-            0.0,
-            screen_height() - GROUND_HEIGHT,
+            offset,
+            y,
             screen_width(),
-            GROUND_HEIGHT,
-            Color::from_rgba(139, 69, 19, 255),
+            height,
+            Color::from_rgba(0, 0, 0, 150),
         );
-        
-        // Draw grass on ground
-        for i in 0..((screen_width() / 20.0) as i32) {
-            draw_rectangle(
-                i as f32 * 20.0,
-                screen_height() - GROUND_HEIGHT,
-                20.0,
-                10.0,
-                Color::from_rgba(34, 139, 34, 255),
-            );
-        }
-
-        // Draw score
-        let score_text = format!("Score: {}", self.score);
-        draw_text(&score_text, 20.0, 50.0, 40.0, WHITE);
-        draw_text(&score_text, 18.0, 48.0, 40.0, BLACK);
+        draw_rectangle(offset, y, screen_width(), 3.0, GOLD);
+        draw_rectangle(offset, y + height - 3.0, screen_width(), 3.0, GOLD);
+        let text = &self.milestone_text;
+        let size = 40.0;
+        let width = measure_text(text, None, size as u16, 1.0).width;
+        let text_x = offset + (screen_width() - width) / 2.0;
+        draw_text(text, text_x, y + height / 2.0 + 12.0, size, GOLD);
+    }
 
-        // Draw high score
-        let high_score = self.high_scores.get(self.difficulty);
-        let hs_text = format!("Best: {}", high_score);
-        draw_text(&hs_text, 20.0, 90.0, 30.0, GOLD);
+    // How far ahead of or behind the best run the current one is at this point
+    // in time. Only normal runs keep splits, so modified runs don't show it.
+    fn draw_best_delta(&self, x: f32, y: f32) {
+        if self.leaderboard() != Leaderboard::Normal || self.practice_pipe.is_some() {
+            return;
+        }
+        let Some(splits) = self.high_scores.best_splits(self.difficulty) else {
+            return;
+        };
+        let delta = self.sim.score - score_at(splits, self.sim.elapsed);
+        let color = match delta.cmp(&0) {
+            std::cmp::Ordering::Greater => GREEN,
+            std::cmp::Ordering::Less => RED,
+            std::cmp::Ordering::Equal => WHITE,
+        };
+        let text = format!("{:+} vs best", delta);
+        draw_text(&text, x, y, 25.0, color);
+    }
 
-        // Draw difficulty
-        let diff_text = format!("Difficulty: {}", self.difficulty.name());
-        draw_text(&diff_text, screen_width() - 200.0, 50.0, 25.0, WHITE);
+    // Pulses unless reduced motion is on, and fades over the last half second
+    fn draw_best_flash(&self, x: f32, y: f32) {
+        let alpha = (self.best_flash_timer / 30.0).min(1.0);
+        let pulse = if self.settings.reduced_motion {
+            1.0
+        } else {
+            1.0 + (get_time() * 8.0).sin() as f32 * 0.1
+        };
+        let size = 30.0 * pulse;
+        draw_text("BEST!", x + 2.0, y + 2.0, size, Color { a: alpha, ..BLACK });
+        draw_text("BEST!", x, y, size, Color { a: alpha, ..GOLD });
+    }
 
-        // Draw status indicators
-        if self.invincible {
-            draw_text("INVINCIBLE", screen_width() / 2.0 - 80.0, 50.0, 30.0, GOLD);
+    // Active effects as (label, color, remaining fraction); None means it lasts
+    // until toggled off
+    fn active_effects(&self) -> Vec<(&str, Color, Option<f32>)> {
+        let mut effects = Vec::new();
+        if self.sim.invincible() {
+            let remaining = (!self.sim.cheat_invincible && self.powerup_timer > 0.0)
+                .then(|| self.powerup_timer / POWERUP_DURATION);
+            effects.push(("INVINCIBLE", GOLD, remaining));
         }
         if self.slow_motion {
-            draw_text("SLOW MOTION", screen_width() / 2.0 - 90.0, 90.0, 30.0, SKYBLUE);
+            let remaining =
+                (self.slow_motion_timer > 0.0).then(|| self.slow_motion_timer / POWERUP_DURATION);
+            effects.push(("SLOW MOTION", SKYBLUE, remaining));
+        }
+        effects
+    }
+
+    // Stacks a labeled countdown bar per active effect in a column
+    fn draw_active_effects(&self, x: f32, y: f32) {
+        let bar_width = 120.0;
+        for (i, (label, color, remaining)) in self.active_effects().into_iter().enumerate() {
+            let row_y = y + i as f32 * 34.0;
+            draw_text(label, x, row_y, 20.0, color);
+            draw_rectangle(
+                x,
+                row_y + 6.0,
+                bar_width,
+                8.0,
+                Color::from_rgba(0, 0, 0, 120),
+            );
+            match remaining {
+                Some(fraction) => draw_rectangle(x, row_y + 6.0, bar_width * fraction, 8.0, color),
+                None => {
+                    draw_rectangle(x, row_y + 6.0, bar_width, 8.0, Color { a: 0.5, ..color });
+                }
+            }
         }
     }
 
     fn draw_pause_overlay(&self) {
+        // Blur the frozen scene by rendering it at low resolution and
+        // stretching it back over the screen with linear filtering
+        let blur_target = self
+            .blur_target
+            .as_ref()
+            .filter(|_| self.settings.quality != GraphicsQuality::Low);
+        if let Some(blur_target) = blur_target {
+            set_camera(&screen_camera(0.0, Some(blur_target)));
+            clear_background(SKYBLUE);
+            self.draw_background();
+            self.draw_playing(Some(blur_target));
+            set_camera(&screen_camera(0.0, self.frame_target()));
+
+            draw_texture_ex(
+                &blur_target.texture,
+                0.0,
+                0.0,
+                Color::new(0.8, 0.8, 0.8, 1.0),
+                DrawTextureParams {
+                    dest_size: Some(vec2(screen_width(), screen_height())),
+                    flip_y: true,
+                    ..Default::default()
+                },
+            );
+        }
+
         // Semi-transparent overlay
         draw_rectangle(
             0.0,
             0.0,
             screen_width(),
             screen_height(),
-            Color::from_rgba(0, 0, 0, 180),
+            Color::new(0.0, 0.0, 0.0, PAUSE_OVERLAY_ALPHA * self.overlay_darkness()),
         );
 
         let pause_text = "PAUSED";
@@ -730,6 +6045,15 @@ impl Game {
             YELLOW,
         );
 
+        if self.lag_paused {
+            draw_centered_text(
+                "Slow frame detected",
+                screen_height() / 2.0 + 5.0,
+                25.0,
+                ORANGE,
+            );
+        }
+
         let resume = "Press SPACE to Resume";
         let resume_width = measure_text(resume, None, 30, 1.0).width;
         draw_text(
@@ -758,20 +6082,66 @@ impl Game {
             0.0,
             screen_width(),
             screen_height(),
-            Color::from_rgba(0, 0, 0, 200),
+            Color::new(
+                0.0,
+                0.0,
+                0.0,
+                GAME_OVER_OVERLAY_ALPHA * self.overlay_darkness(),
+            ),
         );
 
-        let game_over = "GAME OVER";
+        let (game_over, title_color) = if self.ladder_cleared {
+            ("LADDER CLEARED", GOLD)
+        } else if self.sim.completed {
+            ("LEVEL COMPLETE", GREEN)
+        } else {
+            ("GAME OVER", RED)
+        };
         let width = measure_text(game_over, None, 80, 1.0).width;
         draw_text(
             game_over,
             screen_width() / 2.0 - width / 2.0,
             screen_height() / 2.0 - 100.0,
             80.0,
-            RED,
+            title_color,
         );
+        if self.sim.completed {
+            let summary = format!("Finished in {:.1}s", self.run_elapsed);
+            draw_centered_text(&summary, screen_height() / 2.0 - 60.0, 25.0, LIGHTGRAY);
+        }
 
-        let score_text = format!("Score: {}", self.score);
+        if let Some(cause) = self.death_cause {
+            let unranked = if self.is_ranked() { "" } else { " (unranked)" };
+            let gates = match self.sim.bonus_gates {
+                0 => String::new(),
+                1 => ", 1 bonus gate".to_string(),
+                n => format!(", {} bonus gates", n),
+            };
+            let best_time = self.high_scores.best_time.get(self.difficulty);
+            let record = if self.new_best_time {
+                " (longest yet!)".to_string()
+            } else if best_time > 0.0 {
+                format!(" (best {:.1}s)", best_time)
+            } else {
+                String::new()
+            };
+            let ladder = match self.ladder_stage {
+                Some(stage) => format!(" on ladder stage {}/{}", stage + 1, LADDER.len()),
+                None => String::new(),
+            };
+            let summary = format!(
+                "{}{} after {:.1}s{}{}{}",
+                cause.description(),
+                ladder,
+                self.run_elapsed,
+                record,
+                gates,
+                unranked
+            );
+            draw_centered_text(&summary, screen_height() / 2.0 - 60.0, 25.0, LIGHTGRAY);
+        }
+
+        let score_text = format!("Score: {}", self.sim.score);
         let score_width = measure_text(&score_text, None, 40, 1.0).width;
         draw_text(
             &score_text,
@@ -781,30 +6151,78 @@ impl Game {
             WHITE,
         );
 
-        let high_score = self.high_scores.get(self.difficulty);
-        let hs_text = if self.score > high_score {
-            format!("NEW HIGH SCORE!")
+        let high_score = self.best_score();
+        let hs_text = if self.new_high_score {
+            "NEW HIGH SCORE!".to_string()
         } else {
             format!("High Score: {}", high_score)
         };
-        let hs_width = measure_text(&hs_text, None, 35, 1.0).width;
-        let hs_color = if self.score > high_score { GOLD } else { YELLOW };
+        // The record line pulses while the celebration is running
+        let (hs_size, hs_color) = if self.celebration_timer > 0.0 {
+            let pulse = (get_time() * 8.0).sin() as f32;
+            let color = if pulse > 0.0 { GOLD } else { YELLOW };
+            (35.0 * (1.0 + 0.12 * pulse), color)
+        } else if self.new_high_score {
+            (35.0, GOLD)
+        } else {
+            (35.0, YELLOW)
+        };
+        let hs_width = measure_text(&hs_text, None, hs_size as u16, 1.0).width;
         draw_text(
             &hs_text,
             screen_width() / 2.0 - hs_width / 2.0,
             screen_height() / 2.0 + 30.0,
-            35.0,
+            hs_size,
             hs_color,
         );
 
-        let retry = "Press SPACE to Retry";
+        // Star rating, popping in one star at a time
+        let stars = self.sim.star_rating();
+        let elapsed = self.game_over_elapsed();
+        for i in 0..3 {
+            let appear = (elapsed - 0.3 - i as f32 * 0.25) / 0.2;
+            if appear <= 0.0 {
+                continue;
+            }
+            // Overshoot slightly before settling at full size
+            let scale = if appear < 1.0 { appear * 1.3 } else { 1.0 };
+            let color = if (i as u32) < stars { GOLD } else { DARKGRAY };
+            draw_star(
+                screen_width() / 2.0 + (i as f32 - 1.0) * 45.0,
+                screen_height() / 2.0 + 65.0,
+                16.0 * scale,
+                color,
+            );
+        }
+        if stars == 3 && self.sim.clean_passes == self.sim.total_passes {
+            draw_text(
+                "PERFECT RUN!",
+                screen_width() / 2.0 + 80.0,
+                screen_height() / 2.0 + 72.0,
+                20.0,
+                GOLD,
+            );
+        }
+
+        // Prompts only appear once they'd actually do something
+        let prompt_alpha =
+            ((elapsed - self.retry_lockout()) / RETRY_PROMPT_FADE_SECONDS).clamp(0.0, 1.0);
+        let prompt_color = Color {
+            a: prompt_alpha,
+            ..WHITE
+        };
+        let retry = if self.ladder_stage.is_some() {
+            "Press SPACE to Climb Again from Easy"
+        } else {
+            "Press SPACE to Retry"
+        };
         let retry_width = measure_text(retry, None, 30, 1.0).width;
         draw_text(
             retry,
             screen_width() / 2.0 - retry_width / 2.0,
             screen_height() / 2.0 + 100.0,
             30.0,
-            WHITE,
+            prompt_color,
         );
 
         let menu = "Press Q for Main Menu";
@@ -814,31 +6232,536 @@ impl Game {
             screen_width() / 2.0 - menu_width / 2.0,
             screen_height() / 2.0 + 150.0,
             25.0,
-            WHITE,
+            prompt_color,
+        );
+
+        let code = if self.adaptive || !self.is_ranked() {
+            None
+        } else {
+            encode_challenge(&self.current_challenge())
+        };
+        if let Some(code) = code {
+            let code_text = format!("Challenge Code: {}", code);
+            draw_centered_text(&code_text, screen_height() / 2.0 + 220.0, 25.0, SKYBLUE);
+            let goal_text = match self.share_goal {
+                Some(goal) => format!("G - Goal: {} pipes", goal),
+                None => "G - Goal: Endless".to_string(),
+            };
+            draw_centered_text(&goal_text, screen_height() / 2.0 + 245.0, 20.0, SKYBLUE);
+        }
+
+        if self.new_high_score {
+            let credits = "Press C for Credits";
+            let credits_width = measure_text(credits, None, 25, 1.0).width;
+            draw_text(
+                credits,
+                screen_width() / 2.0 - credits_width / 2.0,
+                screen_height() / 2.0 + 185.0,
+                25.0,
+                Color {
+                    a: prompt_alpha,
+                    ..GOLD
+                },
+            );
+        }
+    }
+}
+
+// Faint full-screen tint that darkens towards the edges, scaled by strength
+fn draw_vignette(color: Color, strength: f32) {
+    let (w, h) = (screen_width(), screen_height());
+    draw_rectangle(
+        0.0,
+        0.0,
+        w,
+        h,
+        Color {
+            a: 0.12 * strength,
+            ..color
+        },
+    );
+
+    // Overlapping bands stack up, so the outermost edge ends up darkest
+    let bands = 8;
+    let band = 12.0;
+    let band_color = Color {
+        a: 0.08 * strength,
+        ..color
+    };
+    for i in 0..bands {
+        let thickness = (bands - i) as f32 * band;
+        draw_rectangle(0.0, 0.0, w, thickness, band_color);
+        draw_rectangle(0.0, h - thickness, w, thickness, band_color);
+        draw_rectangle(0.0, thickness, thickness, h - 2.0 * thickness, band_color);
+        draw_rectangle(
+            w - thickness,
+            thickness,
+            thickness,
+            h - 2.0 * thickness,
+            band_color,
         );
     }
 }
 
+// Whether a frame of dt seconds during a run took so long that it pauses the
+// run. The frame right after unpausing spans the pause, so it never does.
+fn is_stalled_frame(dt: f32, resumed_from_pause: bool) -> bool {
+    dt > LAG_PAUSE_SECONDS && !resumed_from_pause
+}
+
+// Clouds wrap around once they've scrolled their own width past the left edge
+fn cloud_wrap_width() -> f32 {
+    screen_width() + CLOUD_WIDTH
+}
+
+// Slider values are built up from repeated float steps, so compare loosely
+fn approx_eq(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-3
+}
+
+// Pixel-space camera over a window-sized view whose top edge is at world y,
+// drawing into target when one is given
+fn screen_camera(y: f32, target: Option<&RenderTarget>) -> Camera2D {
+    let mut camera =
+        Camera2D::from_display_rect(Rect::new(0.0, y, screen_width(), screen_height()));
+    camera.render_target = target.cloned();
+    camera
+}
+
+// Falls back to empty scores, with a warning for the top of the screen, when
+// the profile's file can't be read, moving the file aside first. The bool is
+// set if that failed too, so the fallback must never be saved.
+fn load_high_scores(profile: &str) -> (HighScores, Option<String>, bool) {
+    match HighScores::load(profile) {
+        Ok(scores) => (scores, None, false),
+        Err(err) => {
+            let (note, locked) = backup_note(HighScores::back_up(profile));
+            let warning = format!("Scores couldn't be loaded: {}{}", err, note);
+            (HighScores::default(), Some(warning), locked)
+        }
+    }
+}
+
+// How a load warning ends, given how moving the unreadable file aside went,
+// and whether the file now has to be left alone
+fn backup_note(backup: io::Result<()>) -> (String, bool) {
+    match backup {
+        Ok(()) => (" (the old file was kept as a .bak)".to_string(), false),
+        Err(err) => (format!("; it won't be saved over ({})", err), true),
+    }
+}
+
+fn draw_arrow(from: Vec2, to: Vec2, color: Color) {
+    let direction = (to - from).normalize_or_zero();
+    let normal = Vec2::new(-direction.y, direction.x);
+    let base = to - direction * 14.0;
+    draw_line(from.x, from.y, base.x, base.y, 4.0, color);
+    draw_triangle(to, base + normal * 8.0, base - normal * 8.0, color);
+}
+
+fn draw_centered_text(text: &str, y: f32, size: f32, color: Color) {
+    let width = measure_text(text, None, size as u16, 1.0).width;
+    draw_text(text, screen_width() / 2.0 - width / 2.0, y, size, color);
+}
+
+fn draw_star(x: f32, y: f32, radius: f32, color: Color) {
+    let point = |i: usize| {
+        let r = if i.is_multiple_of(2) {
+            radius
+        } else {
+            radius * 0.45
+        };
+        let angle = -std::f32::consts::FRAC_PI_2 + i as f32 * std::f32::consts::PI / 5.0;
+        Vec2::new(x + r * angle.cos(), y + r * angle.sin())
+    };
+    let center = Vec2::new(x, y);
+    for i in 0..10 {
+        draw_triangle(center, point(i), point((i + 1) % 10), color);
+    }
+}
+
+// Runs before main, so it reads the window mode straight from settings.json.
+// Layout is computed from screen_width() / screen_height() every frame, so a
+// borderless window at the desktop's resolution lays itself out the same way.
+// macroquad's own shaders with the blend changed to source-alpha-plus-one, so
+// each particle adds its light to whatever is underneath
+const GLOW_VERTEX_SHADER: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+
+varying lowp vec2 uv;
+varying lowp vec4 color;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    color = color0 / 255.0;
+    uv = texcoord;
+}"#;
+
+const GLOW_FRAGMENT_SHADER: &str = r#"#version 100
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+
+void main() {
+    gl_FragColor = color * texture2D(Texture, uv);
+}"#;
+
+// level.json, if there is one that parses. Problems go to stderr and the game
+// carries on without a level.
+fn load_level() -> Option<Level> {
+    match Level::load() {
+        Ok(level) => level,
+        Err(err) => {
+            eprintln!("Couldn't load level.json: {}", err);
+            None
+        }
+    }
+}
+
+fn load_glow_material() -> Option<Material> {
+    let pipeline_params = PipelineParams {
+        color_blend: Some(BlendState::new(
+            Equation::Add,
+            BlendFactor::Value(BlendValue::SourceAlpha),
+            BlendFactor::One,
+        )),
+        ..Default::default()
+    };
+    let shader = ShaderSource::Glsl {
+        vertex: GLOW_VERTEX_SHADER,
+        fragment: GLOW_FRAGMENT_SHADER,
+    };
+    let params = MaterialParams {
+        pipeline_params,
+        ..Default::default()
+    };
+    match load_material(shader, params) {
+        Ok(material) => Some(material),
+        Err(err) => {
+            eprintln!(
+                "Glowing particles are unavailable, drawing them normally: {}",
+                err
+            );
+            None
+        }
+    }
+}
+
+// --trace logs every simulated frame to trace.log, one line each:
+// `elapsed inputs bird_y velocity pipes score`, where inputs is J (jump key)
+// and M (mouse) or - for each. Every run starts with a `# seed N Difficulty`
+// line, so diffing the traces of two runs on the same seed shows the first
+// frame they disagree on.
+fn open_trace() -> Option<io::BufWriter<fs::File>> {
+    if !std::env::args().any(|arg| arg == "--trace") {
+        return None;
+    }
+    match fs::File::create(save_path("trace.log")) {
+        Ok(file) => Some(io::BufWriter::new(file)),
+        Err(err) => {
+            eprintln!("Couldn't create trace.log: {}", err);
+            None
+        }
+    }
+}
+
+// Tracing stops at the first failed write rather than reporting every frame
+fn write_trace(trace: &mut Option<io::BufWriter<fs::File>>, line: std::fmt::Arguments) {
+    if let Some(writer) = trace {
+        if let Err(err) = writer.write_fmt(line) {
+            eprintln!("Couldn't write trace.log, tracing stopped: {}", err);
+            *trace = None;
+        }
+    }
+}
+
+fn has_window_size() -> bool {
+    screen_width() > 0.0 && screen_height() > 0.0
+}
+
 fn window_conf() -> Conf {
+    let settings = Settings::load().unwrap_or_default();
+    let window_mode = settings.window_mode;
+    let (width, height) = settings.window_size();
+    let borderless = window_mode == WindowMode::Borderless;
+    if borderless
+        && cfg!(any(
+            target_arch = "wasm32",
+            target_os = "android",
+            target_os = "ios"
+        ))
+    {
+        eprintln!("Borderless window mode isn't supported on this platform; using a normal window");
+    }
     Conf {
         window_title: "Flappy Bird - Rust Edition".to_owned(),
-        window_width: 800,
-        window_height: 600,
+        window_width: width,
+        window_height: height,
         window_resizable: false,
+        fullscreen: borderless,
         ..Default::default()
     }
 }
 
 #[macroquad::main(window_conf)]
 async fn main() {
-    let mut game = Game::new();
+    match DifficultyTable::load() {
+        Ok(table) => table.install(),
+        Err(err) => {
+            eprintln!(
+                "Couldn't load difficulties.json, using the built-in difficulties: {}",
+                err
+            )
+        }
+    }
+    let audio_enabled = audio_available().await;
+    let mut loading = LoadingScreen::new(audio_enabled);
+    loading.present().await;
+    let audio = AudioManager::load(audio_enabled, &mut loading).await;
+    let music = Music::load(audio_enabled, &mut loading).await;
+    let mut game = Game::new(audio, music);
+    let volume = game.settings.music_volume.get(game.difficulty);
+    game.music.play_for(game.difficulty, volume);
+    // Handle the window's close button ourselves so saves get flushed first
+    prevent_quit();
 
-    loop {
+    while !game.should_quit {
         clear_background(SKYBLUE);
-        
+
         game.update();
         game.draw();
+        game.record_clip_frame();
+        if game.reload_requested {
+            game.hot_reload().await;
+        }
 
         next_frame().await
     }
+
+    game.shutdown();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Stands in for the window under test: 800x600, every key up, and a clock
+    // that only moves when a test sets it
+    pub(super) mod window {
+        use macroquad::prelude::KeyCode;
+        use std::cell::Cell;
+
+        thread_local! {
+            static TIME: Cell<f64> = const { Cell::new(0.0) };
+        }
+
+        pub fn set_time(seconds: f64) {
+            TIME.with(|time| time.set(seconds));
+        }
+
+        pub fn get_time() -> f64 {
+            TIME.with(Cell::get)
+        }
+
+        pub fn screen_width() -> f32 {
+            800.0
+        }
+
+        pub fn screen_height() -> f32 {
+            600.0
+        }
+
+        pub fn is_key_pressed(_key: KeyCode) -> bool {
+            false
+        }
+
+        pub fn is_key_down(_key: KeyCode) -> bool {
+            false
+        }
+
+        pub fn is_quit_requested() -> bool {
+            false
+        }
+    }
+
+    // A game on the default settings with nothing to draw into or play on.
+    // Saves are locked so nothing a test does is written next to the binary.
+    fn headless_game() -> Game {
+        let mut game = Game::without_graphics(AudioManager::silent(), Music::silent());
+        game.sized = true;
+        game.scores_locked = true;
+        game.settings_locked = true;
+        game.state = GameState::Menu;
+        game
+    }
+
+    #[test]
+    fn gif_clips_decode_to_the_recorded_frames() {
+        let (width, height) = (GIF_WIDTH as u16, 3);
+        let flat = vec![clip_palette_index(255, 255, 255); GIF_WIDTH * 3];
+        let noisy: Vec<u8> = (0..GIF_WIDTH * 3).map(|i| (i * 37 % 252) as u8).collect();
+        let frames = vec![flat, noisy];
+        let gif = gif_bytes(width, height, &frames, GIF_FRAME_DELAY).unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(gif.as_slice()).unwrap();
+        assert_eq!(decoder.global_palette(), Some(clip_palette().as_slice()));
+        for expected in &frames {
+            let frame = decoder.read_next_frame().unwrap().unwrap();
+            assert_eq!(
+                (frame.width, frame.height, frame.delay),
+                (width, height, GIF_FRAME_DELAY)
+            );
+            assert_eq!(&*frame.buffer, expected.as_slice());
+        }
+        assert!(decoder.read_next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn particles_never_pile_up_past_the_cap() {
+        let mut particles = Vec::new();
+        let mut rng = StdRng::seed_from_u64(1);
+        for i in 0..200 {
+            let color = if i % 2 == 0 { GOLD } else { WHITE };
+            let (origin, count, shape) = (vec2(100.0, 100.0), 1 + i % 40, ParticleShape::Star);
+            launch_particles(&mut particles, &mut rng, origin, color, count, 1.0, shape);
+            assert!(particles.len() <= MAX_PARTICLES);
+            // The burst just launched always survives the trim
+            assert!(particles.iter().rev().take(count).all(|p| p.color == color));
+            update_particles(&mut particles, 1.0);
+        }
+        // One burst bigger than the cap on its own is trimmed too
+        let (count, shape) = (MAX_PARTICLES * 2, ParticleShape::Square);
+        launch_particles(
+            &mut particles,
+            &mut rng,
+            Vec2::ZERO,
+            GOLD,
+            count,
+            1.0,
+            shape,
+        );
+        assert_eq!(particles.len(), MAX_PARTICLES);
+    }
+
+    // Where a scripted run's particles are after 600 frames: a burst from
+    // the bird every 20 frames, all moving at 60fps
+    fn particles_after_a_run(run_seed: u32) -> Vec<Particle> {
+        let mut particles = Vec::new();
+        let mut rng = particle_rng(run_seed);
+        for frame in 0..600 {
+            if frame % 20 == 0 {
+                let origin = vec2(150.0, 300.0 + frame as f32 % 50.0);
+                let shape = ParticleShape::Feather;
+                launch_particles(&mut particles, &mut rng, origin, WHITE, 5, 1.0, shape);
+            }
+            update_particles(&mut particles, 1.0);
+        }
+        particles
+    }
+
+    #[test]
+    fn particles_replay_exactly_from_the_run_seed() {
+        let bits = |particles: &[Particle]| -> Vec<[u32; 6]> {
+            particles
+                .iter()
+                .map(|p| [p.x, p.y, p.vx, p.vy, p.life, p.size].map(f32::to_bits))
+                .collect()
+        };
+        let first = particles_after_a_run(42);
+        assert!(!first.is_empty());
+        assert_eq!(bits(&first), bits(&particles_after_a_run(42)));
+        assert_eq!(first, particles_after_a_run(42));
+        assert_ne!(bits(&first), bits(&particles_after_a_run(43)));
+    }
+
+    #[test]
+    fn stalled_frames_pause_instead_of_stepping() {
+        for dt in [0.3, 2.0, 3600.0, f32::INFINITY] {
+            assert!(is_stalled_frame(dt, false), "{}", dt);
+            // unless the frame is the one spanning a pause
+            assert!(!is_stalled_frame(dt, true));
+        }
+        // Merely slow frames are still simulated, as a single step
+        for dt in [NOMINAL_FRAME_DT, MAX_FRAME_DT, LAG_PAUSE_SECONDS] {
+            assert!(!is_stalled_frame(dt, false));
+        }
+
+        let mut game = headless_game();
+        game.start_run(false);
+        game.sim.cheat_invincible = true;
+        let mut frame = 0;
+        while game.sim.pipes.is_empty() {
+            frame += 1;
+            assert!(frame < 1000, "no pipe ever spawned");
+            game.tick(
+                InputState {
+                    frame,
+                    ..InputState::default()
+                },
+                NOMINAL_FRAME_DT,
+            );
+        }
+        let bird_y = game.sim.bird.y;
+        let pipe_xs: Vec<f32> = game.sim.pipes.iter().map(|pipe| pipe.x).collect();
+        let stalled = InputState {
+            frame: frame + 1,
+            ..InputState::default()
+        };
+        game.tick(stalled, LAG_PAUSE_SECONDS + 0.5);
+        assert_eq!(game.state, GameState::Paused);
+        assert!(game.lag_paused);
+        assert_eq!(game.sim.bird.y, bird_y);
+        assert_eq!(
+            game.sim.pipes.iter().map(|pipe| pipe.x).collect::<Vec<_>>(),
+            pipe_xs
+        );
+    }
+
+    #[test]
+    fn a_retry_press_cant_flap_the_new_run() {
+        let press = InputState {
+            frame: 7,
+            jump: true,
+            confirm: true,
+            ..InputState::default()
+        };
+        let mut retry = press;
+        retry.consume();
+        assert!(!retry.jump && !retry.confirm);
+        assert_eq!(retry.frame, 7);
+
+        // Ticking the new run with the frame that retried it does nothing
+        let again = press.unspent(Some(7));
+        assert!(!again.jump && !again.confirm && !again.any);
+        // but the next frame's flap goes through
+        let next = InputState { frame: 8, ..press };
+        assert!(next.unspent(Some(7)).jump);
+        assert!(press.unspent(None).jump);
+
+        // A real game over, past the lockout, retried and ticked again with
+        // the same frame's press
+        let mut game = headless_game();
+        game.start_run(false);
+        game.die(DeathCause::Ground);
+        game.death_timer = 0.0;
+        window::set_time(game.game_over_time + game.retry_lockout() as f64 + 1.0);
+        game.tick(press, NOMINAL_FRAME_DT);
+        assert_eq!(game.state, GameState::Playing);
+        let flap = game.sim.config.jump_strength;
+        game.tick(press, NOMINAL_FRAME_DT);
+        assert_eq!(game.state, GameState::Playing);
+        assert!(game.sim.bird.velocity >= 0.0, "{}", game.sim.bird.velocity);
+        assert!(!approx_eq(game.sim.bird.velocity, flap));
+        // The next frame's press does flap
+        game.tick(next, NOMINAL_FRAME_DT);
+        assert!(game.sim.bird.velocity < 0.0);
+    }
 }
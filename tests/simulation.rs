@@ -0,0 +1,141 @@
+// Drives the simulation through the library's public API only, the way a bot
+// or an outside tool would, with no window
+
+use flappy_bird::*;
+
+// A Medium run in the default 800x600 world with gravity off, so the bird
+// hovers at y 300 until a test moves it. Pipes still spawn on the usual timer,
+// which isn't reachable from out here, so tests that place their own pipes
+// step it for less than a spawn interval.
+fn hovering() -> Simulation {
+    let mut config = SimConfig::new(Difficulty::Medium, 1, 800.0, 600.0);
+    config.gravity = 0.0;
+    Simulation::new(config)
+}
+
+// A solid pipe at x with its gap from gap_y to gap_y + 150, as if it spawned
+// ahead of the bird
+fn pipe(x: f32, gap_y: f32) -> Pipe {
+    let mut pipe = Pipe::with_gap(x, gap_y, 150.0, 520.0);
+    pipe.age = PIPE_ENTRANCE_FRAMES;
+    pipe.was_ahead = true;
+    pipe
+}
+
+#[test]
+fn check_collision_names_what_the_bird_is_touching() {
+    let mut sim = hovering();
+    assert_eq!(sim.check_collision(), None);
+
+    // The bird at y 300 is in the top half of a pipe whose gap starts at 350
+    sim.pipes.push(pipe(140.0, 350.0));
+    assert_eq!(sim.check_collision(), Some(DeathCause::Pipe));
+    // A pipe still sliding in isn't solid yet
+    sim.pipes[0].age = 0.0;
+    assert_eq!(sim.check_collision(), None);
+    sim.pipes.clear();
+
+    sim.enemies.push(Enemy::new(150.0, 300.0));
+    assert_eq!(sim.check_collision(), Some(DeathCause::Enemy));
+    sim.enemies.clear();
+
+    sim.bird.y = BIRD_SIZE / 2.0;
+    assert_eq!(sim.check_collision(), Some(DeathCause::Ceiling));
+    sim.bird.y = sim.ground_y() - BIRD_SIZE / 2.0;
+    assert_eq!(sim.check_collision(), Some(DeathCause::Ground));
+
+    // Asking doesn't end the run; stepping does, for the same reason
+    assert!(sim.death.is_none());
+    sim.cheat_invincible = true;
+    assert_eq!(sim.check_collision(), None);
+    sim.cheat_invincible = false;
+    assert_eq!(sim.step(1.0), vec![SimEvent::Died(DeathCause::Ground)]);
+    assert_eq!(sim.death, Some(DeathCause::Ground));
+
+    let mut zen = hovering();
+    zen.config.zen = true;
+    zen.pipes.push(pipe(140.0, 350.0));
+    assert_eq!(zen.check_collision(), None);
+}
+
+#[test]
+fn pipes_to_score_is_what_the_next_step_scores() {
+    let mut sim = hovering();
+    // The trailing edge of the first is already behind the bird at 150
+    sim.pipes.push(pipe(80.0, 225.0));
+    sim.pipes.push(pipe(400.0, 225.0));
+    assert!(sim.pipes[0].is_passed(sim.bird.x));
+    assert!(!sim.pipes[1].is_passed(sim.bird.x));
+    assert_eq!(sim.pipes_to_score(), 1);
+
+    sim.step(1.0);
+    assert_eq!(sim.score, 1);
+    assert_eq!(sim.pipes_to_score(), 0);
+    assert!(!sim.pipes[0].is_passed(sim.bird.x));
+
+    // One that never was ahead of the bird isn't passed, however far behind
+    let mut behind = pipe(0.0, 225.0);
+    behind.was_ahead = false;
+    assert!(!behind.is_passed(sim.bird.x));
+}
+
+#[test]
+fn frames_to_next_pipe_counts_down_to_the_bird() {
+    let mut sim = hovering();
+    assert_eq!(sim.frames_to_next_pipe(), None);
+
+    let speed = sim.effective_speed();
+    let front = sim.bird.x + BIRD_SIZE / 2.0;
+    sim.pipes.push(pipe(front + 20.0 * speed, 225.0));
+    let frames = sim.frames_to_next_pipe().unwrap();
+    assert!((frames - 20.0).abs() < 0.01);
+
+    for _ in 0..20 {
+        sim.step(1.0);
+    }
+    assert!((sim.pipes[0].x - front).abs() < 0.01);
+    assert_eq!(sim.frames_to_next_pipe(), Some(0.0));
+    assert!(sim.death.is_none());
+}
+
+#[test]
+fn predicted_path_is_where_the_bird_goes() {
+    let mut config = SimConfig::new(Difficulty::Medium, 1, 800.0, 600.0);
+    config.gravity_curve = true;
+    let mut sim = Simulation::new(config);
+    sim.jump(0.0, 1.0);
+    let path = sim.predicted_path(40);
+    assert_eq!(path.len(), 40);
+    for &(_, y) in &path {
+        sim.step(1.0);
+        assert_eq!(sim.bird.y, y);
+    }
+}
+
+#[test]
+fn level_finished_waits_for_the_last_pipe() {
+    let mut sim = hovering();
+    sim.cheat_invincible = true;
+    let entry = LevelPipe {
+        gap_y: 225.0,
+        gap_height: 150.0,
+        bonus_gate: false,
+    };
+    sim.level = Some(Level {
+        name: String::new(),
+        pipes: vec![entry],
+        looped: false,
+    });
+    assert!(!sim.level_finished());
+    let mut events = Vec::new();
+    for _ in 0..1000 {
+        if sim.completed {
+            break;
+        }
+        assert!(!sim.level_finished());
+        events.extend(sim.step(1.0));
+    }
+    assert!(sim.completed && sim.level_finished());
+    assert_eq!(sim.score, 1);
+    assert!(events.ends_with(&[SimEvent::LevelComplete]));
+}